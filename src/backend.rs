@@ -0,0 +1,693 @@
+//! The compression backends.
+//!
+//! The backend selected through cargo features is used by default, see
+//! [`backend_info()`] for which one that is. [`Compress`] and [`Decompress`] can
+//! also be built on any other implementation of the [`DeflateBackend`] and
+//! [`InflateBackend`] traits, with [`Compress::with_backend`] and
+//! [`Decompress::with_backend`]. All of the `read`, `write` and gzip types
+//! accept such objects as well, so a custom backend can be used in the same
+//! binary as the default one.
+//!
+//! The built-in backends implement these traits too, with a compressor and a
+//! decompressor type for each backend whose feature is enabled, such as
+//! `MinizOxideDeflate` and `ZlibRsInflate`. This allows using a backend
+//! which isn't the default one, for instance zlib-rs to decompress and
+//! miniz_oxide to compress, when both features are enabled.
+//!
+//! # Examples
+//!
+//! A backend which counts the calls to the default backend:
+//!
+//! ```
+//! use std::io::prelude::*;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! use flate2::backend::{Backend, InflateBackend};
+//! use flate2::{Decompress, DecompressError, FlushDecompress, Status};
+//!
+//! #[derive(Debug)]
+//! struct Counting {
+//!     inner: Decompress,
+//!     calls: Arc<AtomicUsize>,
+//! }
+//!
+//! impl Backend for Counting {
+//!     fn total_in(&self) -> u64 {
+//!         self.inner.total_in()
+//!     }
+//!
+//!     fn total_out(&self) -> u64 {
+//!         self.inner.total_out()
+//!     }
+//! }
+//!
+//! impl InflateBackend for Counting {
+//!     fn decompress(
+//!         &mut self,
+//!         input: &[u8],
+//!         output: &mut [u8],
+//!         flush: FlushDecompress,
+//!     ) -> Result<Status, DecompressError> {
+//!         self.calls.fetch_add(1, Ordering::Relaxed);
+//!         self.inner.decompress(input, output, flush)
+//!     }
+//!
+//!     fn reset(&mut self, zlib_header: bool) {
+//!         self.inner.reset(zlib_header);
+//!     }
+//! }
+//!
+//! # let mut e = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+//! # e.write_all(b"Hello World").unwrap();
+//! # let compressed = e.finish().unwrap();
+//! let calls = Arc::new(AtomicUsize::new(0));
+//! let backend = Counting {
+//!     inner: Decompress::new(false),
+//!     calls: calls.clone(),
+//! };
+//! let decompress = Decompress::with_backend(backend, flate2::Format::Deflate);
+//! let mut decoder = flate2::read::DeflateDecoder::new_with_decompress(&compressed[..], decompress);
+//! let mut s = String::new();
+//! decoder.read_to_string(&mut s).unwrap();
+//! assert_eq!(s, "Hello World");
+//! assert!(calls.load(Ordering::Relaxed) > 0);
+//! ```
+//!
+//! With both the `miniz_oxide` and the `zlib-rs` feature, the backends can be
+//! picked for each stream:
+//!
+//! ```
+//! # #[cfg(all(feature = "miniz_oxide", feature = "zlib-rs"))]
+//! # fn main() -> std::io::Result<()> {
+//! use std::io::prelude::*;
+//!
+//! use flate2::backend::{MinizOxideDeflate, ZlibRsInflate};
+//! use flate2::{Compress, Decompress, Format};
+//!
+//! let backend = MinizOxideDeflate::new(&Compress::builder())?;
+//! let mut e = flate2::write::ZlibEncoder::new_with_compress(Vec::new(), Compress::with_backend(backend));
+//! e.write_all(b"Hello World")?;
+//! let compressed = e.finish()?;
+//!
+//! let backend = ZlibRsInflate::new(&Decompress::builder())?;
+//! let decompress = Decompress::with_backend(backend, Format::Zlib);
+//! let mut d = flate2::read::ZlibDecoder::new_with_decompress(&compressed[..], decompress);
+//! let mut s = String::new();
+//! d.read_to_string(&mut s)?;
+//! assert_eq!(s, "Hello World");
+//! # Ok(())
+//! # }
+//! # #[cfg(not(all(feature = "miniz_oxide", feature = "zlib-rs")))]
+//! # fn main() {}
+//! ```
+//!
+//! [`Compress`]: crate::Compress
+//! [`Decompress`]: crate::Decompress
+//! [`Compress::with_backend`]: crate::Compress::with_backend
+//! [`Decompress::with_backend`]: crate::Decompress::with_backend
+
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+use crate::ffi;
+use crate::mem::{
+    CompressBuilder, CompressError, DecompressBuilder, DecompressError, DecompressPosition,
+    ErrorKind, FlushCompress, FlushDecompress, Format, Status, Strategy,
+};
+use crate::Compression;
+
+/// Returns information about the backend which this crate was built with.
+///
+/// The backend is selected through cargo features, and another crate in the
+/// dependency graph may enable a different one than the application asked
+/// for. This reports which backend is actually in use, e.g. to log it or to
+/// pick a code path at runtime.
+///
+/// # Examples
+///
+/// ```
+/// let backend = flate2::backend_info();
+/// println!("using {}", backend.name());
+/// if let Some(version) = backend.version() {
+///     println!("version {}", version);
+/// }
+/// assert!(backend.capabilities().gzip());
+/// ```
+pub fn backend_info() -> BackendInfo {
+    ffi::backend_info()
+}
+
+/// The name, version and capabilities of a backend, as returned by
+/// [`backend_info`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BackendInfo {
+    pub(crate) name: &'static str,
+    pub(crate) version: Option<&'static str>,
+    pub(crate) capabilities: Capabilities,
+}
+
+impl BackendInfo {
+    /// Returns the name of the backend: `"miniz_oxide"`, `"zlib-rs"`,
+    /// `"zlib"` or `"zlib-ng"`.
+    ///
+    /// zlib-ng is also reported if it's used through the `zlib-ng-compat`
+    /// feature, or if another crate made `libz-sys` build it.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the version of the backend, if it's known.
+    ///
+    /// For the C backends this is the version of the library that is linked,
+    /// as reported by `zlibVersion`. Cargo doesn't tell which version of a
+    /// Rust dependency was built, so it's `None` for the Rust backends.
+    pub fn version(&self) -> Option<&'static str> {
+        self.version
+    }
+
+    /// Returns which of the optional features the backend supports.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
+
+/// The optional features supported by a backend, as returned by
+/// [`BackendInfo::capabilities`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    pub(crate) window_bits: bool,
+    pub(crate) dictionary: bool,
+    pub(crate) set_level: bool,
+    pub(crate) gzip: bool,
+    pub(crate) strategies: bool,
+    pub(crate) mem_level: bool,
+    pub(crate) try_clone: bool,
+    pub(crate) block_boundaries: bool,
+    pub(crate) prime: bool,
+}
+
+impl Capabilities {
+    /// The capabilities of the backends which are fully zlib compatible.
+    #[cfg(feature = "any_c_zlib")]
+    pub(crate) const ZLIB: Capabilities = Capabilities {
+        window_bits: true,
+        dictionary: true,
+        set_level: true,
+        gzip: true,
+        strategies: true,
+        mem_level: true,
+        try_clone: true,
+        block_boundaries: true,
+        prime: true,
+    };
+
+    /// Whether a window size other than the default can be used, see
+    /// [`Compress::new_with_window_bits`](crate::Compress::new_with_window_bits)
+    /// and [`Decompress::new_with_window_bits`](crate::Decompress::new_with_window_bits).
+    pub fn window_bits(&self) -> bool {
+        self.window_bits
+    }
+
+    /// Whether preset dictionaries are supported, see
+    /// [`Compress::set_dictionary`](crate::Compress::set_dictionary) and
+    /// [`Decompress::set_dictionary`](crate::Decompress::set_dictionary).
+    pub fn dictionary(&self) -> bool {
+        self.dictionary
+    }
+
+    /// Whether the compression level can be changed in the middle of a
+    /// stream, see [`Compress::set_level`](crate::Compress::set_level).
+    pub fn set_level(&self) -> bool {
+        self.set_level
+    }
+
+    /// Whether [`Compress`](crate::Compress) and
+    /// [`Decompress`](crate::Decompress) can read and write gzip framing
+    /// themselves, see [`Compress::new_gzip`](crate::Compress::new_gzip).
+    ///
+    /// The gzip types of this crate work with every backend.
+    pub fn gzip(&self) -> bool {
+        self.gzip
+    }
+
+    /// Whether all compression strategies are supported, see [`Strategy`].
+    pub fn strategies(&self) -> bool {
+        self.strategies
+    }
+
+    /// Whether a memory level other than the default can be used, see
+    /// [`CompressBuilder::mem_level`](crate::CompressBuilder::mem_level).
+    pub fn mem_level(&self) -> bool {
+        self.mem_level
+    }
+
+    /// Whether the state of a stream can be copied, see
+    /// [`Compress::try_clone`](crate::Compress::try_clone) and
+    /// [`Decompress::try_clone`](crate::Decompress::try_clone).
+    pub fn try_clone(&self) -> bool {
+        self.try_clone
+    }
+
+    /// Whether the decompressor reports the block boundaries at which
+    /// [`FlushDecompress::Block`] stops, see
+    /// [`Decompress::position`](crate::Decompress::position).
+    pub fn block_boundaries(&self) -> bool {
+        self.block_boundaries
+    }
+
+    /// Whether bits can be inserted anywhere in a stream, see
+    /// [`Compress::prime`](crate::Compress::prime) and
+    /// [`Decompress::prime`](crate::Decompress::prime).
+    ///
+    /// The miniz_oxide backend only supports `Decompress::prime` at the start
+    /// of a raw deflate stream and at block boundaries, which is enough to
+    /// resume decompression at a block boundary, and reports `false`.
+    pub fn prime(&self) -> bool {
+        self.prime
+    }
+}
+
+fn initialize_buffer(output: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    // SAFETY: Here we zero-initialize the output and cast it to [u8]
+    unsafe {
+        output.as_mut_ptr().write_bytes(0, output.len());
+        &mut *(output as *mut [MaybeUninit<u8>] as *mut [u8])
+    }
+}
+
+fn decompress_unsupported<T>() -> Result<T, DecompressError> {
+    Err(DecompressError::new(
+        ErrorKind::Unsupported,
+        Some("not supported by this backend"),
+    ))
+}
+
+fn compress_unsupported<T>() -> Result<T, CompressError> {
+    Err(CompressError::new(
+        ErrorKind::Unsupported,
+        Some("not supported by this backend"),
+    ))
+}
+
+/// The state shared by compression and decompression backends.
+///
+/// Backends must be `Send` and `Sync`, as well as `UnwindSafe` and
+/// `RefUnwindSafe`, as [`Compress`](crate::Compress) and
+/// [`Decompress`](crate::Decompress) are.
+pub trait Backend: Send + Sync + UnwindSafe + RefUnwindSafe + fmt::Debug {
+    /// Returns the total number of input bytes which have been processed.
+    fn total_in(&self) -> u64;
+
+    /// Returns the total number of output bytes which have been produced.
+    fn total_out(&self) -> u64;
+
+    /// Returns the number of bytes of memory held by the internal state, see
+    /// [`Compress::memory_usage`](crate::Compress::memory_usage).
+    ///
+    /// The default implementation returns 0.
+    fn memory_usage(&self) -> usize {
+        0
+    }
+}
+
+/// A decompressor which [`Decompress`](crate::Decompress) can be built on,
+/// see [`Decompress::with_backend`](crate::Decompress::with_backend).
+///
+/// The methods have the semantics of the methods of `Decompress` with the
+/// same name, which calls them after handling its own parameters. The
+/// optional methods have default implementations which return an error of
+/// kind [`ErrorKind::Unsupported`].
+pub trait InflateBackend: Backend {
+    /// Decompresses the input data into the output, see
+    /// [`Decompress::decompress`](crate::Decompress::decompress).
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushDecompress,
+    ) -> Result<Status, DecompressError>;
+
+    /// Decompresses the input data into an uninitialized output.
+    ///
+    /// The default implementation zero-initializes the output and calls
+    /// [`InflateBackend::decompress`].
+    fn decompress_uninit(
+        &mut self,
+        input: &[u8],
+        output: &mut [MaybeUninit<u8>],
+        flush: FlushDecompress,
+    ) -> Result<Status, DecompressError> {
+        self.decompress(input, initialize_buffer(output), flush)
+    }
+
+    /// Resets the decompressor for a new stream, which has a zlib header if
+    /// `zlib_header` is set and is a raw deflate stream otherwise.
+    fn reset(&mut self, zlib_header: bool);
+
+    /// Resets the decompressor for a new stream in the given format.
+    ///
+    /// The default implementation calls [`InflateBackend::reset`], with a
+    /// zlib header for both the zlib and the gzip format.
+    fn reset_format(&mut self, format: Format) {
+        self.reset(format != Format::Deflate);
+    }
+
+    /// Sets the preset dictionary of the stream and returns its Adler-32
+    /// checksum.
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
+        let _ = dictionary;
+        decompress_unsupported()
+    }
+
+    /// Returns the data which the decompressor currently uses as its
+    /// dictionary.
+    ///
+    /// The default implementation returns an empty dictionary.
+    fn get_dictionary(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Skips the input until a possible full flush point, see
+    /// [`Decompress::sync`](crate::Decompress::sync).
+    fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+        let _ = input;
+        decompress_unsupported()
+    }
+
+    /// Returns where the decompressor stopped in its input, see
+    /// [`Decompress::position`](crate::Decompress::position).
+    ///
+    /// The default implementation never reports a block boundary.
+    fn position(&self) -> DecompressPosition {
+        DecompressPosition::default()
+    }
+
+    /// Feeds the low `bits` bits of `value` to the decompressor ahead of the
+    /// next input, with `bits` at most 16.
+    fn prime(&mut self, bits: u8, value: u16) -> Result<(), DecompressError> {
+        let _ = (bits, value);
+        decompress_unsupported()
+    }
+
+    /// Creates an independent copy of the decompressor.
+    fn try_clone(&mut self) -> Result<Box<dyn InflateBackend>, DecompressError> {
+        decompress_unsupported()
+    }
+}
+
+/// A compressor which [`Compress`](crate::Compress) can be built on, see
+/// [`Compress::with_backend`](crate::Compress::with_backend).
+///
+/// The methods have the semantics of the methods of `Compress` with the
+/// same name, which calls them after handling its own parameters. The
+/// optional methods have default implementations which return an error of
+/// kind [`ErrorKind::Unsupported`].
+pub trait DeflateBackend: Backend {
+    /// Compresses the input data into the output, see
+    /// [`Compress::compress`](crate::Compress::compress).
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushCompress,
+    ) -> Result<Status, CompressError>;
+
+    /// Compresses the input data into an uninitialized output.
+    ///
+    /// The default implementation zero-initializes the output and calls
+    /// [`DeflateBackend::compress`].
+    fn compress_uninit(
+        &mut self,
+        input: &[u8],
+        output: &mut [MaybeUninit<u8>],
+        flush: FlushCompress,
+    ) -> Result<Status, CompressError> {
+        self.compress(input, initialize_buffer(output), flush)
+    }
+
+    /// Resets the compressor for a new stream with the same parameters.
+    fn reset(&mut self);
+
+    /// Returns an upper bound on the size of the output of compressing
+    /// `input_len` bytes, see [`Compress::bound`](crate::Compress::bound).
+    ///
+    /// The default implementation returns the bound for any parameters,
+    /// [`compress_bound`](crate::compress_bound) for gzip framing.
+    fn bound(&self, input_len: usize) -> usize {
+        crate::compress_bound(input_len, crate::Format::Gzip)
+    }
+
+    /// Returns the current compression strategy.
+    ///
+    /// This is used to keep the strategy when only the level is changed with
+    /// [`Compress::set_level`](crate::Compress::set_level). The default
+    /// implementation returns [`Strategy::Default`].
+    fn strategy(&self) -> Strategy {
+        Strategy::Default
+    }
+
+    /// Changes the compression level and strategy, see
+    /// [`Compress::set_params`](crate::Compress::set_params).
+    fn set_params(&mut self, level: Compression, strategy: Strategy) -> Result<(), CompressError> {
+        let _ = (level, strategy);
+        compress_unsupported()
+    }
+
+    /// Sets the preset dictionary of the stream and returns its Adler-32
+    /// checksum.
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
+        let _ = dictionary;
+        compress_unsupported()
+    }
+
+    /// Inserts the low `bits` bits of `value` into the output, with `bits`
+    /// at most 16.
+    fn prime(&mut self, bits: u8, value: u16) -> Result<(), CompressError> {
+        let _ = (bits, value);
+        compress_unsupported()
+    }
+
+    /// Returns the data which the compressor currently uses as its
+    /// dictionary.
+    ///
+    /// The default implementation returns an empty dictionary.
+    fn get_dictionary(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Creates an independent copy of the compressor.
+    fn try_clone(&mut self) -> Result<Box<dyn DeflateBackend>, CompressError> {
+        compress_unsupported()
+    }
+}
+
+/// Defines the public types of a built-in backend, which wrap the types of
+/// its module in `ffi`.
+macro_rules! builtin_backend {
+    (
+        #[cfg($cfg:meta)]
+        $module:ident, $name:literal, $deflate:ident, $inflate:ident
+    ) => {
+        #[doc = concat!("The compressor of the ", $name, " backend.")]
+        ///
+        /// It can be passed to [`Compress::with_backend`](crate::Compress::with_backend)
+        /// in a binary which uses another backend by default.
+        #[cfg($cfg)]
+        #[derive(Debug)]
+        pub struct $deflate(ffi::$module::Deflate);
+
+        #[cfg($cfg)]
+        impl $deflate {
+            /// Creates a compressor with the parameters of `config`, with the
+            /// same errors as [`CompressBuilder::build`].
+            pub fn new(config: &CompressBuilder) -> Result<$deflate, CompressError> {
+                config.make(ffi::$module::Deflate::make).map($deflate)
+            }
+
+            /// Returns the name, version and capabilities of the backend.
+            pub fn info() -> BackendInfo {
+                ffi::$module::backend_info()
+            }
+        }
+
+        #[cfg($cfg)]
+        impl Backend for $deflate {
+            fn total_in(&self) -> u64 {
+                self.0.total_in()
+            }
+
+            fn total_out(&self) -> u64 {
+                self.0.total_out()
+            }
+
+            fn memory_usage(&self) -> usize {
+                self.0.memory_usage()
+            }
+        }
+
+        #[cfg($cfg)]
+        impl DeflateBackend for $deflate {
+            fn compress(
+                &mut self,
+                input: &[u8],
+                output: &mut [u8],
+                flush: FlushCompress,
+            ) -> Result<Status, CompressError> {
+                self.0.compress(input, output, flush)
+            }
+
+            fn compress_uninit(
+                &mut self,
+                input: &[u8],
+                output: &mut [MaybeUninit<u8>],
+                flush: FlushCompress,
+            ) -> Result<Status, CompressError> {
+                self.0.compress_uninit(input, output, flush)
+            }
+
+            fn reset(&mut self) {
+                DeflateBackend::reset(&mut self.0)
+            }
+
+            fn bound(&self, input_len: usize) -> usize {
+                self.0.bound(input_len)
+            }
+
+            fn strategy(&self) -> Strategy {
+                self.0.strategy()
+            }
+
+            fn set_params(
+                &mut self,
+                level: Compression,
+                strategy: Strategy,
+            ) -> Result<(), CompressError> {
+                self.0.set_params(level, strategy)
+            }
+
+            fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
+                DeflateBackend::set_dictionary(&mut self.0, dictionary)
+            }
+
+            fn prime(&mut self, bits: u8, value: u16) -> Result<(), CompressError> {
+                DeflateBackend::prime(&mut self.0, bits, value)
+            }
+
+            fn get_dictionary(&self) -> Vec<u8> {
+                DeflateBackend::get_dictionary(&self.0)
+            }
+
+            fn try_clone(&mut self) -> Result<Box<dyn DeflateBackend>, CompressError> {
+                DeflateBackend::try_clone(&mut self.0)
+            }
+        }
+
+        #[doc = concat!("The decompressor of the ", $name, " backend.")]
+        ///
+        /// It can be passed to [`Decompress::with_backend`](crate::Decompress::with_backend)
+        /// in a binary which uses another backend by default, along with the
+        /// format it was created for.
+        #[cfg($cfg)]
+        #[derive(Debug)]
+        pub struct $inflate(ffi::$module::Inflate);
+
+        #[cfg($cfg)]
+        impl $inflate {
+            /// Creates a decompressor with the parameters of `config`, with
+            /// the same errors as [`DecompressBuilder::build`].
+            pub fn new(config: &DecompressBuilder) -> Result<$inflate, DecompressError> {
+                config.make(ffi::$module::Inflate::make).map($inflate)
+            }
+
+            /// Returns the name, version and capabilities of the backend.
+            pub fn info() -> BackendInfo {
+                ffi::$module::backend_info()
+            }
+        }
+
+        #[cfg($cfg)]
+        impl Backend for $inflate {
+            fn total_in(&self) -> u64 {
+                self.0.total_in()
+            }
+
+            fn total_out(&self) -> u64 {
+                self.0.total_out()
+            }
+
+            fn memory_usage(&self) -> usize {
+                self.0.memory_usage()
+            }
+        }
+
+        #[cfg($cfg)]
+        impl InflateBackend for $inflate {
+            fn decompress(
+                &mut self,
+                input: &[u8],
+                output: &mut [u8],
+                flush: FlushDecompress,
+            ) -> Result<Status, DecompressError> {
+                self.0.decompress(input, output, flush)
+            }
+
+            fn decompress_uninit(
+                &mut self,
+                input: &[u8],
+                output: &mut [MaybeUninit<u8>],
+                flush: FlushDecompress,
+            ) -> Result<Status, DecompressError> {
+                self.0.decompress_uninit(input, output, flush)
+            }
+
+            fn reset(&mut self, zlib_header: bool) {
+                InflateBackend::reset(&mut self.0, zlib_header)
+            }
+
+            fn reset_format(&mut self, format: Format) {
+                self.0.reset_format(format)
+            }
+
+            fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
+                InflateBackend::set_dictionary(&mut self.0, dictionary)
+            }
+
+            fn get_dictionary(&self) -> Vec<u8> {
+                InflateBackend::get_dictionary(&self.0)
+            }
+
+            fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+                self.0.sync(input)
+            }
+
+            fn position(&self) -> DecompressPosition {
+                self.0.position()
+            }
+
+            fn prime(&mut self, bits: u8, value: u16) -> Result<(), DecompressError> {
+                InflateBackend::prime(&mut self.0, bits, value)
+            }
+
+            fn try_clone(&mut self) -> Result<Box<dyn InflateBackend>, DecompressError> {
+                InflateBackend::try_clone(&mut self.0)
+            }
+        }
+    };
+}
+
+builtin_backend! {
+    #[cfg(feature = "miniz_oxide")]
+    miniz_oxide, "miniz_oxide", MinizOxideDeflate, MinizOxideInflate
+}
+
+builtin_backend! {
+    #[cfg(feature = "zlib-rs")]
+    zlib_rs, "zlib-rs", ZlibRsDeflate, ZlibRsInflate
+}
+
+builtin_backend! {
+    #[cfg(feature = "any_c_zlib")]
+    c, "zlib or zlib-ng", ZlibDeflate, ZlibInflate
+}
@@ -0,0 +1,366 @@
+//! Reading and writing the blocked gzip format, BGZF.
+//!
+//! [BGZF] is the format of the compressed files of genomics tools such as
+//! samtools and tabix. A BGZF file is a gzip file with many members, the
+//! *blocks*, each of which holds at most 64 KiB of data and records its own
+//! compressed size in a `BC` subfield of the gzip extra field. It ends with an
+//! empty block, which tells a complete file apart from a truncated one. Since
+//! it's still a multi-member gzip file, it can be read by any gzip decoder,
+//! such as [`MultiGzDecoder`](crate::read::MultiGzDecoder).
+//!
+//! A position in the decompressed data is addressed by a [`VirtualOffset`],
+//! made of the offset of a block in the file and of the offset in its data,
+//! which allows to seek to any position without decompressing everything
+//! before it.
+//!
+//! [BGZF]: https://samtools.github.io/hts-specs/SAMv1.pdf
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::prelude::*;
+//! use std::io::Cursor;
+//! use flate2::bgzf::{Reader, Writer};
+//! use flate2::Compression;
+//!
+//! let mut w = Writer::new(Vec::new(), Compression::default());
+//! w.write_all(b"first line\n").unwrap();
+//! let offset = w.virtual_position().unwrap();
+//! w.write_all(b"second line\n").unwrap();
+//! let compressed = w.finish().unwrap();
+//!
+//! let mut r = Reader::new(Cursor::new(compressed));
+//! r.seek_virtual(offset).unwrap();
+//! let mut line = String::new();
+//! r.read_line(&mut line).unwrap();
+//! assert_eq!(line, "second line\n");
+//! ```
+
+use std::fmt;
+
+mod read;
+mod write;
+
+pub use self::read::Reader;
+#[cfg(feature = "parallel")]
+pub(crate) use self::write::writer;
+pub use self::write::Writer;
+
+/// The most data in a block.
+const MAX_DATA: usize = 64 * 1024;
+
+/// The most data the writer puts in a block, which leaves room for the
+/// compressed data of any block within the 64 KiB a block can take up.
+const WRITE_DATA: usize = 0xff00;
+
+/// The largest block.
+const MAX_BLOCK: usize = 64 * 1024;
+
+/// The size of the header of a block, up to its compressed data.
+const HEADER_SIZE: usize = 18;
+
+/// The empty block at the end of a file.
+const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// A position in the decompressed data of a BGZF file.
+///
+/// A virtual offset is made of the offset of a block in the compressed file
+/// and of the offset in the decompressed data of that block. It's usually
+/// stored as a single integer, the offset of the block shifted left by 16 bits
+/// and the offset in the block, which the `From` conversions translate.
+///
+/// Virtual offsets compare like the positions they point to, although the end
+/// of a block and the start of the next one are two offsets for the same
+/// position.
+#[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtualOffset(u64);
+
+impl VirtualOffset {
+    /// Creates the virtual offset of `data_offset` bytes into the data of the
+    /// block at `block_offset` in the compressed file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_offset` doesn't fit in 48 bits.
+    pub fn new(block_offset: u64, data_offset: u16) -> VirtualOffset {
+        assert!(
+            block_offset >> 48 == 0,
+            "the block offset must fit in 48 bits"
+        );
+        VirtualOffset(block_offset << 16 | u64::from(data_offset))
+    }
+
+    /// Returns the offset of the block in the compressed file.
+    pub fn block_offset(&self) -> u64 {
+        self.0 >> 16
+    }
+
+    /// Returns the offset in the decompressed data of the block.
+    pub fn data_offset(&self) -> u16 {
+        self.0 as u16
+    }
+}
+
+impl From<u64> for VirtualOffset {
+    fn from(offset: u64) -> VirtualOffset {
+        VirtualOffset(offset)
+    }
+}
+
+impl From<VirtualOffset> for u64 {
+    fn from(offset: VirtualOffset) -> u64 {
+        offset.0
+    }
+}
+
+impl fmt::Debug for VirtualOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.block_offset(), self.data_offset())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::io::prelude::*;
+    use std::io::{Cursor, SeekFrom};
+
+    use super::*;
+    use crate::read::{GzDecoder, MultiGzDecoder};
+    use crate::Compression;
+    #[cfg(feature = "parallel")]
+    use crate::ParBuilder;
+
+    fn lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| format!("line {} of the data\n", i).into_bytes())
+            .collect()
+    }
+
+    /// The offsets of all blocks of a file, and the size of their data.
+    fn blocks(compressed: &[u8]) -> Vec<(usize, usize)> {
+        let mut blocks = Vec::new();
+        let mut pos = 0;
+        while pos < compressed.len() {
+            let bsize = u16::from_le_bytes([compressed[pos + 16], compressed[pos + 17]]);
+            let end = pos + usize::from(bsize) + 1;
+            let isize = u32::from_le_bytes(compressed[end - 4..end].try_into().unwrap());
+            blocks.push((pos, isize as usize));
+            pos = end;
+        }
+        blocks
+    }
+
+    #[test]
+    fn format() {
+        let data = lines(20_000);
+        let mut w = Writer::new(Vec::new(), Compression::default());
+        w.write_all(&data).unwrap();
+        let compressed = w.finish().unwrap();
+
+        assert!(compressed.ends_with(&EOF_BLOCK));
+        let blocks = blocks(&compressed);
+        assert_eq!(blocks.last(), Some(&(compressed.len() - 28, 0)));
+        for &(pos, len) in &blocks[..blocks.len() - 1] {
+            assert_eq!(
+                compressed[pos..pos + 16],
+                [31, 139, 8, 4, 0, 0, 0, 0, 0, 255, 6, 0, b'B', b'C', 2, 0]
+            );
+            assert!(len <= WRITE_DATA);
+        }
+        let sizes: usize = blocks.iter().map(|&(_, len)| len).sum();
+        assert_eq!(sizes, data.len());
+
+        let mut out = Vec::new();
+        MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+
+        let mut w = Writer::new(Vec::new(), Compression::default());
+        w.try_finish().unwrap();
+        crate::assert_write_after_finish(w.write(b"more").unwrap_err());
+        assert_eq!(w.total_in(), 0);
+
+        // Only the end of file block.
+        let empty = Writer::new(Vec::new(), Compression::default());
+        assert_eq!(empty.finish().unwrap(), EOF_BLOCK);
+        let mut out = Vec::new();
+        GzDecoder::new(&EOF_BLOCK[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn incompressible_blocks() {
+        let mut data = Vec::new();
+        let mut seed = 1u32;
+        while data.len() < 3 * MAX_DATA {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            data.extend_from_slice(&seed.to_le_bytes());
+        }
+        for level in [Compression::none(), Compression::best()] {
+            let mut w = Writer::new(Vec::new(), level);
+            w.write_all(&data).unwrap();
+            let compressed = w.finish().unwrap();
+
+            let mut out = Vec::new();
+            Reader::new(&compressed[..]).read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn threads() {
+        let data = lines(50_000);
+        let mut w = Writer::new(Vec::new(), Compression::fast());
+        w.write_all(&data).unwrap();
+        let single = w.finish().unwrap();
+
+        // The same blocks no matter the threads.
+        for threads in [1, 4] {
+            let mut w = ParBuilder::new()
+                .threads(threads)
+                .write_bgzf(Vec::new(), Compression::fast());
+            for part in data.chunks(100_000) {
+                w.write_all(part).unwrap();
+            }
+            assert_eq!(w.finish().unwrap(), single);
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let data = lines(30_000);
+        #[cfg(feature = "parallel")]
+        let mut w = ParBuilder::new()
+            .threads(2)
+            .write_bgzf(Vec::new(), Compression::default());
+        #[cfg(not(feature = "parallel"))]
+        let mut w = Writer::new(Vec::new(), Compression::default());
+        let mut offsets = Vec::new();
+        for (i, line) in data.split_inclusive(|&b| b == b'\n').enumerate() {
+            if i % 997 == 0 {
+                offsets.push((w.virtual_position().unwrap(), line.to_vec()));
+            }
+            w.write_all(line).unwrap();
+        }
+        let end = w.virtual_position().unwrap();
+        let compressed = w.finish().unwrap();
+
+        let mut r = Reader::new(Cursor::new(&compressed));
+        for (offset, line) in offsets.iter().rev() {
+            r.seek_virtual(*offset).unwrap();
+            assert_eq!(r.virtual_position(), *offset);
+            let mut read = Vec::new();
+            r.read_until(b'\n', &mut read).unwrap();
+            assert_eq!(&read, line);
+        }
+
+        // The positions of the reader are those of the writer.
+        let mut r = Reader::new(Cursor::new(&compressed));
+        let mut read = Vec::new();
+        for (offset, line) in &offsets {
+            while r.virtual_position() < *offset {
+                read.clear();
+                r.read_until(b'\n', &mut read).unwrap();
+            }
+            assert_eq!(r.virtual_position(), *offset);
+            read.clear();
+            r.read_until(b'\n', &mut read).unwrap();
+            assert_eq!(&read, line);
+        }
+
+        // Seeking through the `Seek` trait.
+        let (offset, line) = &offsets[offsets.len() / 2];
+        let pos = r.seek(SeekFrom::Start(u64::from(*offset))).unwrap();
+        assert_eq!(pos, u64::from(*offset));
+        assert_eq!(r.stream_position().unwrap(), pos);
+        let mut read = vec![0; line.len()];
+        r.read_exact(&mut read).unwrap();
+        assert_eq!(&read, line);
+        assert!(r.seek(SeekFrom::Current(1)).is_err());
+
+        r.seek_virtual(end).unwrap();
+        assert_eq!(r.read(&mut [0; 16]).unwrap(), 0);
+    }
+
+    #[test]
+    fn retry() {
+        let data = lines(20_000);
+        let retry = |mut w: Writer<crate::FlakyWriter>| {
+            let mut rest = &data[..];
+            while !rest.is_empty() {
+                if let Ok(n) = w.write(rest) {
+                    rest = &rest[n..];
+                }
+            }
+            while w.flush().is_err() {}
+            let offset = loop {
+                if let Ok(offset) = w.virtual_position() {
+                    break offset;
+                }
+            };
+            assert_eq!(offset.block_offset(), w.total_out());
+            while w.try_finish().is_err() {}
+            crate::assert_write_after_finish(w.write(b"more").unwrap_err());
+            assert_eq!(w.total_in(), data.len() as u64);
+            assert_eq!(w.total_out(), w.get_ref().data.len() as u64);
+
+            let compressed = w.finish().unwrap().data;
+            let mut out = Vec::new();
+            Reader::new(&compressed[..]).read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        };
+        retry(Writer::new(
+            crate::FlakyWriter::default(),
+            Compression::fast(),
+        ));
+        #[cfg(feature = "parallel")]
+        retry(
+            ParBuilder::new()
+                .threads(4)
+                .write_bgzf(crate::FlakyWriter::default(), Compression::fast()),
+        );
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut w = Writer::new(Vec::new(), Compression::default());
+        w.write_all(&lines(10_000)).unwrap();
+        let compressed = w.finish().unwrap();
+        let read = |data: &[u8]| Reader::new(data).read_to_end(&mut Vec::new());
+
+        let mut bad = compressed.clone();
+        let end = blocks(&compressed)[1].0;
+        bad[end - 8] ^= 1;
+        assert!(read(&bad).is_err());
+
+        let mut bad = compressed.clone();
+        bad[12] = b'X';
+        assert!(read(&bad).is_err());
+
+        assert!(read(&compressed[..compressed.len() - 1]).is_err());
+        assert!(read(&compressed[..end + 10]).is_err());
+        // A file without the end of file block is fine.
+        assert!(read(&compressed[..compressed.len() - 28]).is_ok());
+    }
+
+    #[test]
+    fn virtual_offset() {
+        let offset = VirtualOffset::new(12345, 678);
+        assert_eq!(offset.block_offset(), 12345);
+        assert_eq!(offset.data_offset(), 678);
+        assert_eq!(u64::from(offset), 12345 << 16 | 678);
+        assert_eq!(VirtualOffset::from(12345 << 16 | 678), offset);
+        assert!(VirtualOffset::new(1, 0) > VirtualOffset::new(0, 65535));
+        assert_eq!(format!("{:?}", offset), "12345:678");
+    }
+}
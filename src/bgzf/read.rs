@@ -0,0 +1,259 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use super::{VirtualOffset, MAX_DATA};
+use crate::gz::gzip_error;
+use crate::{Decompress, ErrorKind, FlushDecompress, Status};
+
+fn bad_header() -> io::Error {
+    gzip_error(ErrorKind::InvalidHeader, "invalid BGZF block header")
+}
+
+fn corrupt() -> io::Error {
+    gzip_error(
+        ErrorKind::ChecksumMismatch,
+        "corrupt BGZF block does not have a matching checksum",
+    )
+}
+
+fn truncated() -> io::Error {
+    gzip_error(ErrorKind::TruncatedInput, "BGZF block ended early")
+}
+
+/// A reader of a [BGZF](super) file.
+///
+/// This structure implements [`BufRead`] over the decompressed data of all
+/// blocks, checking the CRC and size of each. If the underlying reader also
+/// implements [`Seek`], so does this one, but its positions are the
+/// [virtual offsets](VirtualOffset) of the file: only seeking to such an
+/// offset and querying the current one are supported. The underlying reader
+/// is expected to be at the start of the file when this reader is created.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::bgzf::{Reader, Writer};
+/// use flate2::Compression;
+///
+/// let mut w = Writer::new(Vec::new(), Compression::default());
+/// w.write_all(b"Hello World").unwrap();
+/// let compressed = w.finish().unwrap();
+///
+/// let mut s = String::new();
+/// Reader::new(&compressed[..]).read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "Hello World");
+/// ```
+pub struct Reader<R> {
+    inner: R,
+    decompress: Decompress,
+    // The compressed data of the current block.
+    buf: Vec<u8>,
+    // The decompressed data of the current block.
+    data: Vec<u8>,
+    pos: usize,
+    block_offset: u64,
+    next_block: u64,
+}
+
+impl<R> Reader<R> {
+    /// Creates a new reader of the BGZF file in `r`.
+    pub fn new(r: R) -> Reader<R> {
+        Reader {
+            inner: r,
+            decompress: Decompress::new(false),
+            buf: Vec::new(),
+            data: Vec::new(),
+            pos: 0,
+            block_offset: 0,
+            next_block: 0,
+        }
+    }
+
+    /// Returns the virtual offset of the next byte read.
+    ///
+    /// At the end of a block, this is the offset of the start of the next
+    /// one, like the reference implementation.
+    pub fn virtual_position(&self) -> VirtualOffset {
+        if self.pos == self.data.len() {
+            VirtualOffset::new(self.next_block, 0)
+        } else {
+            VirtualOffset::new(self.block_offset, self.pos as u16)
+        }
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this reader is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads and decompresses the next block, returning `false` at the end of
+    /// the file.
+    fn read_block(&mut self) -> io::Result<bool> {
+        let mut header = [0; 12];
+        let mut n = 0;
+        while n < header.len() {
+            match self.inner.read(&mut header[n..]) {
+                Ok(0) if n == 0 => return Ok(false),
+                Ok(0) => return Err(truncated()),
+                Ok(read) => n += read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        // Only the extra field is allowed, which holds the size of the block.
+        if header[..4] != [0x1f, 0x8b, 8, crate::gz::FEXTRA] {
+            return Err(bad_header());
+        }
+        let xlen = usize::from(u16::from_le_bytes([header[10], header[11]]));
+        self.buf.resize(xlen, 0);
+        self.inner
+            .read_exact(&mut self.buf)
+            .map_err(eof_truncated)?;
+
+        let mut extra = &self.buf[..];
+        let mut size = None;
+        while extra.len() >= 4 {
+            let len = usize::from(u16::from_le_bytes([extra[2], extra[3]]));
+            if extra[..2] == *b"BC" && len == 2 && extra.len() >= 6 {
+                size = Some(usize::from(u16::from_le_bytes([extra[4], extra[5]])) + 1);
+            }
+            extra = extra.get(4 + len..).unwrap_or_default();
+        }
+        // The rest of the block holds at least the trailer.
+        let rest = match size {
+            Some(size) if size >= header.len() + xlen + 8 => size - header.len() - xlen,
+            _ => return Err(bad_header()),
+        };
+        self.buf.resize(rest, 0);
+        self.inner
+            .read_exact(&mut self.buf)
+            .map_err(eof_truncated)?;
+
+        let (deflate, trailer) = self.buf.split_at(rest - 8);
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) as usize;
+        if len > MAX_DATA {
+            return Err(corrupt());
+        }
+        self.data.clear();
+        self.data.reserve(len + 1);
+        self.decompress.reset(false);
+        let status =
+            self.decompress
+                .decompress_vec(deflate, &mut self.data, FlushDecompress::Finish)?;
+        let mut check = crate::Crc::new();
+        check.update(&self.data);
+        if status != Status::StreamEnd || self.data.len() != len || check.sum() != crc {
+            return Err(corrupt());
+        }
+
+        self.pos = 0;
+        self.block_offset = self.next_block;
+        self.next_block += (header.len() + xlen + rest) as u64;
+        Ok(true)
+    }
+}
+
+fn eof_truncated(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        truncated()
+    } else {
+        err
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Seeks to the given virtual offset, which must point into the data of
+    /// a block or to its end.
+    pub fn seek_virtual(&mut self, offset: VirtualOffset) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset.block_offset()))?;
+        self.next_block = offset.block_offset();
+        self.data.clear();
+        self.pos = 0;
+
+        let data_offset = usize::from(offset.data_offset());
+        if data_offset > 0 {
+            if !self.read_block()? || data_offset > self.data.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "virtual offset beyond the end of a BGZF block",
+                ));
+            }
+            self.pos = data_offset;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Reader<R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let buf = self.fill_buf()?;
+        let n = cmp::min(buf.len(), into.len());
+        into[..n].copy_from_slice(&buf[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Empty blocks, like the one at the end, are skipped.
+        while self.pos == self.data.len() {
+            if !self.read_block()? {
+                break;
+            }
+        }
+        Ok(&self.data[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.data.len());
+    }
+}
+
+/// Seeks to virtual offsets, from [`SeekFrom::Start`]. The current virtual
+/// offset is returned for [`SeekFrom::Current`] with an offset of zero, while
+/// any other seek is an error.
+impl<R: Read + Seek> Seek for Reader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(offset) => self.seek_virtual(VirtualOffset::from(offset))?,
+            SeekFrom::Current(0) => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "BGZF files can only seek to virtual offsets",
+                ))
+            }
+        }
+        Ok(self.virtual_position().into())
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Reader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("inner", &self.inner)
+            .field("virtual_position", &self.virtual_position())
+            .finish()
+    }
+}
@@ -0,0 +1,401 @@
+use std::cmp;
+#[cfg(feature = "parallel")]
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::thread;
+
+use super::{VirtualOffset, EOF_BLOCK, HEADER_SIZE, MAX_BLOCK, WRITE_DATA};
+use crate::crc::Crc;
+use crate::mem::CompressError;
+#[cfg(feature = "parallel")]
+use crate::par::Pool;
+use crate::{Compress, Compression, FlushCompress, Status};
+
+/// The header of a block, up to its size.
+const HEADER: [u8; 16] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C', 0x02, 0x00,
+];
+
+/// The data of a block for the threads, and its compressed block in return.
+#[cfg(feature = "parallel")]
+struct Block {
+    index: u64,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) fn writer<W: Write>(w: W, level: Compression, threads: usize) -> Writer<W> {
+    let mut writer = Writer::new(w, level);
+    writer.threads = threads;
+    writer
+}
+
+/// Compresses `data` into a whole block.
+fn compress_block(compress: &mut Compress, data: &[u8]) -> Result<Vec<u8>, CompressError> {
+    compress.reset();
+    let mut block = Vec::with_capacity(MAX_BLOCK);
+    block.extend_from_slice(&HEADER);
+    block.extend_from_slice(&[0; 2]);
+    // The compressed data has to leave room for the trailer.
+    let mut output = Vec::with_capacity(MAX_BLOCK - HEADER_SIZE - 8);
+    let mut input = data;
+    loop {
+        let before = compress.total_in();
+        let status = compress.compress_vec(input, &mut output, FlushCompress::Finish)?;
+        input = &input[(compress.total_in() - before) as usize..];
+        if status == Status::StreamEnd {
+            block.extend_from_slice(&output);
+            break;
+        }
+        if output.len() == output.capacity() {
+            // Data which doesn't compress is stored, in a single stored block
+            // which always fits.
+            let len = data.len() as u16;
+            block.push(1);
+            block.extend_from_slice(&len.to_le_bytes());
+            block.extend_from_slice(&(!len).to_le_bytes());
+            block.extend_from_slice(data);
+            break;
+        }
+    }
+
+    let mut crc = Crc::new();
+    crc.update(data);
+    block.extend_from_slice(&crc.sum().to_le_bytes());
+    block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    let bsize = (block.len() - 1) as u16;
+    block[16..HEADER_SIZE].copy_from_slice(&bsize.to_le_bytes());
+    Ok(block)
+}
+
+/// A writer of a [BGZF](super) file.
+///
+/// The data is split into blocks of 65280 bytes, the same as the reference
+/// implementation, and the file is ended with the empty block once the writer
+/// is finished or dropped. A [flush](Write::flush) ends the current block
+/// early.
+///
+/// The blocks are compressed on the current thread, or with the `parallel`
+/// feature by a number of threads configured with `ParBuilder::write_bgzf`.
+/// Either way the blocks are the same.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::bgzf::Writer;
+/// use flate2::Compression;
+///
+/// let mut w = Writer::new(Vec::new(), Compression::default());
+/// w.write_all(b"Hello World").unwrap();
+/// let compressed = w.finish().unwrap();
+/// ```
+pub struct Writer<W: Write> {
+    obj: Option<W>,
+    level: Compression,
+    threads: usize,
+    compress: Option<Compress>,
+    #[cfg(feature = "parallel")]
+    pool: Option<Pool<Block, Block>>,
+    // The data of the block which is being filled.
+    block: Vec<u8>,
+    #[cfg(feature = "parallel")]
+    next_index: u64,
+    #[cfg(feature = "parallel")]
+    next_write: u64,
+    #[cfg(feature = "parallel")]
+    done: BTreeMap<u64, Vec<u8>>,
+    // The error of a block which failed to compress on another thread. The
+    // file can't be completed without the block, so it's returned by every
+    // later call.
+    #[cfg(feature = "parallel")]
+    failed: Option<CompressError>,
+    // The compressed data which is ready to be written out.
+    buf: Vec<u8>,
+    finished: bool,
+    total_in: u64,
+    total_out: u64,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer which compresses the blocks on the current thread
+    /// and writes them to `w`.
+    pub fn new(w: W, level: Compression) -> Writer<W> {
+        Writer {
+            obj: Some(w),
+            level,
+            threads: 1,
+            compress: None,
+            #[cfg(feature = "parallel")]
+            pool: None,
+            block: Vec::new(),
+            #[cfg(feature = "parallel")]
+            next_index: 0,
+            #[cfg(feature = "parallel")]
+            next_write: 0,
+            #[cfg(feature = "parallel")]
+            done: BTreeMap::new(),
+            #[cfg(feature = "parallel")]
+            failed: None,
+            buf: Vec::new(),
+            finished: false,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.obj.as_ref().unwrap()
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutating the output/input state of the stream may corrupt
+    /// this object, so care must be taken when using this method.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.obj.as_mut().unwrap()
+    }
+
+    /// Returns the number of bytes which were written to this writer.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Returns the number of compressed bytes which were written to the
+    /// underlying writer.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Returns the virtual offset of the next byte written, for an index of
+    /// the file.
+    ///
+    /// The offset of the block isn't known before the previous blocks are
+    /// compressed, so this waits for the blocks which are compressed on other
+    /// threads.
+    pub fn virtual_position(&mut self) -> io::Result<VirtualOffset> {
+        self.check_failed()?;
+        if self.block.len() == WRITE_DATA {
+            self.submit()?;
+        }
+        self.drain()?;
+        Ok(VirtualOffset::new(self.total_out, self.block.len() as u16))
+    }
+
+    /// Writes out the compressed data which is ready, keeping what wasn't
+    /// written if the writer fails.
+    fn dump(&mut self) -> io::Result<()> {
+        while !self.buf.is_empty() {
+            let n = self.obj.as_mut().unwrap().write(&self.buf)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.buf.drain(..n);
+            self.total_out += n as u64;
+        }
+        Ok(())
+    }
+
+    /// Returns the error of a block which failed to compress, if any.
+    fn check_failed(&self) -> io::Result<()> {
+        #[cfg(feature = "parallel")]
+        if let Some(err) = &self.failed {
+            return Err(err.clone().into());
+        }
+        Ok(())
+    }
+
+    /// Compresses the current block, or hands it to the threads while waiting
+    /// for earlier blocks if too many are in flight. The block is only taken
+    /// if this succeeds.
+    fn submit(&mut self) -> io::Result<()> {
+        #[cfg(feature = "parallel")]
+        if self.threads > 1 {
+            return self.send();
+        }
+        let level = self.level;
+        let compress = self
+            .compress
+            .get_or_insert_with(|| Compress::new(level, false));
+        let block = compress_block(compress, &self.block)?;
+        self.block.clear();
+        self.buf.extend_from_slice(&block);
+        self.dump()
+    }
+
+    #[cfg(feature = "parallel")]
+    fn send(&mut self) -> io::Result<()> {
+        let max_in_flight = 2 * self.threads as u64;
+        while self.next_index - self.next_write - self.done.len() as u64 >= max_in_flight {
+            self.receive()?;
+        }
+        let (threads, level) = (self.threads, self.level);
+        let pool = self.pool.get_or_insert_with(|| {
+            Pool::new(threads, level, |compress, block: Block| {
+                Ok(Block {
+                    index: block.index,
+                    data: compress_block(compress, &block.data)?,
+                })
+            })
+        });
+        pool.send(Block {
+            index: self.next_index,
+            data: self.block.clone(),
+        })?;
+        self.block.clear();
+        self.next_index += 1;
+        Ok(())
+    }
+
+    /// Waits for the next compressed block, and writes out the blocks which
+    /// are next in order.
+    #[cfg(feature = "parallel")]
+    fn receive(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        let block = match self.pool.as_ref().unwrap().recv()? {
+            Ok(block) => block,
+            Err(err) => {
+                self.failed = Some(err.clone());
+                return Err(err.into());
+            }
+        };
+        self.done.insert(block.index, block.data);
+        while let Some(block) = self.done.remove(&self.next_write) {
+            self.buf.extend_from_slice(&block);
+            self.next_write += 1;
+        }
+        self.dump()
+    }
+
+    /// Waits until all submitted blocks were written out.
+    fn drain(&mut self) -> io::Result<()> {
+        #[cfg(feature = "parallel")]
+        while self.next_write < self.next_index {
+            self.receive()?;
+        }
+        self.dump()
+    }
+
+    /// Attempts to finish the file, writing out the last block and the end of
+    /// file block.
+    ///
+    /// This is the same as [`Writer::finish`], except that the underlying
+    /// writer isn't consumed. Further writes return an error of kind
+    /// [`ErrorKind::StreamFinished`](crate::ErrorKind::StreamFinished).
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        if !self.finished {
+            if !self.block.is_empty() {
+                self.submit()?;
+            }
+            self.drain()?;
+            self.buf.extend_from_slice(&EOF_BLOCK);
+            self.finished = true;
+            #[cfg(feature = "parallel")]
+            {
+                self.pool = None;
+            }
+        }
+        self.dump()?;
+        self.get_mut().flush()
+    }
+
+    /// Finishes the file, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.obj.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(crate::mem::write_after_finish());
+        }
+        self.check_failed()?;
+        // A full block is only submitted with the next data, so that none of
+        // `buf` is taken if that fails.
+        if self.block.len() == WRITE_DATA {
+            self.submit()?;
+        }
+        let n = cmp::min(buf.len(), WRITE_DATA - self.block.len());
+        if self.block.capacity() == 0 {
+            self.block.reserve_exact(WRITE_DATA);
+        }
+        self.block.extend_from_slice(&buf[..n]);
+        self.total_in += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        if !self.finished && !self.block.is_empty() {
+            self.submit()?;
+        }
+        self.drain()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if self.obj.is_some() && self.check_failed().is_ok() && !thread::panicking() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for Writer<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("obj", &self.obj)
+            .field("level", &self.level)
+            .field("threads", &self.threads)
+            .field("total_in", &self.total_in)
+            .field("total_out", &self.total_out)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_block_poisons_the_writer() {
+        let data = vec![b'a'; 10 * WRITE_DATA];
+        let mut w = writer(Vec::new(), Compression::fast(), 2);
+        w.pool = Some(Pool::new(
+            2,
+            Compression::fast(),
+            |compress, block: Block| {
+                if block.index == 1 {
+                    let kind = crate::ErrorKind::Other;
+                    return Err(CompressError::new(kind, Some("failed block")));
+                }
+                Ok(Block {
+                    index: block.index,
+                    data: compress_block(compress, &block.data)?,
+                })
+            },
+        ));
+        let err = data
+            .chunks(10_000)
+            .find_map(|chunk| w.write_all(chunk).err())
+            .unwrap_or_else(|| w.flush().unwrap_err());
+        assert!(err.to_string().contains("failed block"), "{}", err);
+        for err in [
+            w.write(b"more").unwrap_err(),
+            w.flush().unwrap_err(),
+            w.virtual_position().unwrap_err(),
+            w.try_finish().unwrap_err(),
+        ] {
+            assert!(err.to_string().contains("failed block"), "{}", err);
+        }
+        // Dropping the writer doesn't wait for the block which never comes.
+        drop(w);
+    }
+}
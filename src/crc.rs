@@ -4,7 +4,7 @@ use std::io;
 use std::io::prelude::*;
 
 /// The CRC calculated by a [`CrcReader`].
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Crc {
     inner: inner::Crc,
 }
@@ -48,7 +48,7 @@ impl Crc {
 mod inner {
     use crc32fast::Hasher;
 
-    #[derive(Debug, Default)]
+    #[derive(Clone, Debug, Default)]
     pub struct Crc {
         amt: u32,
         hasher: Hasher,
@@ -87,7 +87,7 @@ mod inner {
 
 #[cfg(feature = "zlib-rs")]
 mod inner {
-    #[derive(Debug, Default)]
+    #[derive(Clone, Debug, Default)]
     pub struct Crc {
         consumed: u64,
         state: u32,
@@ -128,6 +128,28 @@ mod inner {
     }
 }
 
+/// Updates the Adler-32 checksum `adler` with `data`, with the implementation
+/// of the default backend.
+#[cfg(all(feature = "parallel", feature = "any_c_zlib"))]
+pub(crate) fn adler32(adler: u32, data: &[u8]) -> u32 {
+    use crate::ffi::{adler32, uInt, uLong};
+
+    data.chunks(uInt::MAX as usize).fold(adler, |adler, chunk| {
+        // SAFETY: the pointer and the length describe `chunk`.
+        unsafe { adler32(uLong::from(adler), chunk.as_ptr(), chunk.len() as uInt) as u32 }
+    })
+}
+
+#[cfg(all(feature = "parallel", feature = "zlib-rs", not(feature = "any_c_zlib")))]
+pub(crate) fn adler32(adler: u32, data: &[u8]) -> u32 {
+    zlib_rs::adler32::adler32(adler, data)
+}
+
+#[cfg(all(feature = "parallel", not(feature = "any_zlib")))]
+pub(crate) fn adler32(adler: u32, data: &[u8]) -> u32 {
+    miniz_oxide::mz_adler32_oxide(adler, data)
+}
+
 /// A wrapper around a [`Read`] that calculates the CRC.
 ///
 /// [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
@@ -3,7 +3,7 @@ use std::io::prelude::*;
 use std::mem;
 
 use crate::zio;
-use crate::{Compress, Decompress};
+use crate::{Compress, Decompress, DecompressLimits, Format};
 
 /// A DEFLATE encoder, or compressor.
 ///
@@ -41,6 +41,7 @@ use crate::{Compress, Decompress};
 pub struct DeflateEncoder<R> {
     obj: R,
     data: Compress,
+    pending_level: Option<crate::Compression>,
 }
 
 impl<R: BufRead> DeflateEncoder<R> {
@@ -50,6 +51,19 @@ impl<R: BufRead> DeflateEncoder<R> {
         DeflateEncoder {
             obj: r,
             data: Compress::new(level, false),
+            pending_level: None,
+        }
+    }
+
+    /// Creates a new encoder with the given `compression` settings which will
+    /// read uncompressed data from the given stream `r` and emit the compressed stream.
+    ///
+    /// The `compression` object should not produce a zlib or gzip header.
+    pub fn new_with_compress(r: R, compression: Compress) -> DeflateEncoder<R> {
+        DeflateEncoder {
+            obj: r,
+            data: compression,
+            pending_level: None,
         }
     }
 }
@@ -71,6 +85,15 @@ impl<R> DeflateEncoder<R> {
         mem::replace(&mut self.obj, r)
     }
 
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: crate::Compression) {
+        self.pending_level = Some(level);
+    }
+
     /// Acquires a reference to the underlying reader
     pub fn get_ref(&self) -> &R {
         &self.obj
@@ -108,7 +131,7 @@ impl<R> DeflateEncoder<R> {
 
 impl<R: BufRead> Read for DeflateEncoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        zio::read(&mut self.obj, &mut self.data, buf)
+        zio::read_encoder(&mut self.obj, &mut self.data, &mut self.pending_level, buf)
     }
 }
 
@@ -169,6 +192,16 @@ pub fn reset_decoder_data<R>(zlib: &mut DeflateDecoder<R>) {
     zlib.data.reset(false);
 }
 
+/// Resets the decoder for the next member of a gzip file, whose data still
+/// counts against the limits of the decoder.
+pub fn next_member_data<R>(zlib: &mut DeflateDecoder<R>) {
+    zlib.data.next_stream(Format::Deflate);
+}
+
+pub fn decoder_limits<R>(zlib: &DeflateDecoder<R>) -> DecompressLimits {
+    zlib.data.limits()
+}
+
 impl<R: BufRead> DeflateDecoder<R> {
     /// Creates a new decoder which will decompress data read from the given
     /// stream.
@@ -178,6 +211,15 @@ impl<R: BufRead> DeflateDecoder<R> {
             data: Decompress::new(false),
         }
     }
+
+    /// Creates a new decoder which will decompress data read from the given
+    /// stream, using the given `decompression` settings.
+    pub fn new_with_decompress(r: R, decompression: Decompress) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            obj: r,
+            data: decompression,
+        }
+    }
 }
 
 impl<R> DeflateDecoder<R> {
@@ -231,6 +273,14 @@ impl<R> DeflateDecoder<R> {
     pub fn total_out(&self) -> u64 {
         self.data.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.data.set_limits(limits);
+    }
 }
 
 impl<R: BufRead> Read for DeflateDecoder<R> {
@@ -8,8 +8,8 @@ mod tests {
 
     use rand::{rng, Rng};
 
-    use super::{read, write};
-    use crate::Compression;
+    use super::{bufread, read, write};
+    use crate::{Compress, Compression, Strategy};
 
     #[test]
     fn roundtrip() {
@@ -172,6 +172,73 @@ mod tests {
         }
     }
 
+    #[test]
+    fn roundtrip_strategies() {
+        let v = b"hello hello hello world world world! ".repeat(100);
+        for strategy in [
+            Strategy::Default,
+            Strategy::Filtered,
+            Strategy::HuffmanOnly,
+            Strategy::Rle,
+            Strategy::Fixed,
+        ] {
+            if strategy != Strategy::Default && !crate::backend_info().capabilities().strategies() {
+                continue;
+            }
+            let compress = Compress::new_with_strategy(Compression::default(), false, strategy);
+            let mut w = write::DeflateEncoder::new_with_compress(Vec::new(), compress);
+            w.write_all(&v).unwrap();
+            let written = w.finish().unwrap();
+
+            let compress = Compress::new_with_strategy(Compression::default(), false, strategy);
+            let mut r = read::DeflateEncoder::new_with_compress(&v[..], compress);
+            let mut read = Vec::new();
+            r.read_to_end(&mut read).unwrap();
+            assert_eq!(read, written);
+
+            let mut d = read::DeflateDecoder::new(&written[..]);
+            let mut out = Vec::new();
+            d.read_to_end(&mut out).unwrap();
+            assert_eq!(out, v, "strategy {strategy:?}");
+        }
+    }
+
+    #[test]
+    fn set_level_mid_stream() {
+        let v = (0..40_000).map(|i| i.to_string()).collect::<String>();
+        let v = v.as_bytes();
+        let (first, second) = v.split_at(v.len() / 2);
+
+        let mut w = write::DeflateEncoder::new(Vec::new(), Compression::best());
+        w.write_all(first).unwrap();
+        w.set_level(Compression::none()).unwrap();
+        let compressed_first = w.get_ref().len();
+        w.write_all(second).unwrap();
+        let written = w.finish().unwrap();
+        assert!(written.len() - compressed_first > second.len());
+
+        let mut d = read::DeflateDecoder::new(&written[..]);
+        let mut out = Vec::new();
+        d.read_to_end(&mut out).unwrap();
+        assert_eq!(out, v);
+
+        let reader = std::io::BufReader::with_capacity(1024, v);
+        let mut r = bufread::DeflateEncoder::new(reader, Compression::best());
+        let mut read = vec![0; 16];
+        let n = r.read(&mut read).unwrap();
+        read.truncate(n);
+        let consumed = r.total_in() as usize;
+        assert!(consumed < v.len());
+        r.set_level(Compression::none());
+        r.read_to_end(&mut read).unwrap();
+        assert!(read.len() > v.len() - consumed);
+
+        let mut d = read::DeflateDecoder::new(&read[..]);
+        let mut out = Vec::new();
+        d.read_to_end(&mut out).unwrap();
+        assert_eq!(out, v);
+    }
+
     #[test]
     fn qc_reader() {
         ::quickcheck::quickcheck(test as fn(_) -> _);
@@ -45,6 +45,16 @@ impl<R: Read> DeflateEncoder<R> {
             inner: bufread::DeflateEncoder::new(BufReader::new(r), level),
         }
     }
+
+    /// Creates a new encoder with the given `compression` settings which will
+    /// read uncompressed data from the given stream `r` and emit the compressed stream.
+    ///
+    /// The `compression` object should not produce a zlib or gzip header.
+    pub fn new_with_compress(r: R, compression: crate::Compress) -> DeflateEncoder<R> {
+        DeflateEncoder {
+            inner: bufread::DeflateEncoder::new_with_compress(BufReader::new(r), compression),
+        }
+    }
 }
 
 impl<R> DeflateEncoder<R> {
@@ -63,6 +73,15 @@ impl<R> DeflateEncoder<R> {
         self.inner.get_mut().reset(r)
     }
 
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: crate::Compression) {
+        self.inner.set_level(level)
+    }
+
     /// Acquires a reference to the underlying reader
     pub fn get_ref(&self) -> &R {
         self.inner.get_ref().get_ref()
@@ -176,6 +195,31 @@ impl<R: Read> DeflateDecoder<R> {
             inner: bufread::DeflateDecoder::new(BufReader::with_buf(buf, r)),
         }
     }
+
+    /// Creates a new decoder which will decompress data read from the given
+    /// stream `r`, along with `decompression` settings.
+    pub fn new_with_decompress(r: R, decompression: crate::Decompress) -> DeflateDecoder<R> {
+        DeflateDecoder::new_with_decompress_and_buf(r, vec![0; 32 * 1024], decompression)
+    }
+
+    /// Creates a new decoder which will decompress data read from the given
+    /// stream `r`, using `buf` as backing to speed up reading,
+    /// along with `decompression` settings to configure decoder.
+    ///
+    /// Note that the specified buffer will only be used up to its current
+    /// length. The buffer's capacity will also not grow over time.
+    pub fn new_with_decompress_and_buf(
+        r: R,
+        buf: Vec<u8>,
+        decompression: crate::Decompress,
+    ) -> DeflateDecoder<R> {
+        DeflateDecoder {
+            inner: bufread::DeflateDecoder::new_with_decompress(
+                BufReader::with_buf(buf, r),
+                decompression,
+            ),
+        }
+    }
 }
 
 impl<R> DeflateDecoder<R> {
@@ -228,6 +272,15 @@ impl<R> DeflateDecoder<R> {
     pub fn total_out(&self) -> u64 {
         self.inner.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see
+    /// [`DecompressLimits`](crate::DecompressLimits).
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: crate::DecompressLimits) {
+        self.inner.set_limits(limits);
+    }
 }
 
 impl<R: Read> Read for DeflateDecoder<R> {
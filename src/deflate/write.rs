@@ -2,7 +2,7 @@ use std::io;
 use std::io::prelude::*;
 
 use crate::zio;
-use crate::{Compress, Decompress};
+use crate::{Compress, Decompress, DecompressLimits};
 
 /// A DEFLATE encoder, or compressor.
 ///
@@ -43,6 +43,16 @@ impl<W: Write> DeflateEncoder<W> {
         }
     }
 
+    /// Creates a new encoder which will write compressed data to the stream
+    /// `w` with the given `compression` settings.
+    ///
+    /// The `compression` object should not produce a zlib or gzip header.
+    pub fn new_with_compress(w: W, compression: Compress) -> DeflateEncoder<W> {
+        DeflateEncoder {
+            inner: zio::Writer::new(w, compression),
+        }
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.inner.get_ref()
@@ -78,6 +88,22 @@ impl<W: Write> DeflateEncoder<W> {
         Ok(self.inner.replace(w))
     }
 
+    /// Changes the compression level used for the data written from now on.
+    ///
+    /// All data written so far is compressed with the previous level and
+    /// written out to the underlying writer first, much like a call to
+    /// `flush` which doesn't flush the underlying writer itself.
+    ///
+    /// # Errors
+    ///
+    /// This function will perform I/O to write out the data compressed so
+    /// far, and any I/O errors which occur will be returned from this
+    /// function. An error is also returned if the backend doesn't support
+    /// the change.
+    pub fn set_level(&mut self, level: crate::Compression) -> io::Result<()> {
+        self.inner.set_level(level)
+    }
+
     /// Attempt to finish this output stream, writing out final chunks of data.
     ///
     /// Note that this function can only be used once data has finished being
@@ -220,6 +246,17 @@ impl<W: Write> DeflateDecoder<W> {
         }
     }
 
+    /// Creates a new decoder which will write uncompressed data to the stream `w`
+    /// using the given `decompression` settings.
+    ///
+    /// When this decoder is dropped or unwrapped the final pieces of data will
+    /// be flushed.
+    pub fn new_with_decompress(w: W, decompression: Decompress) -> DeflateDecoder<W> {
+        DeflateDecoder {
+            inner: zio::Writer::new(w, decompression),
+        }
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.inner.get_ref()
@@ -250,7 +287,7 @@ impl<W: Write> DeflateDecoder<W> {
     /// returns an error then that will be returned from this function.
     pub fn reset(&mut self, w: W) -> io::Result<W> {
         self.inner.finish()?;
-        self.inner.data = Decompress::new(false);
+        self.inner.data.reset(false);
         Ok(self.inner.replace(w))
     }
 
@@ -307,6 +344,14 @@ impl<W: Write> DeflateDecoder<W> {
     pub fn total_out(&self) -> u64 {
         self.inner.data.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.data.set_limits(limits);
+    }
 }
 
 impl<W: Write> Write for DeflateDecoder<W> {
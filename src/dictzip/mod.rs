@@ -0,0 +1,219 @@
+//! Reading and writing the dictzip format.
+//!
+//! [dictzip] is the format of the compressed dictionaries of `dictd`, the
+//! `.dz` files. A dictzip file is a gzip file with a single member, whose
+//! data is split into chunks of the same length which are compressed one after
+//! the other, each ending with a full flush of the deflate stream. The
+//! compressed size of every chunk is listed in an `RA` subfield of the gzip
+//! extra field, which allows to decompress any chunk on its own and so to read
+//! from any position. Since it's still a regular gzip file, it can be read by
+//! any gzip decoder, such as [`GzDecoder`](crate::read::GzDecoder).
+//!
+//! [dictzip]: https://linux.die.net/man/1/dictzip
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::prelude::*;
+//! use std::io::{Cursor, SeekFrom};
+//! use flate2::dictzip::{Reader, Writer};
+//! use flate2::Compression;
+//!
+//! let mut w = Writer::new(Vec::new(), Compression::default());
+//! w.write_all(b"first line\n").unwrap();
+//! w.write_all(b"second line\n").unwrap();
+//! let compressed = w.finish().unwrap();
+//!
+//! let mut r = Reader::new(Cursor::new(compressed)).unwrap();
+//! r.seek(SeekFrom::Start(11)).unwrap();
+//! let mut line = String::new();
+//! r.read_line(&mut line).unwrap();
+//! assert_eq!(line, "second line\n");
+//! ```
+
+mod read;
+mod write;
+
+pub use self::read::Reader;
+pub use self::write::Writer;
+
+/// The largest chunk the writer makes, the default of `dictzip`, which leaves
+/// room for the compressed size of any chunk to fit in 16 bits.
+const MAX_CHUNK_LEN: u16 = 58315;
+
+/// The most chunks of a table which fits in the extra field.
+const MAX_CHUNKS: usize = (u16::MAX as usize - 10) / 2;
+
+#[cfg(test)]
+mod tests {
+    use std::io::prelude::*;
+    use std::io::{Cursor, SeekFrom};
+
+    use super::*;
+    use crate::read::GzDecoder;
+    use crate::write::GzEncoder;
+    use crate::{Compression, DecompressError, ErrorKind};
+
+    fn lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| format!("line {} of the data\n", i).into_bytes())
+            .collect()
+    }
+
+    fn write(data: &[u8], chunk_len: u16, level: Compression) -> Vec<u8> {
+        let mut w = Writer::new_with_chunk_len(Vec::new(), level, chunk_len);
+        for part in data.chunks(10_000) {
+            w.write_all(part).unwrap();
+        }
+        w.finish().unwrap()
+    }
+
+    fn read_at<R: Read + Seek>(r: &mut Reader<R>, pos: SeekFrom, len: usize) -> Vec<u8> {
+        r.seek(pos).unwrap();
+        let mut out = Vec::new();
+        r.take(len as u64).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = lines(20_000);
+        for chunk_len in [MAX_CHUNK_LEN, 1000] {
+            let chunks = (data.len() + usize::from(chunk_len) - 1) / usize::from(chunk_len);
+            let compressed = write(&data, chunk_len, Compression::default());
+
+            // A single gzip member, with the chunk table as its extra field.
+            assert_eq!(compressed[..4], [0x1f, 0x8b, 8, crate::gz::FEXTRA]);
+            assert_eq!(compressed[12..14], *b"RA");
+            let count = u16::from_le_bytes([compressed[20], compressed[21]]);
+            assert_eq!(usize::from(count), chunks);
+
+            let mut out = Vec::new();
+            GzDecoder::new(&compressed[..])
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, data);
+
+            let mut r = Reader::new(Cursor::new(&compressed)).unwrap();
+            assert_eq!(r.uncompressed_len(), data.len() as u64);
+            let mut out = Vec::new();
+            r.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let data = lines(1000);
+        for len in [0, 1, 99, 100, 101, 5000, data.len()] {
+            let compressed = write(&data[..len], 100, Compression::fast());
+            let mut out = Vec::new();
+            GzDecoder::new(&compressed[..])
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, data[..len]);
+
+            let mut r = Reader::new(Cursor::new(&compressed)).unwrap();
+            assert_eq!(r.uncompressed_len(), len as u64);
+            let mut out = Vec::new();
+            r.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data[..len]);
+        }
+    }
+
+    #[test]
+    fn incompressible() {
+        let mut data = Vec::new();
+        let mut seed = 1u32;
+        while data.len() < 200_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            data.extend_from_slice(&seed.to_le_bytes());
+        }
+        for level in [Compression::none(), Compression::best()] {
+            let compressed = write(&data, MAX_CHUNK_LEN, level);
+            let mut r = Reader::new(Cursor::new(&compressed)).unwrap();
+            let mut out = Vec::new();
+            r.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let data = lines(30_000);
+        let len = data.len() as u64;
+        let compressed = write(&data, 4096, Compression::default());
+
+        // After some other data in the underlying reader.
+        let mut file = b"something else".to_vec();
+        file.extend_from_slice(&compressed);
+        let mut inner = Cursor::new(file);
+        inner.seek(SeekFrom::Start(14)).unwrap();
+        let mut r = Reader::new(inner).unwrap();
+
+        for &pos in &[len / 2, 0, 4095, 4096, 4097, 12_345, len - 10, len / 3] {
+            let out = read_at(&mut r, SeekFrom::Start(pos), 10_000);
+            let end = std::cmp::min(pos + 10_000, len) as usize;
+            assert_eq!(out, data[pos as usize..end]);
+            assert_eq!(r.stream_position().unwrap(), end as u64);
+        }
+        assert_eq!(
+            read_at(&mut r, SeekFrom::End(-5), 100),
+            data[data.len() - 5..]
+        );
+        r.seek(SeekFrom::Start(100)).unwrap();
+        assert_eq!(read_at(&mut r, SeekFrom::Current(-50), 20), data[50..70]);
+        assert_eq!(read_at(&mut r, SeekFrom::End(10), 100), b"");
+        assert!(r.seek(SeekFrom::Current(-(len as i64) - 11)).is_err());
+    }
+
+    #[test]
+    fn retry_finish() {
+        let data = lines(5000);
+        let compressed = write(&data, 1000, Compression::default());
+
+        let mut w =
+            Writer::new_with_chunk_len(crate::FlakyWriter::default(), Compression::default(), 1000);
+        w.write_all(&data).unwrap();
+        while w.try_finish().is_err() {}
+        crate::assert_write_after_finish(w.write(b"more").unwrap_err());
+        w.try_finish().unwrap();
+        assert_eq!(w.finish().unwrap().data, compressed);
+    }
+
+    #[test]
+    fn errors() {
+        let data = lines(10_000);
+        let compressed = write(&data, 10_000, Compression::default());
+        let read = |file: &[u8]| -> std::io::Result<Vec<u8>> {
+            let mut out = Vec::new();
+            Reader::new(Cursor::new(file))?.read_to_end(&mut out)?;
+            Ok(out)
+        };
+        assert_eq!(read(&compressed).unwrap(), data);
+
+        // A gzip file without a chunk table.
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let err = read(&e.finish().unwrap()).unwrap_err();
+        let inner = err.get_ref().unwrap();
+        let inner = inner.downcast_ref::<DecompressError>().unwrap();
+        assert_eq!(inner.kind(), ErrorKind::InvalidHeader);
+
+        // A chunk table which doesn't match the chunks.
+        let mut bad = compressed.clone();
+        bad[30] ^= 0x55;
+        assert!(read(&bad).is_err());
+
+        // The size in the trailer has to agree with the table.
+        let mut bad = compressed.clone();
+        let at = bad.len() - 4;
+        bad[at] ^= 0x80;
+        assert!(read(&bad).is_err());
+
+        assert!(read(&compressed[..compressed.len() - 1]).is_err());
+        assert!(read(&compressed[..20]).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::gz::{gzip_error, GzHeaderParser};
+use crate::{Decompress, ErrorKind, FlushDecompress, GzHeader, Status};
+
+fn bad_header() -> io::Error {
+    gzip_error(
+        ErrorKind::InvalidHeader,
+        "gzip header without a valid dictzip chunk table",
+    )
+}
+
+fn corrupt() -> io::Error {
+    gzip_error(
+        ErrorKind::ChecksumMismatch,
+        "corrupt dictzip chunk does not match the chunk table",
+    )
+}
+
+fn truncated() -> io::Error {
+    gzip_error(ErrorKind::TruncatedInput, "dictzip file ended early")
+}
+
+fn eof_truncated(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        truncated()
+    } else {
+        err
+    }
+}
+
+/// Parses the `RA` subfield of the gzip extra field into the length of the
+/// chunks and the compressed size of each.
+fn chunk_table(mut extra: &[u8]) -> Option<(u16, Vec<u16>)> {
+    let le_u16 = |bytes: &[u8]| u16::from_le_bytes([bytes[0], bytes[1]]);
+    while extra.len() >= 4 {
+        let len = usize::from(le_u16(&extra[2..]));
+        let field = extra.get(4..4 + len)?;
+        if extra[..2] == *b"RA" {
+            if len < 6 || le_u16(field) != 1 {
+                return None;
+            }
+            let chunk_len = le_u16(&field[2..]);
+            let count = usize::from(le_u16(&field[4..]));
+            if chunk_len == 0 || count == 0 || len != 6 + 2 * count {
+                return None;
+            }
+            return Some((chunk_len, field[6..].chunks(2).map(le_u16).collect()));
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// A reader of a [dictzip](super) file.
+///
+/// This structure implements [`Read`], [`BufRead`] and [`Seek`] over the
+/// decompressed data, seeking to any position by decompressing only the chunk
+/// which holds it. As the chunks are read on their own, the checksum of the
+/// whole file isn't checked, but the size of every chunk is.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use std::io::{Cursor, SeekFrom};
+/// use flate2::dictzip::{Reader, Writer};
+/// use flate2::Compression;
+///
+/// let mut w = Writer::new(Vec::new(), Compression::default());
+/// w.write_all(b"Hello World").unwrap();
+/// let compressed = w.finish().unwrap();
+///
+/// let mut r = Reader::new(Cursor::new(compressed)).unwrap();
+/// r.seek(SeekFrom::Start(6)).unwrap();
+/// let mut s = String::new();
+/// r.read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "World");
+/// ```
+pub struct Reader<R> {
+    inner: R,
+    decompress: Decompress,
+    header: GzHeader,
+    chunk_len: u64,
+    // The offset of each chunk in the underlying reader, followed by that of
+    // the trailer.
+    offsets: Vec<u64>,
+    uncompressed_len: u64,
+    // The compressed data of the current chunk.
+    buf: Vec<u8>,
+    // The decompressed data of the current chunk.
+    data: Vec<u8>,
+    chunk: Option<usize>,
+    pos: u64,
+}
+
+impl<R> Reader<R> {
+    /// Returns the header of the file, which includes the chunk table in its
+    /// extra field.
+    pub fn header(&self) -> &GzHeader {
+        &self.header
+    }
+
+    /// Returns the length of the decompressed data.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.uncompressed_len
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this reader is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consumes this reader, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Creates a new reader of the dictzip file which starts at the current
+    /// position of `r`.
+    ///
+    /// This reads the header and the trailer of the file, and fails if the
+    /// header doesn't hold a chunk table.
+    pub fn new(mut r: R) -> io::Result<Reader<R>> {
+        let mut parser = GzHeaderParser::new();
+        let mut buffered = io::BufReader::new(&mut r);
+        parser.parse(&mut buffered).map_err(eof_truncated)?;
+        let mut offset = buffered.stream_position()?;
+        drop(buffered);
+        let header = GzHeader::from(parser);
+        let (chunk_len, sizes) = header
+            .extra()
+            .and_then(chunk_table)
+            .ok_or_else(bad_header)?;
+
+        let mut offsets = Vec::with_capacity(sizes.len() + 1);
+        for size in sizes {
+            offsets.push(offset);
+            offset += u64::from(size);
+        }
+        offsets.push(offset);
+
+        // Only the size of the last chunk isn't in the table, but it follows
+        // from the size in the trailer.
+        let mut trailer = [0; 8];
+        r.seek(SeekFrom::Start(offset))?;
+        r.read_exact(&mut trailer).map_err(eof_truncated)?;
+        let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        let chunk_len = u64::from(chunk_len);
+        let full_chunks = (offsets.len() as u64 - 2) * chunk_len;
+        let last = u64::from(isize.wrapping_sub(full_chunks as u32));
+        if last > chunk_len {
+            return Err(corrupt());
+        }
+
+        Ok(Reader {
+            inner: r,
+            decompress: Decompress::new(false),
+            header,
+            chunk_len,
+            offsets,
+            uncompressed_len: full_chunks + last,
+            buf: Vec::new(),
+            data: Vec::new(),
+            chunk: None,
+            pos: 0,
+        })
+    }
+
+    /// Reads and decompresses the chunk at `index`.
+    fn read_chunk(&mut self, index: usize) -> io::Result<()> {
+        self.chunk = None;
+        let start = self.offsets[index];
+        self.inner.seek(SeekFrom::Start(start))?;
+        self.buf
+            .resize((self.offsets[index + 1] - start) as usize, 0);
+        self.inner
+            .read_exact(&mut self.buf)
+            .map_err(eof_truncated)?;
+
+        let last = index + 2 == self.offsets.len();
+        let chunk_start = index as u64 * self.chunk_len;
+        let len = cmp::min(self.chunk_len, self.uncompressed_len - chunk_start) as usize;
+        self.data.clear();
+        self.data.reserve(len + 1);
+        // Every chunk but the last ends with a full flush, after which the
+        // deflate stream starts over without any history.
+        self.decompress.reset(false);
+        let status =
+            self.decompress
+                .decompress_vec(&self.buf, &mut self.data, FlushDecompress::Sync)?;
+        if self.decompress.total_in() != self.buf.len() as u64
+            || self.data.len() != len
+            || last != (status == Status::StreamEnd)
+        {
+            return Err(corrupt());
+        }
+        self.chunk = Some(index);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for Reader<R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let buf = self.fill_buf()?;
+        let n = cmp::min(buf.len(), into.len());
+        into[..n].copy_from_slice(&buf[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> BufRead for Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.uncompressed_len {
+            return Ok(&[]);
+        }
+        let index = (self.pos / self.chunk_len) as usize;
+        if self.chunk != Some(index) {
+            self.read_chunk(index)?;
+        }
+        let offset = (self.pos - index as u64 * self.chunk_len) as usize;
+        Ok(&self.data[offset..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+/// Seeks in the decompressed data. Seeking beyond its end is allowed, after
+/// which reads return no data.
+impl<R: Read + Seek> Seek for Reader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+            SeekFrom::End(n) => self.uncompressed_len.checked_add_signed(n),
+        };
+        self.pos = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Reader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("inner", &self.inner)
+            .field("chunk_len", &self.chunk_len)
+            .field("chunks", &(self.offsets.len() - 1))
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
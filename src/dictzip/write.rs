@@ -0,0 +1,231 @@
+use std::cmp;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::thread;
+
+use super::{MAX_CHUNKS, MAX_CHUNK_LEN};
+use crate::crc::Crc;
+use crate::{Compress, Compression, FlushCompress, GzBuilder, Status};
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "too many chunks for the table of a dictzip file",
+    )
+}
+
+/// A writer of a [dictzip](super) file.
+///
+/// The data is split into chunks of 58315 bytes by default, the same as
+/// `dictzip` itself, each of which ends with a full flush of the deflate
+/// stream. As the table of the chunks is in the gzip header, in front of the
+/// chunks, the compressed data is kept in memory and only written out once the
+/// writer is finished or dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::dictzip::Writer;
+/// use flate2::Compression;
+///
+/// let mut w = Writer::new(Vec::new(), Compression::default());
+/// w.write_all(b"Hello World").unwrap();
+/// let compressed = w.finish().unwrap();
+/// ```
+pub struct Writer<W: Write> {
+    obj: Option<W>,
+    level: Compression,
+    compress: Compress,
+    chunk_len: usize,
+    // The data of the chunk which is being filled.
+    chunk: Vec<u8>,
+    // The compressed chunks, and the size of each. Once finished, the data
+    // is the whole file, of which `written` bytes were written out.
+    data: Vec<u8>,
+    sizes: Vec<u16>,
+    crc: Crc,
+    finished: bool,
+    written: usize,
+}
+
+impl<W: Write> Writer<W> {
+    /// Creates a new writer which writes the file to `w` once it's finished.
+    pub fn new(w: W, level: Compression) -> Writer<W> {
+        Writer::new_with_chunk_len(w, level, MAX_CHUNK_LEN)
+    }
+
+    /// Creates a new writer which splits the data into chunks of `chunk_len`
+    /// bytes.
+    ///
+    /// Smaller chunks are faster to seek in but compress worse, and they limit
+    /// the size of the file, as the table holds at most 32762 chunks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_len` is zero or larger than 58315, which makes sure the
+    /// compressed size of any chunk fits in the table.
+    pub fn new_with_chunk_len(w: W, level: Compression, chunk_len: u16) -> Writer<W> {
+        assert!(
+            chunk_len > 0 && chunk_len <= MAX_CHUNK_LEN,
+            "the chunk length must be between 1 and 58315"
+        );
+        Writer {
+            obj: Some(w),
+            level,
+            compress: Compress::new(level, false),
+            chunk_len: usize::from(chunk_len),
+            chunk: Vec::new(),
+            data: Vec::new(),
+            sizes: Vec::new(),
+            crc: Crc::new(),
+            finished: false,
+            written: 0,
+        }
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.obj.as_ref().unwrap()
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutating the output/input state of the stream may corrupt
+    /// this object, so care must be taken when using this method.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.obj.as_mut().unwrap()
+    }
+
+    /// Returns the number of bytes which were written to this writer.
+    pub fn total_in(&self) -> u64 {
+        self.crc.amount().into()
+    }
+
+    /// Compresses the current chunk, ending it with a full flush or, for the
+    /// last one, with the end of the deflate stream.
+    fn compress_chunk(&mut self, flush: FlushCompress) -> io::Result<()> {
+        let start = self.data.len();
+        let mut input = &self.chunk[..];
+        loop {
+            self.data.reserve(input.len() + 64);
+            let before = self.compress.total_in();
+            let status = self.compress.compress_vec(input, &mut self.data, flush)?;
+            input = &input[(self.compress.total_in() - before) as usize..];
+            let done = match flush {
+                FlushCompress::Finish => status == Status::StreamEnd,
+                _ => input.is_empty() && self.data.len() < self.data.capacity(),
+            };
+            if done {
+                break;
+            }
+        }
+        let size = u16::try_from(self.data.len() - start).map_err(|_| too_large())?;
+        self.sizes.push(size);
+        self.chunk.clear();
+        Ok(())
+    }
+
+    /// Attempts to finish the file, writing out the header, all chunks and
+    /// the trailer.
+    ///
+    /// This is the same as [`Writer::finish`], except that the underlying
+    /// writer isn't consumed. Further writes return an error of kind
+    /// [`ErrorKind::StreamFinished`](crate::ErrorKind::StreamFinished).
+    ///
+    /// If writing fails, calling this again continues where it stopped.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.compress_chunk(FlushCompress::Finish)?;
+            let mut extra = Vec::with_capacity(10 + 2 * self.sizes.len());
+            extra.extend_from_slice(b"RA");
+            extra.extend_from_slice(&(6 + 2 * self.sizes.len() as u16).to_le_bytes());
+            extra.extend_from_slice(&1u16.to_le_bytes());
+            extra.extend_from_slice(&(self.chunk_len as u16).to_le_bytes());
+            extra.extend_from_slice(&(self.sizes.len() as u16).to_le_bytes());
+            for size in &self.sizes {
+                extra.extend_from_slice(&size.to_le_bytes());
+            }
+            let header = GzBuilder::new().extra(extra).into_header(self.level);
+            self.data.splice(..0, header);
+            self.data.extend_from_slice(&self.crc.sum().to_le_bytes());
+            self.data
+                .extend_from_slice(&self.crc.amount().to_le_bytes());
+            self.finished = true;
+        }
+        while self.written < self.data.len() {
+            let n = self
+                .obj
+                .as_mut()
+                .unwrap()
+                .write(&self.data[self.written..])?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.written += n;
+        }
+        self.data = Vec::new();
+        self.written = 0;
+        self.get_mut().flush()
+    }
+
+    /// Finishes the file, and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.obj.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(crate::mem::write_after_finish());
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // A full chunk is only compressed once more data follows it, as the
+        // last chunk ends the deflate stream instead.
+        if self.chunk.len() == self.chunk_len {
+            if self.sizes.len() + 2 > MAX_CHUNKS {
+                return Err(too_large());
+            }
+            self.compress_chunk(FlushCompress::Full)?;
+        }
+        if self.chunk.capacity() == 0 {
+            self.chunk.reserve_exact(self.chunk_len);
+        }
+        let n = cmp::min(buf.len(), self.chunk_len - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..n]);
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+
+    /// Flushes the underlying writer. The file itself is only written out
+    /// once it's finished.
+    fn flush(&mut self) -> io::Result<()> {
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        if self.obj.is_some() && !thread::panicking() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for Writer<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer")
+            .field("obj", &self.obj)
+            .field("level", &self.level)
+            .field("chunk_len", &self.chunk_len)
+            .field("chunks", &self.sizes.len())
+            .field("total_in", &self.total_in())
+            .finish()
+    }
+}
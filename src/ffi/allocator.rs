@@ -0,0 +1,131 @@
+//! Allocation functions for the zlib backends.
+//!
+//! Every stream gets its own [`Budget`], which the allocation functions receive
+//! as the `opaque` pointer of the `z_stream`. It keeps track of the memory held
+//! by the stream and fails allocations which would exceed its limit, which
+//! zlib reports as `Z_MEM_ERROR`.
+
+use std::alloc::{self, Layout};
+use std::convert::TryFrom;
+use std::os::raw::{c_uint, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const ALIGN: usize = std::mem::align_of::<usize>();
+
+fn align_up(size: usize, align: usize) -> usize {
+    (size + align - 1) & !(align - 1)
+}
+
+struct Accounting {
+    used: AtomicUsize,
+    limit: usize,
+}
+
+/// The memory accounting of a stream, at a stable address for the lifetime of
+/// the stream.
+pub struct Budget {
+    // SAFETY: Created by `Box::into_raw` and only freed when dropped. The
+    // allocation functions access it through the `opaque` pointer while the
+    // stream uses it, so it must not be turned back into a `Box` before.
+    inner: *mut Accounting,
+}
+
+// SAFETY: `Accounting` only consists of an atomic and an immutable field.
+unsafe impl Send for Budget {}
+unsafe impl Sync for Budget {}
+
+impl Budget {
+    pub fn new(limit: Option<usize>) -> Budget {
+        Budget {
+            inner: Box::into_raw(Box::new(Accounting {
+                used: AtomicUsize::new(0),
+                limit: limit.unwrap_or(usize::MAX),
+            })),
+        }
+    }
+
+    /// The pointer to pass as `opaque` along with [`zalloc`] and [`zfree`].
+    pub fn opaque(&self) -> *mut c_void {
+        self.inner as *mut c_void
+    }
+
+    /// The number of bytes currently allocated for the stream.
+    pub fn used(&self) -> usize {
+        // SAFETY: `inner` is valid until `self` is dropped.
+        unsafe { (*self.inner).used.load(Ordering::Relaxed) }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        // SAFETY: `inner` is valid until `self` is dropped.
+        match unsafe { (*self.inner).limit } {
+            usize::MAX => None,
+            limit => Some(limit),
+        }
+    }
+}
+
+impl Drop for Budget {
+    fn drop(&mut self) {
+        // SAFETY: The stream was ended before, so nothing refers to `inner` anymore.
+        drop(unsafe { Box::from_raw(self.inner) });
+    }
+}
+
+pub extern "C" fn zalloc(opaque: *mut c_void, items: c_uint, item_size: c_uint) -> *mut c_void {
+    // SAFETY: `opaque` always points to the `Accounting` of a live `Budget`.
+    let accounting = unsafe { &*(opaque as *const Accounting) };
+
+    // We need to multiply `items` and `item_size` to get the actual desired
+    // allocation size. Since `zfree` doesn't receive a size argument we
+    // also need to allocate space for a `usize` as a header so we can store
+    // how large the allocation is to deallocate later.
+    let size = match items
+        .checked_mul(item_size)
+        .and_then(|i| usize::try_from(i).ok())
+        .map(|size| align_up(size, ALIGN))
+        .and_then(|i| i.checked_add(std::mem::size_of::<usize>()))
+    {
+        Some(i) => i,
+        None => return ptr::null_mut(),
+    };
+
+    // The stream is only used by one thread at a time, so nothing can allocate
+    // in between the check and the update below.
+    if size > accounting.limit - accounting.used.load(Ordering::Relaxed) {
+        return ptr::null_mut();
+    }
+
+    // Make sure the `size` isn't too big to fail `Layout`'s restrictions
+    let layout = match Layout::from_size_align(size, ALIGN) {
+        Ok(layout) => layout,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    unsafe {
+        // Allocate the data, and if successful store the size we allocated
+        // at the beginning and then return an offset pointer.
+        let ptr = alloc::alloc(layout) as *mut usize;
+        if ptr.is_null() {
+            return ptr as *mut c_void;
+        }
+        accounting.used.fetch_add(size, Ordering::Relaxed);
+        *ptr = size;
+        ptr.add(1) as *mut c_void
+    }
+}
+
+pub extern "C" fn zfree(opaque: *mut c_void, address: *mut c_void) {
+    // SAFETY: `opaque` always points to the `Accounting` of a live `Budget`.
+    let accounting = unsafe { &*(opaque as *const Accounting) };
+    unsafe {
+        // Move our address being freed back one pointer, read the size we
+        // stored in `zalloc`, and then free it using the standard Rust
+        // allocator.
+        let ptr = (address as *mut usize).offset(-1);
+        let size = *ptr;
+        accounting.used.fetch_sub(size, Ordering::Relaxed);
+        let layout = Layout::from_size_align_unchecked(size, ALIGN);
+        alloc::dealloc(ptr as *mut u8, layout)
+    }
+}
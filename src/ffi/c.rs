@@ -1,19 +1,42 @@
 //! Implementation for C backends.
+use std::convert::TryFrom;
 use std::fmt;
 use std::marker;
 use std::mem::MaybeUninit;
 use std::os::raw::{c_int, c_uint};
 use std::ptr;
 
+use super::allocator::{self, Budget};
 use super::*;
-use crate::mem;
-
-#[derive(Clone, Default)]
-pub struct ErrorMessage(pub(crate) Option<&'static str>);
+use crate::mem::{self, DecompressPosition, ErrorKind, Format};
+use crate::Compression;
+
+pub fn backend_info() -> BackendInfo {
+    // SAFETY: zlibVersion returns a pointer to a static, nul-terminated string.
+    let version = unsafe { std::ffi::CStr::from_ptr(zlibVersion()) };
+    let version = version.to_str().unwrap_or("unknown");
+    // zlib-ng in compat mode reports the zlib version it is compatible with,
+    // followed by its own name.
+    let name = if cfg!(feature = "zlib-ng") || version.contains("zlib-ng") {
+        "zlib-ng"
+    } else {
+        "zlib"
+    };
+    BackendInfo {
+        name,
+        version: Some(version),
+        capabilities: Capabilities::ZLIB,
+    }
+}
 
-impl ErrorMessage {
-    pub fn get(&self) -> Option<&str> {
-        self.0
+/// The kind of the error zlib reports with the return code `rc`. zlib only
+/// tells why data is corrupt in its message, so that's [`ErrorKind::InvalidData`].
+fn error_kind(rc: c_int) -> ErrorKind {
+    match rc {
+        MZ_DATA_ERROR => ErrorKind::InvalidData,
+        MZ_MEM_ERROR => ErrorKind::OutOfMemory,
+        MZ_STREAM_ERROR => ErrorKind::InvalidParameter,
+        _ => ErrorKind::Other,
     }
 }
 
@@ -22,6 +45,7 @@ pub struct StreamWrapper {
     // since it points to a cyclic structure, and it must never be copied
     // by Rust.
     pub inner: *mut mz_stream,
+    pub budget: Budget,
 }
 
 impl fmt::Debug for StreamWrapper {
@@ -30,8 +54,9 @@ impl fmt::Debug for StreamWrapper {
     }
 }
 
-impl Default for StreamWrapper {
-    fn default() -> StreamWrapper {
+impl StreamWrapper {
+    pub fn new(memory_limit: Option<usize>) -> StreamWrapper {
+        let budget = Budget::new(memory_limit);
         // SAFETY: The field `state` will be initialized across the FFI to
         // point to the opaque type `mz_internal_state`, which will contain a copy
         // of `inner`. This cyclic structure breaks the uniqueness invariant of
@@ -48,12 +73,13 @@ impl Default for StreamWrapper {
                 adler: 0,
                 data_type: 0,
                 reserved: 0,
-                opaque: ptr::null_mut(),
+                opaque: budget.opaque(),
                 state: ptr::null_mut(),
 
                 zalloc: allocator::zalloc,
                 zfree: allocator::zfree,
             })),
+            budget,
         }
     }
 }
@@ -68,62 +94,22 @@ impl Drop for StreamWrapper {
     }
 }
 
-mod allocator {
-    use super::*;
-
-    use std::alloc::{self, Layout};
-    use std::convert::TryFrom;
-    use std::os::raw::c_void;
-
-    const ALIGN: usize = std::mem::align_of::<usize>();
-
-    fn align_up(size: usize, align: usize) -> usize {
-        (size + align - 1) & !(align - 1)
-    }
-
-    pub extern "C" fn zalloc(_ptr: *mut c_void, items: uInt, item_size: uInt) -> *mut c_void {
-        // We need to multiply `items` and `item_size` to get the actual desired
-        // allocation size. Since `zfree` doesn't receive a size argument we
-        // also need to allocate space for a `usize` as a header so we can store
-        // how large the allocation is to deallocate later.
-        let size = match items
-            .checked_mul(item_size)
-            .and_then(|i| usize::try_from(i).ok())
-            .map(|size| align_up(size, ALIGN))
-            .and_then(|i| i.checked_add(std::mem::size_of::<usize>()))
-        {
-            Some(i) => i,
-            None => return ptr::null_mut(),
-        };
-
-        // Make sure the `size` isn't too big to fail `Layout`'s restrictions
-        let layout = match Layout::from_size_align(size, ALIGN) {
-            Ok(layout) => layout,
-            Err(_) => return ptr::null_mut(),
-        };
-
-        unsafe {
-            // Allocate the data, and if successful store the size we allocated
-            // at the beginning and then return an offset pointer.
-            let ptr = alloc::alloc(layout) as *mut usize;
-            if ptr.is_null() {
-                return ptr as *mut c_void;
-            }
-            *ptr = size;
-            ptr.add(1) as *mut c_void
-        }
-    }
-
-    pub extern "C" fn zfree(_ptr: *mut c_void, address: *mut c_void) {
-        unsafe {
-            // Move our address being freed back one pointer, read the size we
-            // stored in `zalloc`, and then free it using the standard Rust
-            // allocator.
-            let ptr = (address as *mut usize).offset(-1);
-            let size = *ptr;
-            let layout = Layout::from_size_align_unchecked(size, ALIGN);
-            alloc::dealloc(ptr as *mut u8, layout)
-        }
+/// Copies the stream `source` into `dest` with `copy`, which is either
+/// `inflateCopy` or `deflateCopy`.
+///
+/// zlib allocates the state of the copy through the allocator of the source,
+/// and the copy inherits it, so the source is pointed to the accounting of the
+/// copy for the duration of the call.
+unsafe fn copy_stream(
+    source: &StreamWrapper,
+    dest: &StreamWrapper,
+    copy: unsafe extern "C" fn(*mut mz_stream, *mut mz_stream) -> c_int,
+) -> c_int {
+    unsafe {
+        (*source.inner).opaque = dest.budget.opaque();
+        let ret = copy(dest.inner, source.inner);
+        (*source.inner).opaque = source.budget.opaque();
+        ret
     }
 }
 
@@ -221,9 +207,14 @@ impl Inflate {
             (*raw).avail_out = 0;
 
             match rc {
-                MZ_DATA_ERROR | MZ_STREAM_ERROR | MZ_MEM_ERROR => {
-                    mem::decompress_failed(self.inner.msg())
+                MZ_DATA_ERROR | MZ_STREAM_ERROR => {
+                    mem::decompress_failed(error_kind(rc), self.inner.msg())
                 }
+                // zlib doesn't set a message if the window couldn't be allocated.
+                MZ_MEM_ERROR => mem::decompress_failed(
+                    ErrorKind::OutOfMemory,
+                    ErrorMessage(Some("insufficient memory")),
+                ),
                 MZ_OK => Ok(Status::Ok),
                 MZ_BUF_ERROR => Ok(Status::BufError),
                 MZ_STREAM_END => Ok(Status::StreamEnd),
@@ -233,11 +224,14 @@ impl Inflate {
             }
         }
     }
-}
-impl InflateBackend for Inflate {
-    fn make(zlib_header: bool, window_bits: u8) -> Self {
+
+    pub fn make(
+        zlib_header: bool,
+        window_bits: u8,
+        memory_limit: Option<usize>,
+    ) -> Result<Self, DecompressError> {
         unsafe {
-            let state = StreamWrapper::default();
+            let state = StreamWrapper::new(memory_limit);
             let ret = mz_inflateInit2(
                 state.inner,
                 if zlib_header {
@@ -246,18 +240,29 @@ impl InflateBackend for Inflate {
                     -(window_bits as c_int)
                 },
             );
-            assert_eq!(ret, 0);
-            Inflate {
+            match ret {
+                MZ_OK => {}
+                MZ_MEM_ERROR => {
+                    return mem::decompress_failed(
+                        ErrorKind::OutOfMemory,
+                        ErrorMessage(Some("insufficient memory")),
+                    )
+                }
+                c => panic!("unknown return code: {}", c),
+            }
+            Ok(Inflate {
                 inner: Stream {
                     stream_wrapper: state,
                     total_in: 0,
                     total_out: 0,
                     _marker: marker::PhantomData,
                 },
-            }
+            })
         }
     }
+}
 
+impl InflateBackend for Inflate {
     fn decompress(
         &mut self,
         input: &[u8],
@@ -276,10 +281,18 @@ impl InflateBackend for Inflate {
     }
 
     fn reset(&mut self, zlib_header: bool) {
-        let bits = if zlib_header {
-            MZ_DEFAULT_WINDOW_BITS
+        self.reset_format(if zlib_header {
+            Format::Zlib
         } else {
-            -MZ_DEFAULT_WINDOW_BITS
+            Format::Deflate
+        });
+    }
+
+    fn reset_format(&mut self, format: Format) {
+        let bits = match format {
+            Format::Deflate => -MZ_DEFAULT_WINDOW_BITS,
+            Format::Zlib => MZ_DEFAULT_WINDOW_BITS,
+            Format::Gzip => MZ_DEFAULT_WINDOW_BITS + 16,
         };
         unsafe {
             inflateReset2(self.inner.stream_wrapper.inner, bits);
@@ -287,6 +300,127 @@ impl InflateBackend for Inflate {
         self.inner.total_out = 0;
         self.inner.total_in = 0;
     }
+
+    fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+        let raw = self.inner.stream_wrapper.inner;
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self`.
+        let (rc, skipped) = unsafe {
+            (*raw).msg = ptr::null_mut();
+            (*raw).next_in = input.as_ptr() as *mut u8;
+            (*raw).avail_in = input.len().min(c_uint::MAX as usize) as c_uint;
+
+            let rc = inflateSync(raw);
+            let skipped = (*raw).next_in as usize - input.as_ptr() as usize;
+
+            (*raw).next_in = ptr::null_mut();
+            (*raw).avail_in = 0;
+            (rc, skipped)
+        };
+        self.inner.total_in += skipped as u64;
+
+        match rc {
+            MZ_OK => Ok(skipped),
+            MZ_DATA_ERROR | MZ_BUF_ERROR => mem::decompress_failed(
+                ErrorKind::InvalidData,
+                ErrorMessage(Some("no sync point found")),
+            ),
+            _ => mem::decompress_failed(error_kind(rc), self.inner.msg()),
+        }
+    }
+
+    fn position(&self) -> DecompressPosition {
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self`.
+        let data_type = unsafe { (*self.inner.stream_wrapper.inner).data_type };
+        DecompressPosition::from_data_type(data_type)
+    }
+
+    fn prime(&mut self, bits: u8, value: u16) -> Result<(), DecompressError> {
+        let raw = self.inner.stream_wrapper.inner;
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self`.
+        let rc = unsafe {
+            (*raw).msg = ptr::null_mut();
+            inflatePrime(raw, bits as c_int, value as c_int)
+        };
+
+        match rc {
+            MZ_OK => Ok(()),
+            MZ_STREAM_ERROR => mem::decompress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("too many bits pending in the decompressor")),
+            ),
+            _ => mem::decompress_failed(error_kind(rc), self.inner.msg()),
+        }
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self.inner.stream_wrapper`.
+        let stream = self.inner.stream_wrapper.inner;
+        let rc = unsafe {
+            (*stream).msg = ptr::null_mut();
+            assert!(dictionary.len() < uInt::MAX as usize);
+            inflateSetDictionary(stream, dictionary.as_ptr(), dictionary.len() as uInt)
+        };
+
+        #[allow(clippy::unnecessary_cast)]
+        match rc {
+            MZ_STREAM_ERROR => mem::decompress_failed(error_kind(rc), self.inner.msg()),
+            MZ_DATA_ERROR => mem::decompress_need_dict(unsafe { (*stream).adler } as u32),
+            MZ_OK => Ok(unsafe { (*stream).adler } as u32),
+            c => panic!("unknown return code: {}", c),
+        }
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        let mut dictionary = vec![0; 1 << MZ_DEFAULT_WINDOW_BITS];
+        let mut len: uInt = 0;
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. The dictionary buffer can hold
+        // the largest possible window.
+        let rc = unsafe {
+            inflateGetDictionary(
+                self.inner.stream_wrapper.inner,
+                dictionary.as_mut_ptr(),
+                &mut len,
+            )
+        };
+        debug_assert_eq!(rc, MZ_OK);
+        dictionary.truncate(len as usize);
+        dictionary
+    }
+
+    fn try_clone(&mut self) -> Result<Box<dyn InflateBackend>, DecompressError> {
+        let state = StreamWrapper::new(self.inner.stream_wrapper.budget.limit());
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. `inflateCopy` points the new
+        // state to the new stream.
+        let ret = unsafe { copy_stream(&self.inner.stream_wrapper, &state, inflateCopy) };
+        match ret {
+            MZ_OK => {}
+            MZ_MEM_ERROR => {
+                return mem::decompress_failed(
+                    ErrorKind::OutOfMemory,
+                    ErrorMessage(Some("insufficient memory")),
+                )
+            }
+            _ => return mem::decompress_failed(error_kind(ret), self.inner.msg()),
+        }
+        Ok(Box::new(Inflate {
+            inner: Stream {
+                stream_wrapper: state,
+                total_in: self.inner.total_in,
+                total_out: self.inner.total_out,
+                _marker: marker::PhantomData,
+            },
+        }))
+    }
 }
 
 impl Backend for Inflate {
@@ -299,11 +433,16 @@ impl Backend for Inflate {
     fn total_out(&self) -> u64 {
         self.inner.total_out
     }
+
+    fn memory_usage(&self) -> usize {
+        self.inner.stream_wrapper.budget.used()
+    }
 }
 
 #[derive(Debug)]
 pub struct Deflate {
     pub inner: Stream<DirCompress>,
+    pub strategy: Strategy,
 }
 
 impl Deflate {
@@ -342,40 +481,58 @@ impl Deflate {
                 MZ_OK => Ok(Status::Ok),
                 MZ_BUF_ERROR => Ok(Status::BufError),
                 MZ_STREAM_END => Ok(Status::StreamEnd),
-                MZ_STREAM_ERROR => mem::compress_failed(self.inner.msg()),
+                MZ_STREAM_ERROR => mem::compress_failed(error_kind(rc), self.inner.msg()),
                 c => panic!("unknown return code: {}", c),
             }
         }
     }
-}
 
-impl DeflateBackend for Deflate {
-    fn make(level: Compression, zlib_header: bool, window_bits: u8) -> Self {
+    pub fn make(config: &CompressBuilder) -> Result<Self, CompressError> {
+        if config.level.level() > 9 {
+            return mem::compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("invalid compression level")),
+            );
+        }
         unsafe {
-            let state = StreamWrapper::default();
+            let state = StreamWrapper::new(config.memory_limit);
             let ret = mz_deflateInit2(
                 state.inner,
-                level.0 as c_int,
+                config.level.level() as c_int,
                 MZ_DEFLATED,
-                if zlib_header {
-                    window_bits as c_int
-                } else {
-                    -(window_bits as c_int)
-                },
-                8,
-                MZ_DEFAULT_STRATEGY,
+                config.zlib_window_bits(),
+                config.mem_level as c_int,
+                strategy_to_c(config.strategy),
             );
-            assert_eq!(ret, 0);
-            Deflate {
+            match ret {
+                MZ_OK => {}
+                MZ_MEM_ERROR => {
+                    return mem::compress_failed(
+                        ErrorKind::OutOfMemory,
+                        ErrorMessage(Some("insufficient memory")),
+                    )
+                }
+                _ => {
+                    return mem::compress_failed(
+                        ErrorKind::InvalidParameter,
+                        ErrorMessage(Some("invalid compression parameters")),
+                    )
+                }
+            }
+            Ok(Deflate {
                 inner: Stream {
                     stream_wrapper: state,
                     total_in: 0,
                     total_out: 0,
                     _marker: marker::PhantomData,
                 },
-            }
+                strategy: config.strategy,
+            })
         }
     }
+}
+
+impl DeflateBackend for Deflate {
     fn compress(
         &mut self,
         input: &[u8],
@@ -398,6 +555,159 @@ impl DeflateBackend for Deflate {
         let rc = unsafe { mz_deflateReset(self.inner.stream_wrapper.inner) };
         assert_eq!(rc, MZ_OK);
     }
+    fn bound(&self, input_len: usize) -> usize {
+        match uLong::try_from(input_len) {
+            // SAFETY: The field `inner` must always be accessed as a raw pointer,
+            // since it points to a cyclic structure. `deflateBound` only reads the
+            // state of the stream.
+            Ok(len) => unsafe { deflateBound(self.inner.stream_wrapper.inner, len) as usize },
+            // The largest possible wrapper is a gzip header and trailer.
+            Err(_) => deflate_bound(input_len).saturating_add(18),
+        }
+    }
+    fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+    fn set_params(&mut self, level: Compression, strategy: Strategy) -> Result<(), CompressError> {
+        if level.level() > 9 {
+            return mem::compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("invalid compression level")),
+            );
+        }
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self.inner.stream_wrapper`.
+        let raw = self.inner.stream_wrapper.inner;
+        let rc = unsafe {
+            (*raw).msg = ptr::null_mut();
+            // Any input that is still buffered is compressed with the previous
+            // parameters first, which requires an output buffer even if there
+            // is nothing left to compress. An empty one makes sure nothing is
+            // written.
+            let mut empty = [0u8; 1];
+            (*raw).next_out = empty.as_mut_ptr();
+            (*raw).avail_out = 0;
+            let rc = deflateParams(raw, level.level() as c_int, strategy_to_c(strategy));
+            (*raw).next_out = ptr::null_mut();
+            rc
+        };
+
+        match rc {
+            MZ_OK => {
+                self.strategy = strategy;
+                Ok(())
+            }
+            MZ_BUF_ERROR => mem::compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some(
+                    "pending data must be flushed before changing parameters",
+                )),
+            ),
+            _ => mem::compress_failed(error_kind(rc), self.inner.msg()),
+        }
+    }
+
+    fn prime(&mut self, bits: u8, value: u16) -> Result<(), CompressError> {
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self.inner.stream_wrapper`.
+        let raw = self.inner.stream_wrapper.inner;
+        let rc = unsafe {
+            (*raw).msg = ptr::null_mut();
+            deflatePrime(raw, bits as c_int, value as c_int)
+        };
+
+        match rc {
+            MZ_OK => Ok(()),
+            MZ_BUF_ERROR => mem::compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some(
+                    "pending data must be flushed before priming the compressor",
+                )),
+            ),
+            _ => mem::compress_failed(error_kind(rc), self.inner.msg()),
+        }
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. No copies of `inner` can be
+        // retained for longer than the lifetime of `self.inner.stream_wrapper`.
+        let stream = self.inner.stream_wrapper.inner;
+        let rc = unsafe {
+            (*stream).msg = ptr::null_mut();
+            assert!(dictionary.len() < uInt::MAX as usize);
+            deflateSetDictionary(stream, dictionary.as_ptr(), dictionary.len() as uInt)
+        };
+
+        match rc {
+            MZ_STREAM_ERROR => mem::compress_failed(error_kind(rc), self.inner.msg()),
+            #[allow(clippy::unnecessary_cast)]
+            MZ_OK => Ok(unsafe { (*stream).adler } as u32),
+            c => panic!("unknown return code: {}", c),
+        }
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        let mut dictionary = vec![0; 1 << MZ_DEFAULT_WINDOW_BITS];
+        let mut len: uInt = 0;
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. The dictionary buffer can hold
+        // the largest possible window.
+        let rc = unsafe {
+            deflateGetDictionary(
+                self.inner.stream_wrapper.inner,
+                dictionary.as_mut_ptr(),
+                &mut len,
+            )
+        };
+        debug_assert_eq!(rc, MZ_OK);
+        dictionary.truncate(len as usize);
+        dictionary
+    }
+
+    fn try_clone(&mut self) -> Result<Box<dyn DeflateBackend>, CompressError> {
+        let state = StreamWrapper::new(self.inner.stream_wrapper.budget.limit());
+        // SAFETY: The field `inner` must always be accessed as a raw pointer,
+        // since it points to a cyclic structure. `deflateCopy` points the new
+        // state to the new stream. If it fails, the new stream is left without
+        // a state of its own, so it must only be freed, not ended.
+        let ret = unsafe { copy_stream(&self.inner.stream_wrapper, &state, deflateCopy) };
+        match ret {
+            MZ_OK => {}
+            MZ_MEM_ERROR => {
+                return mem::compress_failed(
+                    ErrorKind::OutOfMemory,
+                    ErrorMessage(Some("insufficient memory")),
+                )
+            }
+            _ => return mem::compress_failed(error_kind(ret), self.inner.msg()),
+        }
+        Ok(Box::new(Deflate {
+            inner: Stream {
+                stream_wrapper: state,
+                total_in: self.inner.total_in,
+                total_out: self.inner.total_out,
+                _marker: marker::PhantomData,
+            },
+            strategy: self.strategy,
+        }))
+    }
+}
+
+pub fn deflate_bound(input_len: usize) -> usize {
+    zlib_deflate_bound(input_len)
+}
+
+pub fn strategy_to_c(strategy: Strategy) -> c_int {
+    match strategy {
+        Strategy::Default => MZ_DEFAULT_STRATEGY,
+        Strategy::Filtered => MZ_FILTERED,
+        Strategy::HuffmanOnly => MZ_HUFFMAN_ONLY,
+        Strategy::Rle => MZ_RLE,
+        Strategy::Fixed => MZ_FIXED,
+    }
 }
 
 impl Backend for Deflate {
@@ -410,6 +720,10 @@ impl Backend for Deflate {
     fn total_out(&self) -> u64 {
         self.inner.total_out
     }
+
+    fn memory_usage(&self) -> usize {
+        self.inner.stream_wrapper.budget.used()
+    }
 }
 
 pub use self::c_backend::*;
@@ -440,19 +754,40 @@ mod c_backend {
     pub use libz::Z_DATA_ERROR as MZ_DATA_ERROR;
     pub use libz::Z_DEFAULT_STRATEGY as MZ_DEFAULT_STRATEGY;
     pub use libz::Z_DEFLATED as MZ_DEFLATED;
+    pub use libz::Z_FILTERED as MZ_FILTERED;
     pub use libz::Z_FINISH as MZ_FINISH;
+    pub use libz::Z_FIXED as MZ_FIXED;
     pub use libz::Z_FULL_FLUSH as MZ_FULL_FLUSH;
+    pub use libz::Z_HUFFMAN_ONLY as MZ_HUFFMAN_ONLY;
     pub use libz::Z_MEM_ERROR as MZ_MEM_ERROR;
     pub use libz::Z_NEED_DICT as MZ_NEED_DICT;
     pub use libz::Z_NO_FLUSH as MZ_NO_FLUSH;
     pub use libz::Z_OK as MZ_OK;
     pub use libz::Z_PARTIAL_FLUSH as MZ_PARTIAL_FLUSH;
+    pub use libz::Z_RLE as MZ_RLE;
     pub use libz::Z_STREAM_END as MZ_STREAM_END;
     pub use libz::Z_STREAM_ERROR as MZ_STREAM_ERROR;
     pub use libz::Z_SYNC_FLUSH as MZ_SYNC_FLUSH;
+    pub use libz::Z_TREES as MZ_TREES;
 
     pub const MZ_DEFAULT_WINDOW_BITS: c_int = 15;
 
+    // These aren't bound by libz-sys, as very old zlib versions lack them.
+    extern "C" {
+        #[cfg_attr(feature = "zlib-ng", link_name = "zng_inflateGetDictionary")]
+        pub fn inflateGetDictionary(
+            strm: *mut mz_stream,
+            dictionary: *mut u8,
+            dict_length: *mut uInt,
+        ) -> c_int;
+        #[cfg_attr(feature = "zlib-ng", link_name = "zng_deflateGetDictionary")]
+        pub fn deflateGetDictionary(
+            strm: *mut mz_stream,
+            dictionary: *mut u8,
+            dict_length: *mut uInt,
+        ) -> c_int;
+    }
+
     pub unsafe extern "C" fn mz_deflateInit2(
         stream: *mut mz_stream,
         level: c_int,
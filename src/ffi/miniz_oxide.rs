@@ -2,27 +2,50 @@
 
 use std::convert::TryInto;
 use std::fmt;
+use std::io;
 
-use ::miniz_oxide::deflate::core::CompressorOxide;
-use ::miniz_oxide::inflate::stream::InflateState;
-pub use ::miniz_oxide::*;
+use ::miniz_oxide::deflate::core::{CompressionStrategy, CompressorOxide};
+use ::miniz_oxide::inflate::core::{
+    inflate_flags, BlockBoundaryState, DecompressorOxide, TINFL_LZ_DICT_SIZE,
+};
+use ::miniz_oxide::inflate::TINFLStatus;
+pub use ::miniz_oxide::MZ_DEFAULT_WINDOW_BITS;
+use ::miniz_oxide::{
+    deflate, inflate, mz_adler32_oxide, DataFormat, MZError, MZFlush, MZStatus, StreamResult,
+    MZ_ADLER32_INIT,
+};
 
 pub const MZ_NO_FLUSH: isize = MZFlush::None as isize;
 pub const MZ_PARTIAL_FLUSH: isize = MZFlush::Partial as isize;
 pub const MZ_SYNC_FLUSH: isize = MZFlush::Sync as isize;
 pub const MZ_FULL_FLUSH: isize = MZFlush::Full as isize;
 pub const MZ_FINISH: isize = MZFlush::Finish as isize;
+pub const MZ_BLOCK: isize = MZFlush::Block as isize;
+/// zlib's `Z_TREES`, which miniz_oxide has no equivalent of. It only serves as
+/// the value of `FlushDecompress::Trees`, which this backend handles like
+/// `FlushDecompress::Block`.
+pub(crate) const MZ_TREES: isize = 6;
 
 use super::*;
-use crate::mem;
+use crate::gz::GzHeaderParser;
+use crate::mem::{self, DecompressPosition, ErrorKind, Format};
+use crate::{Compression, Crc};
 
-// miniz_oxide doesn't provide any error messages (yet?)
-#[derive(Clone, Default)]
-pub struct ErrorMessage;
-
-impl ErrorMessage {
-    pub fn get(&self) -> Option<&str> {
-        None
+pub fn backend_info() -> BackendInfo {
+    BackendInfo {
+        name: "miniz_oxide",
+        version: None,
+        capabilities: Capabilities {
+            window_bits: true,
+            dictionary: true,
+            set_level: true,
+            gzip: true,
+            strategies: true,
+            mem_level: false,
+            try_clone: true,
+            block_boundaries: true,
+            prime: false,
+        },
     }
 }
 
@@ -34,8 +57,76 @@ fn format_from_bool(zlib_header: bool) -> DataFormat {
     }
 }
 
+// The buffers of the streaming decompressor, boxed together as they're large.
+#[derive(Clone)]
+struct InflateBuffers {
+    decomp: DecompressorOxide,
+    // The window of recently decompressed data, which is also used to hand
+    // out the data to the caller.
+    dict: [u8; TINFL_LZ_DICT_SIZE],
+}
+
+/// The memory held by an `Inflate`.
+const INFLATE_MEMORY: usize = std::mem::size_of::<InflateBuffers>();
+
+/// The size of the window of the compressor, which the history kept for
+/// `get_dictionary` has as well.
+const WINDOW_SIZE: usize = 1 << MZ_DEFAULT_WINDOW_BITS;
+
+/// The memory held by a `Deflate` apart from the history it keeps for
+/// `get_dictionary`: the compressor, its dictionary and hash chains, and
+/// its Huffman tables.
+const DEFLATE_MEMORY: usize =
+    std::mem::size_of::<CompressorOxide>() + (32_768 + 258) + 2 * 2 * 32_768 + 3 * 288 * 5;
+
+// miniz_oxide rejects zlib headers asking for a dictionary and doesn't
+// support gzip, so headers are read by the streaming layer instead.
+#[derive(Clone)]
+enum Header {
+    Zlib { buf: [u8; 6], have: usize },
+    NeedDict { cmf: u8, flg: u8, dict_id: u32 },
+    Gzip(Box<GzHeaderParser>),
+    Done,
+}
+
+// The trailer of a gzip stream, and the checksum of the output to verify it
+// with. The checksum can't be verified anymore after `sync`.
+#[derive(Clone, Default)]
+struct GzipTrailer {
+    crc: Option<Crc>,
+    buf: [u8; 8],
+    have: usize,
+}
+
+/// The streaming decompressor, built on miniz_oxide's core decompressor with
+/// its window as the output buffer, the same way as its `InflateState`. That
+/// can't be used itself, as `stream::inflate` takes no flags to stop at block
+/// boundaries.
+#[derive(Clone)]
 pub struct Inflate {
-    inner: Box<InflateState>,
+    inner: Box<InflateBuffers>,
+    // Where the data which wasn't handed out yet starts in the window, and
+    // how much of it there is.
+    dict_ofs: usize,
+    dict_avail: usize,
+    // How much of the data before `dict_ofs` was handed out for this stream.
+    window_len: usize,
+    // Whether nothing was decompressed yet, so that a call which finishes the
+    // stream can decompress straight into its output, like `stream::inflate`.
+    first_call: bool,
+    last_status: TINFLStatus,
+    // Why the stream failed, if it's known.
+    failure: Option<(ErrorKind, &'static str)>,
+    format: DataFormat,
+    header: Header,
+    trailer: Option<GzipTrailer>,
+    // Whether the decompressor stopped at the end of a block. The stream is
+    // only there once the rest of the block's data was handed out.
+    at_boundary: bool,
+    zlib_header: bool,
+    gzip: bool,
+    window_bits: u8,
+    sync: SyncSearch,
     total_in: u64,
     total_out: u64,
 }
@@ -50,35 +141,389 @@ impl fmt::Debug for Inflate {
     }
 }
 
-impl From<FlushDecompress> for MZFlush {
-    fn from(value: FlushDecompress) -> Self {
-        match value {
-            FlushDecompress::None => Self::None,
-            FlushDecompress::Sync => Self::Sync,
-            FlushDecompress::Finish => Self::Finish,
+impl Inflate {
+    fn reset_state(&mut self, format: DataFormat) {
+        self.inner.decomp.init();
+        self.dict_ofs = 0;
+        self.dict_avail = 0;
+        self.window_len = 0;
+        self.first_call = true;
+        self.last_status = TINFLStatus::NeedsMoreInput;
+        self.failure = None;
+        self.format = format;
+        self.header = match format {
+            DataFormat::Raw => Header::Done,
+            _ => Header::Zlib {
+                buf: [0; 6],
+                have: 0,
+            },
+        };
+        self.trailer = None;
+        self.at_boundary = false;
+    }
+
+    fn reset_stream(&mut self) {
+        if self.gzip {
+            self.reset_state(DataFormat::Raw);
+            self.header = Header::Gzip(Box::new(GzHeaderParser::new()));
+            self.trailer = Some(GzipTrailer {
+                crc: Some(Crc::new()),
+                ..GzipTrailer::default()
+            });
+        } else {
+            self.reset_state(format_from_bool(self.zlib_header));
         }
     }
-}
 
-impl InflateBackend for Inflate {
-    fn make(zlib_header: bool, _window_bits: u8) -> Self {
-        let format = format_from_bool(zlib_header);
+    /// Reads the header from the start of `input` and returns its length, or
+    /// the result to return if it's incomplete or asks for a dictionary.
+    fn read_header(&mut self, input: &[u8]) -> Result<usize, StreamResult> {
+        let (buf, have) = match &mut self.header {
+            Header::Zlib { buf, have } => (buf, have),
+            Header::NeedDict { .. } => {
+                return Err(StreamResult {
+                    bytes_consumed: 0,
+                    bytes_written: 0,
+                    status: Ok(MZStatus::NeedDict),
+                })
+            }
+            Header::Gzip(parser) => {
+                let mut rest = input;
+                let res = parser.parse(&mut rest);
+                let consumed = input.len() - rest.len();
+                let status = match res {
+                    Ok(()) => {
+                        self.header = Header::Done;
+                        return Ok(consumed);
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && consumed == 0 => {
+                        Err(MZError::Buf)
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(MZStatus::Ok),
+                    Err(e) => {
+                        let msg = e
+                            .get_ref()
+                            .and_then(|e| e.downcast_ref::<DecompressError>())
+                            .and_then(DecompressError::static_message)
+                            .unwrap_or("invalid gzip header");
+                        self.last_status = TINFLStatus::Failed;
+                        self.failure = Some((ErrorKind::InvalidHeader, msg));
+                        Err(MZError::Data)
+                    }
+                };
+                return Err(StreamResult {
+                    bytes_consumed: consumed,
+                    bytes_written: 0,
+                    status,
+                });
+            }
+            Header::Done => return Ok(0),
+        };
+        let mut consumed = 0;
+        loop {
+            let len = if *have >= 2 && buf[1] & 0x20 != 0 {
+                6
+            } else {
+                2
+            };
+            if *have == len {
+                break;
+            }
+            let Some(&byte) = input.get(consumed) else {
+                let status = if consumed == 0 {
+                    Err(MZError::Buf)
+                } else {
+                    Ok(MZStatus::Ok)
+                };
+                return Err(StreamResult {
+                    bytes_consumed: consumed,
+                    bytes_written: 0,
+                    status,
+                });
+            };
+            buf[*have] = byte;
+            *have += 1;
+            consumed += 1;
+
+            // The header check, the compression method and the window size.
+            let msg = if *have != 2 {
+                None
+            } else if u16::from_be_bytes([buf[0], buf[1]]) % 31 != 0 {
+                Some("incorrect header check")
+            } else if buf[0] & 0x0f != 8 {
+                Some("unknown compression method")
+            } else if (buf[0] >> 4) + 8 > self.window_bits {
+                Some("invalid window size")
+            } else {
+                None
+            };
+            if let Some(msg) = msg {
+                self.last_status = TINFLStatus::Failed;
+                self.failure = Some((ErrorKind::InvalidHeader, msg));
+                return Err(StreamResult {
+                    bytes_consumed: consumed,
+                    bytes_written: 0,
+                    status: Err(MZError::Data),
+                });
+            }
+        }
 
-        Inflate {
-            inner: InflateState::new_boxed(format),
+        let (cmf, flg) = (buf[0], buf[1]);
+        if flg & 0x20 != 0 {
+            let dict_id = u32::from_be_bytes([buf[2], buf[3], buf[4], buf[5]]);
+            self.header = Header::NeedDict { cmf, flg, dict_id };
+            return Err(StreamResult {
+                bytes_consumed: consumed,
+                bytes_written: 0,
+                status: Ok(MZStatus::NeedDict),
+            });
+        }
+        self.start_after_header(cmf, flg);
+        Ok(consumed)
+    }
+
+    fn start_after_header(&mut self, cmf: u8, flg: u8) {
+        // The decompressor still reads the checksum at the end of the stream.
+        self.inner.decomp = DecompressorOxide::from_block_boundary_state(&BlockBoundaryState {
+            z_header0: cmf.into(),
+            z_header1: flg.into(),
+            ..BlockBoundaryState::default()
+        });
+        self.header = Header::Done;
+    }
+
+    fn inflate(&mut self, input: &[u8], output: &mut [u8], flush: FlushDecompress) -> StreamResult {
+        let mut res = self.inflate_data(input, output, flush);
+        let Some(trailer) = &mut self.trailer else {
+            return res;
+        };
+        if let Some(crc) = &mut trailer.crc {
+            crc.update(&output[..res.bytes_written]);
+        }
+        if res.status != Ok(MZStatus::StreamEnd) {
+            return res;
+        }
+
+        // The deflate stream ended, but the gzip stream only ends after the
+        // trailer was read and verified.
+        let rest = &input[res.bytes_consumed..];
+        let n = (trailer.buf.len() - trailer.have).min(rest.len());
+        trailer.buf[trailer.have..][..n].copy_from_slice(&rest[..n]);
+        trailer.have += n;
+        res.bytes_consumed += n;
+        if trailer.have < trailer.buf.len() {
+            let progress = res.bytes_consumed != 0 || res.bytes_written != 0;
+            res.status = if progress && flush != FlushDecompress::Finish {
+                Ok(MZStatus::Ok)
+            } else {
+                Err(MZError::Buf)
+            };
+        } else if let Some(crc) = &trailer.crc {
+            let (sum, amount) = trailer.buf.split_at(4);
+            let msg = if sum != crc.sum().to_le_bytes() {
+                Some("incorrect data check")
+            } else if amount != crc.amount().to_le_bytes() {
+                Some("incorrect length check")
+            } else {
+                None
+            };
+            if let Some(msg) = msg {
+                self.last_status = TINFLStatus::Failed;
+                self.failure = Some((ErrorKind::ChecksumMismatch, msg));
+                res.status = Err(MZError::Data);
+            }
+        }
+        res
+    }
+
+    fn inflate_data(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushDecompress,
+    ) -> StreamResult {
+        let mut flags = match self.format {
+            DataFormat::Zlib => {
+                inflate_flags::TINFL_FLAG_COMPUTE_ADLER32
+                    | inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER
+            }
+            DataFormat::ZLibIgnoreChecksum => {
+                inflate_flags::TINFL_FLAG_IGNORE_ADLER32
+                    | inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER
+            }
+            _ => inflate_flags::TINFL_FLAG_IGNORE_ADLER32,
+        };
+        let finish = flush == FlushDecompress::Finish;
+        let stop_at_block = matches!(flush, FlushDecompress::Block | FlushDecompress::Trees);
+        if stop_at_block {
+            flags |= inflate_flags::TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY;
+        }
+
+        let first_call = std::mem::replace(&mut self.first_call, false);
+        if self.last_status == TINFLStatus::FailedCannotMakeProgress {
+            return StreamResult::error(MZError::Buf);
+        }
+        if (self.last_status as i32) < 0 {
+            return StreamResult::error(MZError::Data);
+        }
+
+        let header_len = match self.read_header(input) {
+            Ok(len) => len,
+            Err(res) => return res,
+        };
+        let input = &input[header_len..];
+        if header_len > 0 && (input.is_empty() || stop_at_block) {
+            // Like zlib, stop right after the header when stopping at blocks.
+            self.at_boundary = stop_at_block;
+            return StreamResult {
+                bytes_consumed: header_len,
+                bytes_written: 0,
+                status: Ok(MZStatus::Ok),
+            };
+        }
+
+        if finish && first_call {
+            flags |= inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+            let (status, in_bytes, out_bytes) =
+                inflate::core::decompress(&mut self.inner.decomp, input, output, 0, flags);
+            self.last_status = status;
+            // Keep the window up to date for `get_dictionary`.
+            let window = &output[out_bytes.saturating_sub(TINFL_LZ_DICT_SIZE)..out_bytes];
+            self.inner.dict[..window.len()].copy_from_slice(window);
+            self.dict_ofs = window.len() & (TINFL_LZ_DICT_SIZE - 1);
+            self.window_len = window.len();
+            let status = if status == TINFLStatus::FailedCannotMakeProgress {
+                Err(MZError::Buf)
+            } else if (status as i32) < 0 {
+                Err(MZError::Data)
+            } else if status != TINFLStatus::Done {
+                self.last_status = TINFLStatus::Failed;
+                Err(MZError::Buf)
+            } else {
+                Ok(MZStatus::StreamEnd)
+            };
+            return StreamResult {
+                bytes_consumed: header_len + in_bytes,
+                bytes_written: out_bytes,
+                status,
+            };
+        }
+        if !finish {
+            flags |= inflate_flags::TINFL_FLAG_HAS_MORE_INPUT;
+        }
+
+        // Data which didn't fit into the output of the last call goes first.
+        let mut next_in = input;
+        let mut next_out = output;
+        let mut bytes_written = self.push_dict_out(&mut next_out);
+        let status = loop {
+            if self.dict_avail != 0 {
+                break Ok(MZStatus::Ok);
+            } else if self.last_status == TINFLStatus::Done {
+                break Ok(MZStatus::StreamEnd);
+            } else if self.at_boundary && stop_at_block && bytes_written != 0 {
+                // The rest of the block before the boundary was handed out.
+                break Ok(MZStatus::Ok);
+            }
+
+            let inner = &mut *self.inner;
+            let (status, in_bytes, out_bytes) = inflate::core::decompress(
+                &mut inner.decomp,
+                next_in,
+                &mut inner.dict,
+                self.dict_ofs,
+                flags,
+            );
+            self.last_status = status;
+            self.at_boundary = status == TINFLStatus::BlockBoundary;
+            next_in = &next_in[in_bytes..];
+            self.dict_avail = out_bytes;
+            bytes_written += self.push_dict_out(&mut next_out);
+
+            if status == TINFLStatus::FailedCannotMakeProgress {
+                break Err(MZError::Buf);
+            } else if (status as i32) < 0 {
+                break Err(MZError::Data);
+            } else if status == TINFLStatus::NeedsMoreInput && input.is_empty() {
+                break Err(MZError::Buf);
+            }
+
+            let done = status == TINFLStatus::Done;
+            if finish {
+                // The output must have room for all of the data.
+                if (done && self.dict_avail != 0) || (!done && next_out.is_empty()) {
+                    break Err(MZError::Buf);
+                }
+            } else if (self.at_boundary && stop_at_block)
+                || (!done && (next_in.is_empty() || next_out.is_empty()))
+            {
+                break Ok(MZStatus::Ok);
+            }
+        };
+        StreamResult {
+            bytes_consumed: header_len + input.len() - next_in.len(),
+            bytes_written,
+            status,
+        }
+    }
+
+    fn push_dict_out(&mut self, next_out: &mut &mut [u8]) -> usize {
+        let n = self.dict_avail.min(next_out.len());
+        next_out[..n].copy_from_slice(&self.inner.dict[self.dict_ofs..self.dict_ofs + n]);
+        *next_out = &mut std::mem::take(next_out)[n..];
+        self.dict_avail -= n;
+        self.dict_ofs = (self.dict_ofs + n) & (TINFL_LZ_DICT_SIZE - 1);
+        self.window_len = (self.window_len + n).min(TINFL_LZ_DICT_SIZE);
+        n
+    }
+
+    pub fn make(
+        zlib_header: bool,
+        window_bits: u8,
+        memory_limit: Option<usize>,
+    ) -> Result<Self, DecompressError> {
+        if matches!(memory_limit, Some(limit) if limit < INFLATE_MEMORY) {
+            return mem::decompress_failed(
+                ErrorKind::OutOfMemory,
+                ErrorMessage(Some("insufficient memory")),
+            );
+        }
+        let mut inflate = Inflate {
+            inner: Box::new(InflateBuffers {
+                decomp: DecompressorOxide::new(),
+                dict: [0; TINFL_LZ_DICT_SIZE],
+            }),
+            dict_ofs: 0,
+            dict_avail: 0,
+            window_len: 0,
+            first_call: true,
+            last_status: TINFLStatus::NeedsMoreInput,
+            failure: None,
+            format: DataFormat::Raw,
+            header: Header::Done,
+            trailer: None,
+            at_boundary: false,
+            zlib_header,
+            // Like zlib, window bits above 15 select gzip framing.
+            gzip: window_bits > 15,
+            window_bits: window_bits & 15,
+            sync: SyncSearch::default(),
             total_in: 0,
             total_out: 0,
-        }
+        };
+        inflate.reset_stream();
+        Ok(inflate)
     }
+}
 
+impl InflateBackend for Inflate {
     fn decompress(
         &mut self,
         input: &[u8],
         output: &mut [u8],
         flush: FlushDecompress,
     ) -> Result<Status, DecompressError> {
-        let mz_flush = flush.into();
-        let res = inflate::stream::inflate(&mut self.inner, input, output, mz_flush);
+        let res = self.inflate(input, output, flush);
         self.total_in += res.bytes_consumed as u64;
         self.total_out += res.bytes_written as u64;
 
@@ -86,22 +531,174 @@ impl InflateBackend for Inflate {
             Ok(status) => match status {
                 MZStatus::Ok => Ok(Status::Ok),
                 MZStatus::StreamEnd => Ok(Status::StreamEnd),
-                MZStatus::NeedDict => {
-                    mem::decompress_need_dict(self.inner.decompressor().adler32().unwrap_or(0))
-                }
+                MZStatus::NeedDict => match self.header {
+                    Header::NeedDict { dict_id, .. } => mem::decompress_need_dict(dict_id),
+                    _ => mem::decompress_failed(
+                        ErrorKind::Other,
+                        ErrorMessage(Some("need dictionary")),
+                    ),
+                },
             },
             Err(status) => match status {
                 MZError::Buf => Ok(Status::BufError),
-                _ => mem::decompress_failed(ErrorMessage),
+                _ => {
+                    // Headers and trailers are checked here, which tells why
+                    // they're invalid. miniz_oxide doesn't tell why it failed
+                    // to decompress the data.
+                    let (kind, msg) = match (self.failure, self.last_status) {
+                        (Some(failure), _) => failure,
+                        (None, TINFLStatus::Adler32Mismatch) => {
+                            (ErrorKind::ChecksumMismatch, "incorrect data check")
+                        }
+                        (None, TINFLStatus::BadParam) => {
+                            (ErrorKind::InvalidParameter, "stream error")
+                        }
+                        (None, _) => (ErrorKind::InvalidData, "invalid deflate data"),
+                    };
+                    mem::decompress_failed(kind, ErrorMessage(Some(msg)))
+                }
             },
         }
     }
 
     fn reset(&mut self, zlib_header: bool) {
-        self.inner.reset(format_from_bool(zlib_header));
+        self.reset_format(if zlib_header {
+            Format::Zlib
+        } else {
+            Format::Deflate
+        });
+    }
+
+    fn reset_format(&mut self, format: Format) {
+        self.zlib_header = format != Format::Deflate;
+        self.gzip = format == Format::Gzip;
+        self.window_bits = MZ_DEFAULT_WINDOW_BITS as u8;
+        self.reset_stream();
+        self.sync = SyncSearch::default();
         self.total_in = 0;
         self.total_out = 0;
     }
+
+    fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+        // miniz_oxide doesn't support this, so search for the empty stored
+        // block emitted by a full flush the same way zlib does.
+        let header_read = self.zlib_header && matches!(self.header, Header::Done);
+        let (skipped, found) = self.sync.search(input);
+        self.total_in += skipped as u64;
+        if !found {
+            return mem::decompress_failed(
+                ErrorKind::InvalidData,
+                ErrorMessage(Some("no sync point found")),
+            );
+        }
+
+        // Continue with a new block. Data following a full flush point doesn't
+        // refer to any previous data, but the checksum of a zlib or gzip
+        // stream can't be verified anymore.
+        if header_read && self.gzip {
+            self.reset_state(DataFormat::Raw);
+            self.trailer = Some(GzipTrailer::default());
+        } else if header_read {
+            self.reset_state(DataFormat::ZLibIgnoreChecksum);
+            // The decompressor still expects a zlib header, so start it after
+            // a dummy one.
+            self.start_after_header(0x78, 0x01);
+        } else {
+            self.reset_state(DataFormat::Raw);
+        }
+        Ok(skipped)
+    }
+
+    fn position(&self) -> DecompressPosition {
+        // The decompressor only exposes its bit buffer at block boundaries.
+        let at_boundary = self.at_boundary && self.dict_avail == 0;
+        let unused_bits = match self.inner.decomp.block_boundary_state() {
+            Some(state) if at_boundary => state.num_bits,
+            _ => 0,
+        };
+        DecompressPosition {
+            unused_bits,
+            block_boundary: at_boundary,
+            last_block: self.last_status == TINFLStatus::Done,
+            after_header: false,
+        }
+    }
+
+    fn prime(&mut self, bits: u8, value: u16) -> Result<(), DecompressError> {
+        // The decompressor can only be resumed with a few bits at a block
+        // boundary, which is also where a raw stream starts.
+        let at_start = self.format == DataFormat::Raw && self.first_call && self.total_in == 0;
+        let mut state = match self.inner.decomp.block_boundary_state() {
+            Some(state) if self.at_boundary || at_start => state,
+            None if at_start => BlockBoundaryState::default(),
+            _ => return mem::decompress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some(
+                    "the miniz_oxide backend can only prime the decompressor at a block boundary",
+                )),
+            ),
+        };
+        if state.num_bits + bits > 7 {
+            return mem::decompress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some(
+                    "the miniz_oxide backend can't hold more than 7 pending bits",
+                )),
+            );
+        }
+        state.bit_buf |= (value << state.num_bits) as u8;
+        state.num_bits += bits;
+        self.inner.decomp = DecompressorOxide::from_block_boundary_state(&state);
+        Ok(())
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        let start =
+            (self.dict_ofs + TINFL_LZ_DICT_SIZE - self.window_len) & (TINFL_LZ_DICT_SIZE - 1);
+        let mut dictionary = Vec::with_capacity(self.window_len);
+        if start + self.window_len <= TINFL_LZ_DICT_SIZE {
+            dictionary.extend_from_slice(&self.inner.dict[start..start + self.window_len]);
+        } else {
+            dictionary.extend_from_slice(&self.inner.dict[start..]);
+            dictionary.extend_from_slice(&self.inner.dict[..self.dict_ofs]);
+        }
+        dictionary
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
+        let adler = mz_adler32_oxide(MZ_ADLER32_INIT, dictionary);
+        match self.header {
+            Header::NeedDict { dict_id, .. } if dict_id != adler => {
+                return mem::decompress_need_dict(dict_id)
+            }
+            Header::NeedDict { cmf, flg, .. } => self.start_after_header(cmf, flg),
+            Header::Done
+                if !self.gzip && self.format == DataFormat::Raw && self.dict_avail == 0 => {}
+            _ => {
+                return mem::decompress_failed(
+                    ErrorKind::InvalidParameter,
+                    ErrorMessage(Some(
+                        "a dictionary can't be set at this point of the stream",
+                    )),
+                )
+            }
+        }
+
+        // Later data refers to the dictionary as if it was decompressed
+        // right before it.
+        let dictionary = &dictionary[dictionary.len().saturating_sub(TINFL_LZ_DICT_SIZE)..];
+        let first = (TINFL_LZ_DICT_SIZE - self.dict_ofs).min(dictionary.len());
+        self.inner.dict[self.dict_ofs..][..first].copy_from_slice(&dictionary[..first]);
+        self.inner.dict[..dictionary.len() - first].copy_from_slice(&dictionary[first..]);
+        self.dict_ofs = (self.dict_ofs + dictionary.len()) & (TINFL_LZ_DICT_SIZE - 1);
+        self.window_len = (self.window_len + dictionary.len()).min(TINFL_LZ_DICT_SIZE);
+        self.first_call = false;
+        Ok(adler)
+    }
+
+    fn try_clone(&mut self) -> Result<Box<dyn InflateBackend>, DecompressError> {
+        Ok(Box::new(self.clone()))
+    }
 }
 
 impl Backend for Inflate {
@@ -114,10 +711,29 @@ impl Backend for Inflate {
     fn total_out(&self) -> u64 {
         self.total_out
     }
+
+    fn memory_usage(&self) -> usize {
+        INFLATE_MEMORY
+    }
 }
 
+#[derive(Clone)]
 pub struct Deflate {
     inner: Box<CompressorOxide>,
+    format: Format,
+    level: Compression,
+    strategy: Strategy,
+    window_bits: u8,
+    // The checksum of the input so far, if the wrapper is written here rather
+    // than by miniz_oxide, which only writes zlib headers without a
+    // dictionary.
+    trailer: Option<Trailer>,
+    // Output which wasn't written yet, ahead of the compressor's output.
+    pending: Vec<u8>,
+    // Whether a dictionary was set, which is part of the compressor's state.
+    dictionary: bool,
+    // Only kept if requested with `CompressBuilder::keep_dictionary`.
+    window: Option<Window>,
     total_in: u64,
     total_out: u64,
 }
@@ -132,6 +748,87 @@ impl fmt::Debug for Deflate {
     }
 }
 
+/// The error for a failure of the compressor, with the message zlib has for
+/// the same return code.
+fn compress_error<T>(err: MZError) -> Result<T, CompressError> {
+    let (kind, msg) = match err {
+        MZError::Stream | MZError::Param => (ErrorKind::InvalidParameter, "stream error"),
+        MZError::Data => (ErrorKind::InvalidData, "data error"),
+        MZError::Mem => (ErrorKind::OutOfMemory, "insufficient memory"),
+        MZError::Buf => (ErrorKind::Other, "buffer error"),
+        MZError::Version => (ErrorKind::Other, "incompatible version"),
+        _ => (ErrorKind::Other, "file error"),
+    };
+    mem::compress_failed(kind, ErrorMessage(Some(msg)))
+}
+
+#[derive(Clone)]
+enum Trailer {
+    Zlib(u32),
+    Gzip(Crc),
+}
+
+impl Deflate {
+    /// The format of the stream written by miniz_oxide itself.
+    fn data_format(&self) -> DataFormat {
+        match self.format {
+            Format::Zlib => DataFormat::Zlib,
+            _ => DataFormat::Raw,
+        }
+    }
+
+    fn start_stream(&mut self) {
+        if self.format == Format::Gzip {
+            // The extra flags are set the same way as by zlib.
+            let xfl = match self.level.level() {
+                9.. => 2,
+                0 | 1 => 4,
+                _ if matches!(
+                    self.strategy,
+                    Strategy::HuffmanOnly | Strategy::Rle | Strategy::Fixed
+                ) =>
+                {
+                    4
+                }
+                _ => 0,
+            };
+            self.pending
+                .extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, xfl, 255]);
+            self.trailer = Some(Trailer::Gzip(Crc::new()));
+        }
+    }
+
+    fn write_pending(&mut self, output: &mut [u8]) -> usize {
+        let n = self.pending.len().min(output.len());
+        output[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        self.total_out += n as u64;
+        n
+    }
+}
+
+/// The header of a zlib stream using a dictionary, which is the dictionary's
+/// checksum away from the data. `FLEVEL` is set the same way as by zlib.
+fn zlib_dict_header(level: Compression, strategy: Strategy, window_bits: u8) -> [u8; 2] {
+    let flevel = match level.level() {
+        _ if matches!(
+            strategy,
+            Strategy::HuffmanOnly | Strategy::Rle | Strategy::Fixed
+        ) =>
+        {
+            0
+        }
+        0 | 1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    };
+    let cmf = (window_bits - 8) << 4 | 8;
+    let flg = flevel << 6 | 0x20;
+    let check = (u16::from(cmf) << 8 | u16::from(flg)) % 31;
+    [cmf, flg + (31 - check as u8)]
+}
+
 impl From<FlushCompress> for MZFlush {
     fn from(value: FlushCompress) -> Self {
         match value {
@@ -140,56 +837,291 @@ impl From<FlushCompress> for MZFlush {
             FlushCompress::Sync => Self::Sync,
             FlushCompress::Full => Self::Full,
             FlushCompress::Finish => Self::Finish,
+            // miniz_oxide can't end a block without aligning the output.
+            FlushCompress::Block => Self::Sync,
         }
     }
 }
 
-impl DeflateBackend for Deflate {
-    fn make(level: Compression, zlib_header: bool, _window_bits: u8) -> Self {
-        // Check in case the integer value changes at some point. Unlike the other zlib
-        // implementations, miniz_oxide actually has a compression level 10.
-        debug_assert!(level.level() <= 10);
+impl From<Strategy> for CompressionStrategy {
+    fn from(value: Strategy) -> Self {
+        match value {
+            Strategy::Default => Self::Default,
+            Strategy::Filtered => Self::Filtered,
+            Strategy::HuffmanOnly => Self::HuffmanOnly,
+            Strategy::Rle => Self::RLE,
+            Strategy::Fixed => Self::Fixed,
+        }
+    }
+}
 
-        let mut inner: Box<CompressorOxide> = Box::default();
-        let format = format_from_bool(zlib_header);
-        inner.set_format_and_level(format, level.level().try_into().unwrap_or(1));
+impl Deflate {
+    pub fn make(config: &CompressBuilder) -> Result<Self, CompressError> {
+        if config.mem_level != mem::DEFAULT_MEM_LEVEL {
+            return mem::compress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some(
+                    "mem_level is not supported by the miniz_oxide backend",
+                )),
+            );
+        }
+        let window_memory = if config.keep_dictionary {
+            WINDOW_SIZE
+        } else {
+            0
+        };
+        if matches!(config.memory_limit, Some(limit) if limit < DEFLATE_MEMORY + window_memory) {
+            return mem::compress_failed(
+                ErrorKind::OutOfMemory,
+                ErrorMessage(Some("insufficient memory")),
+            );
+        }
 
-        Deflate {
-            inner,
+        let mut deflate = Deflate {
+            inner: Box::new(compressor(
+                match config.format {
+                    Format::Zlib => DataFormat::Zlib,
+                    _ => DataFormat::Raw,
+                },
+                config.level,
+                config.strategy,
+                config.window_bits,
+            )),
+            format: config.format,
+            level: config.level,
+            strategy: config.strategy,
+            window_bits: config.window_bits,
+            trailer: None,
+            pending: Vec::new(),
+            dictionary: false,
+            window: config.keep_dictionary.then(|| Window::new(WINDOW_SIZE)),
             total_in: 0,
             total_out: 0,
-        }
+        };
+        deflate.start_stream();
+        Ok(deflate)
     }
+}
 
+impl DeflateBackend for Deflate {
     fn compress(
         &mut self,
         input: &[u8],
         output: &mut [u8],
         flush: FlushCompress,
     ) -> Result<Status, CompressError> {
+        let n = self.write_pending(output);
+        let output = &mut output[n..];
+        if !self.pending.is_empty() || (n > 0 && output.is_empty()) {
+            return Ok(if n > 0 { Status::Ok } else { Status::BufError });
+        }
+
         let mz_flush = flush.into();
         let res = deflate::stream::deflate(&mut self.inner, input, output, mz_flush);
-        self.total_in += res.bytes_consumed as u64;
+        let consumed = &input[..res.bytes_consumed];
+        self.total_in += consumed.len() as u64;
         self.total_out += res.bytes_written as u64;
+        if let Some(window) = &mut self.window {
+            window.push(consumed);
+        }
+
+        match &mut self.trailer {
+            Some(Trailer::Zlib(adler)) => *adler = mz_adler32_oxide(*adler, consumed),
+            Some(Trailer::Gzip(crc)) => crc.update(consumed),
+            None => {}
+        }
+        if res.status == Ok(MZStatus::StreamEnd) {
+            match self.trailer.take() {
+                Some(Trailer::Zlib(adler)) => self.pending.extend_from_slice(&adler.to_be_bytes()),
+                Some(Trailer::Gzip(crc)) => {
+                    self.pending.extend_from_slice(&crc.sum().to_le_bytes());
+                    self.pending.extend_from_slice(&crc.amount().to_le_bytes());
+                }
+                None => {}
+            }
+            self.write_pending(&mut output[res.bytes_written..]);
+            if !self.pending.is_empty() {
+                return Ok(Status::Ok);
+            }
+        }
 
         match res.status {
             Ok(status) => match status {
                 MZStatus::Ok => Ok(Status::Ok),
                 MZStatus::StreamEnd => Ok(Status::StreamEnd),
-                MZStatus::NeedDict => mem::compress_failed(ErrorMessage),
-            },
-            Err(status) => match status {
-                MZError::Buf => Ok(Status::BufError),
-                _ => mem::compress_failed(ErrorMessage),
+                MZStatus::NeedDict => {
+                    mem::compress_failed(ErrorKind::Other, ErrorMessage(Some("need dictionary")))
+                }
             },
+            Err(MZError::Buf) => Ok(Status::BufError),
+            Err(err) => compress_error(err),
         }
     }
 
     fn reset(&mut self) {
         self.total_in = 0;
         self.total_out = 0;
-        self.inner.reset();
+        self.trailer = None;
+        self.pending.clear();
+        self.dictionary = false;
+        if let Some(window) = &mut self.window {
+            window.clear();
+        }
+        if self.inner.data_format() == self.data_format() {
+            self.inner.reset();
+        } else {
+            // Let miniz_oxide write the zlib wrapper again.
+            *self.inner = compressor(
+                self.data_format(),
+                self.level,
+                self.strategy,
+                self.window_bits,
+            );
+        }
+        self.start_stream();
+    }
+
+    fn bound(&self, input_len: usize) -> usize {
+        // Headers written here are already pending.
+        let wrap_len = match (&self.trailer, self.inner.data_format()) {
+            (Some(Trailer::Zlib(_)), _) => 4,
+            (Some(Trailer::Gzip(_)), _) => 8,
+            (None, DataFormat::Zlib) => 6,
+            (None, _) => 0,
+        };
+        deflate_bound(input_len)
+            .saturating_add(wrap_len)
+            .saturating_add(self.pending.len())
+    }
+
+    fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    fn set_params(&mut self, level: Compression, strategy: Strategy) -> Result<(), CompressError> {
+        let format = self.inner.data_format();
+        if self.total_in == 0 && self.total_out == 0 && !self.dictionary {
+            // Nothing was compressed yet, so the compressor can simply be
+            // replaced by one using the new parameters.
+            *self.inner = compressor(format, level, strategy, self.window_bits);
+            if self.format == Format::Gzip {
+                // The header reflects the level.
+                self.pending.clear();
+                self.level = level;
+                self.strategy = strategy;
+                self.start_stream();
+            }
+        } else if strategy == Strategy::Default
+            && (self.window_bits == MZ_DEFAULT_WINDOW_BITS as u8 || level.level() == 0)
+        {
+            // miniz_oxide only supports changing the level of a running
+            // compressor, which also resets its strategy to the default one.
+            self.inner
+                .set_format_and_level(format, level.level().try_into().unwrap_or(1));
+        } else if strategy == Strategy::Default {
+            return mem::compress_failed(ErrorKind::InvalidParameter, ErrorMessage(Some(
+                "the miniz_oxide backend can't change the level of a running compressor with a smaller window",
+            )));
+        } else {
+            return mem::compress_failed(ErrorKind::InvalidParameter, ErrorMessage(Some(
+                "the miniz_oxide backend can only change to the default strategy after compression started",
+            )));
+        }
+        self.level = level;
+        self.strategy = strategy;
+        Ok(())
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        self.window.as_ref().map_or_else(Vec::new, Window::get)
     }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
+        if self.total_in != 0 || self.total_out != 0 {
+            return mem::compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some(
+                    "the miniz_oxide backend can only set a dictionary before compressing",
+                )),
+            );
+        }
+        let adler = mz_adler32_oxide(MZ_ADLER32_INIT, dictionary);
+        match self.format {
+            Format::Deflate => {}
+            Format::Zlib => {
+                if self.inner.data_format() == DataFormat::Zlib {
+                    *self.inner =
+                        compressor(DataFormat::Raw, self.level, self.strategy, self.window_bits);
+                }
+                self.pending.clear();
+                self.pending.extend_from_slice(&zlib_dict_header(
+                    self.level,
+                    self.strategy,
+                    self.window_bits,
+                ));
+                self.pending.extend_from_slice(&adler.to_be_bytes());
+                self.trailer = Some(Trailer::Zlib(MZ_ADLER32_INIT));
+            }
+            Format::Gzip => {
+                return mem::compress_failed(
+                    ErrorKind::InvalidParameter,
+                    ErrorMessage(Some("a dictionary can't be used with gzip framing")),
+                )
+            }
+        }
+
+        // miniz_oxide can't be given a dictionary directly. Compressing it
+        // and throwing away the output leaves it in the window, and the sync
+        // flush lets the stream continue with a new block.
+        let mut dictionary = &dictionary[dictionary.len().saturating_sub(WINDOW_SIZE)..];
+        self.dictionary = true;
+        if let Some(window) = &mut self.window {
+            window.push(dictionary);
+        }
+        let mut scratch = vec![0; deflate_bound(dictionary.len())];
+        loop {
+            let res =
+                deflate::stream::deflate(&mut self.inner, dictionary, &mut scratch, MZFlush::Sync);
+            dictionary = &dictionary[res.bytes_consumed..];
+            match res.status {
+                Ok(_) | Err(MZError::Buf) => {}
+                Err(err) => return compress_error(err),
+            }
+            if dictionary.is_empty() && res.bytes_written < scratch.len() {
+                break;
+            }
+        }
+        Ok(adler)
+    }
+
+    fn try_clone(&mut self) -> Result<Box<dyn DeflateBackend>, CompressError> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+fn compressor(
+    format: DataFormat,
+    level: Compression,
+    strategy: Strategy,
+    window_bits: u8,
+) -> CompressorOxide {
+    // Unlike the other zlib implementations, miniz_oxide actually has a
+    // compression level 10, higher levels are clamped to it. Smaller windows
+    // are supported by limiting the level and strategy.
+    CompressorOxide::with_params(
+        format,
+        level.level().try_into().unwrap_or(1),
+        strategy.into(),
+        window_bits,
+    )
+}
+
+/// The bound of miniz' `mz_deflateBound`, which holds for any compression
+/// parameters. The constant overhead is large enough for any wrapper.
+pub fn deflate_bound(input_len: usize) -> usize {
+    let compressed = input_len.saturating_mul(110) / 100;
+    let stored = input_len.saturating_add((input_len / (31 * 1024) + 1).saturating_mul(5));
+    compressed.max(stored).saturating_add(128)
 }
 
 impl Backend for Deflate {
@@ -202,4 +1134,10 @@ impl Backend for Deflate {
     fn total_out(&self) -> u64 {
         self.total_out
     }
+
+    fn memory_usage(&self) -> usize {
+        DEFLATE_MEMORY
+            + self.window.as_ref().map_or(0, Window::memory_usage)
+            + self.pending.capacity()
+    }
 }
@@ -1,79 +1,146 @@
 //! This module contains backend-specific code.
 
-use crate::mem::{CompressError, DecompressError, FlushCompress, FlushDecompress, Status};
-use crate::Compression;
-use std::mem::MaybeUninit;
-
-fn initialize_buffer(output: &mut [MaybeUninit<u8>]) -> &mut [u8] {
-    // SAFETY: Here we zero-initialize the output and cast it to [u8]
-    unsafe {
-        output.as_mut_ptr().write_bytes(0, output.len());
-        &mut *(output as *mut [MaybeUninit<u8>] as *mut [u8])
-    }
-}
+use crate::backend::{Backend, DeflateBackend, InflateBackend};
+use crate::mem::{
+    CompressBuilder, CompressError, DecompressError, FlushCompress, FlushDecompress, Status,
+    Strategy,
+};
+use crate::{BackendInfo, Capabilities};
 
-/// Traits specifying the interface of the backends.
+/// zlib's conservative upper bound on the size of a raw deflate stream
+/// compressing `input_len` bytes, which holds for any compression parameters.
 ///
-/// Sync + Send are added as a condition to ensure they are available
-/// for the frontend.
-pub trait Backend: Sync + Send {
-    fn total_in(&self) -> u64;
-    fn total_out(&self) -> u64;
+/// Level 0 with a small memory level emits short stored blocks, whose bound
+/// has a larger constant overhead than the conservative bound for tiny inputs.
+#[cfg(feature = "any_zlib")]
+fn zlib_deflate_bound(input_len: usize) -> usize {
+    let conservative = input_len
+        .saturating_add(input_len.saturating_add(7) >> 3)
+        .saturating_add(input_len.saturating_add(63) >> 6)
+        .saturating_add(5);
+    let stored = input_len
+        .saturating_add(input_len >> 5)
+        .saturating_add(input_len >> 7)
+        .saturating_add(input_len >> 11)
+        .saturating_add(7);
+    conservative.max(stored)
+}
+
+/// A search for the empty stored block emitted by a full flush, for the
+/// backends which can't skip to it themselves. It's found the same way as by
+/// zlib's `inflateSync`, which looks for the `00 00 ff ff` of the block's
+/// length and its complement.
+#[cfg(any(feature = "zlib-rs", feature = "miniz_oxide"))]
+#[derive(Clone, Copy, Default)]
+struct SyncSearch {
+    // The number of bytes of the marker found so far.
+    have: u8,
 }
 
-pub trait InflateBackend: Backend {
-    fn make(zlib_header: bool, window_bits: u8) -> Self;
-    fn decompress(
-        &mut self,
-        input: &[u8],
-        output: &mut [u8],
-        flush: FlushDecompress,
-    ) -> Result<Status, DecompressError>;
-    fn decompress_uninit(
-        &mut self,
-        input: &[u8],
-        output: &mut [MaybeUninit<u8>],
-        flush: FlushDecompress,
-    ) -> Result<Status, DecompressError> {
-        self.decompress(input, initialize_buffer(output), flush)
+#[cfg(any(feature = "zlib-rs", feature = "miniz_oxide"))]
+impl SyncSearch {
+    /// Searches `input` for the rest of the marker, and returns the number of
+    /// bytes searched and whether the marker is complete. The search
+    /// continues with the next input if it isn't.
+    fn search(&mut self, input: &[u8]) -> (usize, bool) {
+        let mut searched = 0;
+        while searched < input.len() && self.have < 4 {
+            let expected = if self.have < 2 { 0 } else { 0xff };
+            if input[searched] == expected {
+                self.have += 1;
+            } else if input[searched] != 0 {
+                self.have = 0;
+            } else {
+                self.have = 4 - self.have;
+            }
+            searched += 1;
+        }
+        let found = self.have == 4;
+        if found {
+            self.have = 0;
+        }
+        (searched, found)
     }
-    fn reset(&mut self, zlib_header: bool);
 }
 
-pub trait DeflateBackend: Backend {
-    fn make(level: Compression, zlib_header: bool, window_bits: u8) -> Self;
-    fn compress(
-        &mut self,
-        input: &[u8],
-        output: &mut [u8],
-        flush: FlushCompress,
-    ) -> Result<Status, CompressError>;
-    fn compress_uninit(
-        &mut self,
-        input: &[u8],
-        output: &mut [MaybeUninit<u8>],
-        flush: FlushCompress,
-    ) -> Result<Status, CompressError> {
-        self.compress(input, initialize_buffer(output), flush)
+/// The data most recently passed through a stream, up to the size of the
+/// window, for `get_dictionary` with the backends which don't expose their
+/// window.
+#[cfg(any(feature = "zlib-rs", feature = "miniz_oxide"))]
+#[derive(Clone)]
+struct Window {
+    buf: Vec<u8>,
+    size: usize,
+    // Where the oldest data starts once the buffer is full.
+    start: usize,
+}
+
+#[cfg(any(feature = "zlib-rs", feature = "miniz_oxide"))]
+impl Window {
+    fn new(size: usize) -> Window {
+        Window {
+            buf: Vec::new(),
+            size,
+            start: 0,
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        if data.len() >= self.size {
+            self.buf.clear();
+            self.buf.extend_from_slice(&data[data.len() - self.size..]);
+            self.start = 0;
+            return;
+        }
+        let n = (self.size - self.buf.len()).min(data.len());
+        self.buf.extend_from_slice(&data[..n]);
+        let rest = &data[n..];
+        let first = (self.size - self.start).min(rest.len());
+        self.buf[self.start..][..first].copy_from_slice(&rest[..first]);
+        self.buf[..rest.len() - first].copy_from_slice(&rest[first..]);
+        self.start = (self.start + rest.len()) % self.size;
+    }
+
+    fn get(&self) -> Vec<u8> {
+        [&self.buf[self.start..], &self.buf[..self.start]].concat()
+    }
+
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.start = 0;
+    }
+
+    /// The memory the window takes once it's full, which is what the static
+    /// estimates of the memory of a stream count.
+    fn memory_usage(&self) -> usize {
+        self.size
     }
-    fn reset(&mut self);
 }
 
+#[cfg(feature = "any_c_zlib")]
+mod allocator;
+
+// Every backend whose feature is enabled is built, so that it can be used
+// through the types of the `backend` module. Only the default one is
+// re-exported here, so the others allow the code which those types don't use.
+
 // Default to Rust implementation unless explicitly opted in to a different backend.
 #[cfg(feature = "any_c_zlib")]
-mod c;
+pub(crate) mod c;
 #[cfg(feature = "any_c_zlib")]
 pub use self::c::*;
 
-// Only bring in `zlib-rs` if there is no C-based backend.
-#[cfg(all(not(feature = "any_c_zlib"), feature = "zlib-rs"))]
-mod zlib_rs;
+// Only use `zlib-rs` by default if there is no C-based backend.
+#[cfg(feature = "zlib-rs")]
+#[cfg_attr(feature = "any_c_zlib", allow(dead_code))]
+pub(crate) mod zlib_rs;
 #[cfg(all(not(feature = "any_c_zlib"), feature = "zlib-rs"))]
 pub use self::zlib_rs::*;
 
-// Use miniz_oxide when no fully compliant zlib is selected.
-#[cfg(all(not(feature = "any_zlib"), feature = "miniz_oxide"))]
-mod miniz_oxide;
+// Use miniz_oxide by default when no fully compliant zlib is selected.
+#[cfg(feature = "miniz_oxide")]
+#[cfg_attr(feature = "any_zlib", allow(dead_code))]
+pub(crate) mod miniz_oxide;
 #[cfg(all(not(feature = "any_zlib"), feature = "miniz_oxide"))]
 pub use self::miniz_oxide::*;
 
@@ -81,6 +148,16 @@ pub use self::miniz_oxide::*;
 #[cfg(not(feature = "any_impl"))]
 compile_error!("No compression backend selected; enable one of `zlib`, `zlib-ng`, `zlib-rs`, or the default `rust_backend` feature.");
 
+/// The message of an error, which is shared by all backends.
+#[derive(Clone, Default)]
+pub struct ErrorMessage(pub(crate) Option<&'static str>);
+
+impl ErrorMessage {
+    pub fn get(&self) -> Option<&str> {
+        self.0
+    }
+}
+
 impl std::fmt::Debug for ErrorMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         self.get().fmt(f)
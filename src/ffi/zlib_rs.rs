@@ -5,7 +5,7 @@
 //! - `Deflate` for compression, implements the `Backend` and `DeflateBackend` trait
 //! - `Inflate` for decompression, implements the `Backend` and `InflateBackend` trait
 //!
-//! Additionally the backend provides a number of constants, and a `ErrorMessage` type.
+//! Additionally the backend provides a number of constants.
 //!
 //! ## Allocation
 //!
@@ -16,21 +16,49 @@
 //!
 //! With zlib_rs the state is not self-referential and hence no boxing is needed. The `new` methods
 //! internally do allocate space for the (de)compression state.
+//!
+//! ## Stable API
+//!
+//! This backend only uses the stable API of zlib_rs, which doesn't cover everything zlib offers.
+//! The features it lacks, such as copying a stream or compression strategies other than the
+//! default one, return an error of kind `Unsupported`.
 
 use std::fmt;
+use std::panic::AssertUnwindSafe;
 
-use ::zlib_rs::{DeflateFlush, InflateError, InflateFlush};
+use ::zlib_rs::{DeflateError, DeflateFlush, InflateError, InflateFlush};
 
 pub const MZ_NO_FLUSH: isize = DeflateFlush::NoFlush as isize;
 pub const MZ_PARTIAL_FLUSH: isize = DeflateFlush::PartialFlush as isize;
 pub const MZ_SYNC_FLUSH: isize = DeflateFlush::SyncFlush as isize;
 pub const MZ_FULL_FLUSH: isize = DeflateFlush::FullFlush as isize;
 pub const MZ_FINISH: isize = DeflateFlush::Finish as isize;
+pub const MZ_BLOCK: isize = DeflateFlush::Block as isize;
+pub const MZ_TREES: isize = InflateFlush::Trees as isize;
 
 pub const MZ_DEFAULT_WINDOW_BITS: core::ffi::c_int = 15;
 
 use super::*;
-use crate::mem::{compress_failed, decompress_failed};
+use crate::mem::{self, compress_failed, decompress_failed, ErrorKind, Format};
+use crate::Compression;
+
+pub fn backend_info() -> BackendInfo {
+    BackendInfo {
+        name: "zlib-rs",
+        version: None,
+        capabilities: Capabilities {
+            window_bits: true,
+            dictionary: true,
+            set_level: true,
+            gzip: true,
+            strategies: false,
+            mem_level: false,
+            try_clone: false,
+            block_boundaries: false,
+            prime: false,
+        },
+    }
+}
 
 impl From<::zlib_rs::Status> for crate::mem::Status {
     fn from(value: ::zlib_rs::Status) -> Self {
@@ -42,17 +70,35 @@ impl From<::zlib_rs::Status> for crate::mem::Status {
     }
 }
 
-#[derive(Clone, Default)]
-pub struct ErrorMessage(Option<&'static str>);
+/// The memory an `Inflate` needs, as documented by zlib: the window, plus
+/// about 7 KiB for the state, and the copy of the window kept for
+/// `get_dictionary`. zlib_rs doesn't report its allocations through the
+/// stable API, so this estimate is all `memory_usage` and the memory limit go
+/// by.
+fn inflate_memory(window_bits: u8) -> usize {
+    2 * (1 << window_bits) + 7 * 1024
+}
 
-impl ErrorMessage {
-    pub fn get(&self) -> Option<&str> {
-        self.0
-    }
+/// The memory a `Deflate` needs, as documented by zlib: the window and the
+/// hash chains, the hash table for the default memory level, plus a few
+/// kilobytes for the state. Like [`inflate_memory`], this is an estimate.
+fn deflate_memory(window_bits: u8) -> usize {
+    (1 << (window_bits + 2)) + (1 << (mem::DEFAULT_MEM_LEVEL + 9)) + 6 * 1024
 }
 
 pub struct Inflate {
-    pub(crate) inner: ::zlib_rs::Inflate,
+    // A panic can't leave the stream in a state which is unsafe to use, at
+    // worst in one which only returns errors.
+    inner: AssertUnwindSafe<::zlib_rs::Inflate>,
+    memory: usize,
+    format: Format,
+    window_bits: u8,
+    sync: SyncSearch,
+    // The stable API doesn't expose the window, so the output is kept here.
+    window: Window,
+    // After `sync`, the number of bytes of the zlib or gzip trailer which are
+    // left to skip once the deflate data ended.
+    trailer: Option<usize>,
     // NOTE: these counts do not count the dictionary.
     total_in: u64,
     total_out: u64,
@@ -69,56 +115,167 @@ impl fmt::Debug for Inflate {
     }
 }
 
-impl From<FlushDecompress> for DeflateFlush {
+impl From<FlushDecompress> for InflateFlush {
     fn from(value: FlushDecompress) -> Self {
         match value {
             FlushDecompress::None => Self::NoFlush,
             FlushDecompress::Sync => Self::SyncFlush,
             FlushDecompress::Finish => Self::Finish,
+            FlushDecompress::Block => Self::Block,
+            FlushDecompress::Trees => Self::Trees,
         }
     }
 }
 
-impl InflateBackend for Inflate {
-    fn make(zlib_header: bool, window_bits: u8) -> Self {
-        Inflate {
-            inner: ::zlib_rs::Inflate::new(zlib_header, window_bits),
+impl Inflate {
+    pub fn make(
+        zlib_header: bool,
+        window_bits: u8,
+        memory_limit: Option<usize>,
+    ) -> Result<Self, DecompressError> {
+        // Gzip streams are selected by adding 16 to the window bits.
+        let memory = inflate_memory(window_bits & 15);
+        if matches!(memory_limit, Some(limit) if limit < memory) {
+            return decompress_failed(
+                ErrorKind::OutOfMemory,
+                ErrorMessage(Some("insufficient memory")),
+            );
+        }
+        let format = if window_bits > 15 {
+            Format::Gzip
+        } else if zlib_header {
+            Format::Zlib
+        } else {
+            Format::Deflate
+        };
+        Ok(Inflate {
+            inner: AssertUnwindSafe(::zlib_rs::Inflate::new(zlib_header, window_bits)),
+            memory,
+            format,
+            window_bits: window_bits & 15,
+            sync: SyncSearch::default(),
+            window: Window::new(1 << (window_bits & 15)),
+            trailer: None,
             total_in: 0,
             total_out: 0,
-        }
+        })
     }
 
+    fn decompress_error<T>(&self, err: InflateError) -> Result<T, DecompressError> {
+        let kind = match err {
+            InflateError::DataError => ErrorKind::InvalidData,
+            InflateError::MemError => ErrorKind::OutOfMemory,
+            InflateError::StreamError => ErrorKind::InvalidParameter,
+            InflateError::NeedDict { dict_id } => return mem::decompress_need_dict(dict_id),
+        };
+        decompress_failed(kind, ErrorMessage(self.inner.error_message()))
+    }
+}
+
+impl InflateBackend for Inflate {
     fn decompress(
         &mut self,
         input: &[u8],
         output: &mut [u8],
         flush: FlushDecompress,
     ) -> Result<Status, DecompressError> {
-        let flush = match flush {
-            FlushDecompress::None => InflateFlush::NoFlush,
-            FlushDecompress::Sync => InflateFlush::SyncFlush,
-            FlushDecompress::Finish => InflateFlush::Finish,
-        };
-
         let total_in_start = self.inner.total_in();
         let total_out_start = self.inner.total_out();
 
-        let result = self.inner.decompress(input, output, flush);
+        let result = self.inner.decompress(input, output, flush.into());
 
-        self.total_in += self.inner.total_in() - total_in_start;
-        self.total_out += self.inner.total_out() - total_out_start;
+        // The total counters of zlib_rs are as wide as a c_ulong, so they might
+        // overflow while processing large amounts of data.
+        let consumed = self.inner.total_in().wrapping_sub(total_in_start);
+        let written = self.inner.total_out().wrapping_sub(total_out_start);
+        self.total_in += consumed;
+        self.total_out += written;
+        self.window.push(&output[..written as usize]);
 
         match result {
+            Ok(::zlib_rs::Status::StreamEnd) => match &mut self.trailer {
+                Some(left) => {
+                    let n = (*left).min(input.len() - consumed as usize);
+                    *left -= n;
+                    self.total_in += n as u64;
+                    if *left == 0 {
+                        Ok(Status::StreamEnd)
+                    } else if consumed != 0 || written != 0 || n != 0 {
+                        Ok(Status::Ok)
+                    } else {
+                        Ok(Status::BufError)
+                    }
+                }
+                None => Ok(Status::StreamEnd),
+            },
             Ok(status) => Ok(status.into()),
-            Err(InflateError::NeedDict { dict_id }) => crate::mem::decompress_need_dict(dict_id),
-            Err(_) => self.decompress_error(),
+            Err(err) => self.decompress_error(err),
         }
     }
 
     fn reset(&mut self, zlib_header: bool) {
+        self.reset_format(if zlib_header {
+            Format::Zlib
+        } else {
+            Format::Deflate
+        });
+    }
+
+    fn reset_format(&mut self, format: Format) {
+        self.format = format;
+        self.window_bits = MZ_DEFAULT_WINDOW_BITS as u8;
+        self.sync = SyncSearch::default();
+        self.window = Window::new(1 << self.window_bits);
+        self.trailer = None;
         self.total_in = 0;
         self.total_out = 0;
-        self.inner.reset(zlib_header);
+        if format == Format::Gzip {
+            // The stable API can't reset to gzip framing, so start over.
+            let window_bits = MZ_DEFAULT_WINDOW_BITS as u8 + 16;
+            self.inner = AssertUnwindSafe(::zlib_rs::Inflate::new(true, window_bits));
+        } else {
+            self.inner.reset(format == Format::Zlib);
+        }
+    }
+
+    fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+        // The stable API of zlib_rs doesn't support this, so search for the
+        // empty stored block emitted by a full flush the same way zlib does.
+        let header_read = self.format != Format::Deflate && self.total_in != 0;
+        let (skipped, found) = self.sync.search(input);
+        self.total_in += skipped as u64;
+        if !found {
+            return decompress_failed(
+                ErrorKind::InvalidData,
+                ErrorMessage(Some("no sync point found")),
+            );
+        }
+
+        // Continue with a new block in a raw stream. Data following a full
+        // flush point doesn't refer to any previous data, but the checksum of
+        // a zlib or gzip stream can't be verified anymore, so its trailer is
+        // only skipped.
+        self.trailer = match self.format {
+            Format::Zlib if header_read => Some(4),
+            Format::Gzip if header_read => Some(8),
+            _ => None,
+        };
+        self.inner = AssertUnwindSafe(::zlib_rs::Inflate::new(false, self.window_bits));
+        Ok(skipped)
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
+        match self.inner.set_dictionary(dictionary) {
+            Ok(v) => {
+                self.window.push(dictionary);
+                Ok(v)
+            }
+            Err(err) => self.decompress_error(err),
+        }
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        self.window.get()
     }
 }
 
@@ -132,23 +289,24 @@ impl Backend for Inflate {
     fn total_out(&self) -> u64 {
         self.total_out
     }
-}
 
-impl Inflate {
-    fn decompress_error<T>(&self) -> Result<T, DecompressError> {
-        decompress_failed(ErrorMessage(self.inner.error_message()))
+    fn memory_usage(&self) -> usize {
+        self.memory
     }
+}
 
-    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
-        match self.inner.set_dictionary(dictionary) {
-            Ok(v) => Ok(v),
-            Err(_) => self.decompress_error(),
-        }
-    }
+pub fn deflate_bound(input_len: usize) -> usize {
+    zlib_deflate_bound(input_len)
 }
 
 pub struct Deflate {
-    pub(crate) inner: ::zlib_rs::Deflate,
+    // See `Inflate::inner`.
+    inner: AssertUnwindSafe<::zlib_rs::Deflate>,
+    // The parameters the stream was created with, kept up to date by `set_params`.
+    config: CompressBuilder,
+    // Only kept if requested with `CompressBuilder::keep_dictionary`, as the
+    // stable API doesn't expose the window.
+    window: Option<Window>,
     // NOTE: these counts do not count the dictionary.
     total_in: u64,
     total_out: u64,
@@ -165,51 +323,184 @@ impl fmt::Debug for Deflate {
     }
 }
 
-impl DeflateBackend for Deflate {
-    fn make(level: Compression, zlib_header: bool, window_bits: u8) -> Self {
-        // Check in case the integer value changes at some point.
-        debug_assert!(level.level() <= 9);
+impl From<FlushCompress> for DeflateFlush {
+    fn from(value: FlushCompress) -> Self {
+        match value {
+            FlushCompress::None => Self::NoFlush,
+            FlushCompress::Partial => Self::PartialFlush,
+            FlushCompress::Sync => Self::SyncFlush,
+            FlushCompress::Full => Self::FullFlush,
+            FlushCompress::Finish => Self::Finish,
+            FlushCompress::Block => Self::Block,
+        }
+    }
+}
+
+impl Deflate {
+    pub fn make(config: &CompressBuilder) -> Result<Self, CompressError> {
+        if config.level.level() > 9 {
+            return compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("invalid compression level")),
+            );
+        }
+        if config.strategy != Strategy::Default {
+            return compress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some("strategies are not supported by the zlib-rs backend")),
+            );
+        }
+        if config.mem_level != mem::DEFAULT_MEM_LEVEL {
+            return compress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some("mem_level is not supported by the zlib-rs backend")),
+            );
+        }
+        let window_memory = if config.keep_dictionary {
+            1 << config.window_bits
+        } else {
+            0
+        };
+        if matches!(config.memory_limit, Some(limit) if limit < deflate_memory(config.window_bits) + window_memory)
+        {
+            return compress_failed(
+                ErrorKind::OutOfMemory,
+                ErrorMessage(Some("insufficient memory")),
+            );
+        }
 
-        Deflate {
-            inner: ::zlib_rs::Deflate::new(level.level() as i32, zlib_header, window_bits),
+        let window_bits = config.zlib_window_bits();
+        Ok(Deflate {
+            inner: AssertUnwindSafe(::zlib_rs::Deflate::new(
+                config.level.level() as i32,
+                window_bits > 0,
+                window_bits.unsigned_abs() as u8,
+            )),
+            config: *config,
+            window: config
+                .keep_dictionary
+                .then(|| Window::new(1 << config.window_bits)),
             total_in: 0,
             total_out: 0,
-        }
+        })
+    }
+
+    fn compress_error<T>(&self, err: DeflateError) -> Result<T, CompressError> {
+        let kind = match err {
+            DeflateError::DataError => ErrorKind::InvalidData,
+            DeflateError::MemError => ErrorKind::OutOfMemory,
+            DeflateError::StreamError => ErrorKind::InvalidParameter,
+        };
+        compress_failed(kind, ErrorMessage(self.inner.error_message()))
     }
+}
 
+impl DeflateBackend for Deflate {
     fn compress(
         &mut self,
         input: &[u8],
         output: &mut [u8],
         flush: FlushCompress,
     ) -> Result<Status, CompressError> {
-        let flush = match flush {
-            FlushCompress::None => DeflateFlush::NoFlush,
-            FlushCompress::Partial => DeflateFlush::PartialFlush,
-            FlushCompress::Sync => DeflateFlush::SyncFlush,
-            FlushCompress::Full => DeflateFlush::FullFlush,
-            FlushCompress::Finish => DeflateFlush::Finish,
-        };
-
         let total_in_start = self.inner.total_in();
         let total_out_start = self.inner.total_out();
 
-        let result = self.inner.compress(input, output, flush);
+        let result = self.inner.compress(input, output, flush.into());
 
-        self.total_in += self.inner.total_in() - total_in_start;
-        self.total_out += self.inner.total_out() - total_out_start;
+        // The total counters of zlib_rs are as wide as a c_ulong, so they might
+        // overflow while processing large amounts of data.
+        let consumed = self.inner.total_in().wrapping_sub(total_in_start);
+        self.total_in += consumed;
+        self.total_out += self.inner.total_out().wrapping_sub(total_out_start);
+        if let Some(window) = &mut self.window {
+            window.push(&input[..consumed as usize]);
+        }
 
         match result {
             Ok(status) => Ok(status.into()),
-            Err(_) => self.compress_error(),
+            Err(err) => self.compress_error(err),
         }
     }
 
     fn reset(&mut self) {
         self.total_in = 0;
         self.total_out = 0;
+        if let Some(window) = &mut self.window {
+            window.clear();
+        }
         self.inner.reset();
     }
+
+    fn bound(&self, input_len: usize) -> usize {
+        // zlib's `deflateBound`: the tight bound of `compressBound` for the
+        // default window and memory level, and the conservative bound
+        // otherwise, each with the length of the wrapper. The dictionary id of
+        // a zlib stream is always accounted for.
+        let wrap_len = match self.config.format {
+            Format::Deflate => 0,
+            Format::Zlib => 6 + 4,
+            Format::Gzip => 18,
+        };
+        if self.config.window_bits == 15 {
+            ::zlib_rs::compress_bound(input_len)
+                .saturating_sub(6)
+                .saturating_add(wrap_len)
+        } else {
+            zlib_deflate_bound(input_len).saturating_add(wrap_len)
+        }
+    }
+
+    fn set_params(&mut self, level: Compression, strategy: Strategy) -> Result<(), CompressError> {
+        if level.level() > 9 {
+            return compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("invalid compression level")),
+            );
+        }
+        if strategy != Strategy::Default {
+            return compress_failed(
+                ErrorKind::Unsupported,
+                ErrorMessage(Some("strategies are not supported by the zlib-rs backend")),
+            );
+        }
+        // Any input that is still buffered is compressed with the previous
+        // level first, which writes to the output of the last call. Point that
+        // to an empty buffer, so nothing is written and the pending data is
+        // reported instead.
+        let _ = self.inner.compress(&[], &mut [], DeflateFlush::NoFlush);
+        match self.inner.set_level(level.level() as i32) {
+            Ok(::zlib_rs::Status::Ok) => {
+                self.config.level = level;
+                Ok(())
+            }
+            Ok(::zlib_rs::Status::BufError) => compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some(
+                    "pending data must be flushed before changing parameters",
+                )),
+            ),
+            Ok(::zlib_rs::Status::StreamEnd) => {
+                unreachable!("zlib-rs is known to never return the StreamEnd status")
+            }
+            Err(err) => self.compress_error(err),
+        }
+    }
+
+    fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
+        match self.inner.set_dictionary(dictionary) {
+            Ok(v) => {
+                if let Some(window) = &mut self.window {
+                    window.push(dictionary);
+                }
+                Ok(v)
+            }
+            Err(err) => self.compress_error(err),
+        }
+    }
+
+    fn get_dictionary(&self) -> Vec<u8> {
+        self.window.as_ref().map_or_else(Vec::new, Window::get)
+    }
 }
 
 impl Backend for Deflate {
@@ -222,32 +513,9 @@ impl Backend for Deflate {
     fn total_out(&self) -> u64 {
         self.total_out
     }
-}
 
-impl Deflate {
-    fn compress_error<T>(&self) -> Result<T, CompressError> {
-        compress_failed(ErrorMessage(self.inner.error_message()))
-    }
-
-    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
-        match self.inner.set_dictionary(dictionary) {
-            Ok(v) => Ok(v),
-            Err(_) => self.compress_error(),
-        }
-    }
-
-    pub fn set_level(&mut self, level: Compression) -> Result<(), CompressError> {
-        use ::zlib_rs::Status;
-
-        match self.inner.set_level(level.level() as i32) {
-            Ok(status) => match status {
-                Status::Ok => Ok(()),
-                Status::BufError => compress_failed(ErrorMessage(Some("insufficient space"))),
-                Status::StreamEnd => {
-                    unreachable!("zlib-rs is known to never return the StreamEnd status")
-                }
-            },
-            Err(_) => self.compress_error(),
-        }
+    fn memory_usage(&self) -> usize {
+        deflate_memory(self.config.window_bits)
+            + self.window.as_ref().map_or(0, Window::memory_usage)
     }
 }
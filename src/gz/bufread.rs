@@ -1,4 +1,5 @@
 use std::cmp;
+use std::convert::TryFrom;
 use std::io;
 use std::io::prelude::*;
 use std::mem;
@@ -6,7 +7,7 @@ use std::mem;
 use super::{corrupt, read_into, GzBuilder, GzHeader, GzHeaderParser};
 use crate::crc::CrcReader;
 use crate::deflate;
-use crate::Compression;
+use crate::{Compress, Compression, Decompress, DecompressLimits};
 
 fn copy(into: &mut [u8], from: &[u8], pos: &mut usize) -> usize {
     let min = cmp::min(into.len(), from.len() - *pos);
@@ -53,10 +54,10 @@ pub struct GzEncoder<R> {
     eof: bool,
 }
 
-pub fn gz_encoder<R: BufRead>(header: Vec<u8>, r: R, lvl: Compression) -> GzEncoder<R> {
+pub fn gz_encoder<R: BufRead>(header: Vec<u8>, r: R, compress: Compress) -> GzEncoder<R> {
     let crc = CrcReader::new(r);
     GzEncoder {
-        inner: deflate::bufread::DeflateEncoder::new(crc, lvl),
+        inner: deflate::bufread::DeflateEncoder::new_with_compress(crc, compress),
         header,
         pos: 0,
         eof: false,
@@ -75,6 +76,16 @@ impl<R: BufRead> GzEncoder<R> {
         GzBuilder::new().buf_read(r, level)
     }
 
+    /// Creates a new encoder which compresses the data read from `r` with the
+    /// given `compression`, which must produce a raw deflate stream.
+    ///
+    /// The emitted header is the same as for [`GzEncoder::new`] with the
+    /// default compression level.
+    pub fn new_with_compress(r: R, compression: Compress) -> GzEncoder<R> {
+        let header = GzBuilder::new().into_header(Compression::default());
+        gz_encoder(header, r, compression)
+    }
+
     fn read_footer(&mut self, into: &mut [u8]) -> io::Result<usize> {
         if self.pos == 8 {
             return Ok(0);
@@ -96,6 +107,15 @@ impl<R: BufRead> GzEncoder<R> {
 }
 
 impl<R> GzEncoder<R> {
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: Compression) {
+        self.inner.set_level(level)
+    }
+
     /// Acquires a reference to the underlying reader.
     pub fn get_ref(&self) -> &R {
         self.inner.get_ref().get_ref()
@@ -211,6 +231,10 @@ pub struct GzDecoder<R> {
     state: GzState,
     reader: CrcReader<deflate::bufread::DeflateDecoder<R>>,
     multi: bool,
+    // The number of members started, counting against the member limit.
+    members: u64,
+    // The uncompressed size from the trailer at the end of the input.
+    expected_size: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -226,12 +250,21 @@ pub fn reset_decoder_data<R>(decoder: &mut GzDecoder<R>) {
     decoder.state = GzState::Header(GzHeaderParser::new());
     decoder.reader.reset(); // reset CrcReader
     decoder.reader.get_mut().reset_data(); // reset DeflateDecoder
+    decoder.members = 1;
 }
 
 impl<R: BufRead> GzDecoder<R> {
     /// Creates a new decoder from the given reader, immediately parsing the
     /// gzip header.
-    pub fn new(mut r: R) -> GzDecoder<R> {
+    pub fn new(r: R) -> GzDecoder<R> {
+        GzDecoder::new_with_decompress(r, Decompress::new(false))
+    }
+
+    /// Creates a new decoder from the given reader, immediately parsing the
+    /// gzip header, which inflates the body with the given `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(mut r: R, decompression: Decompress) -> GzDecoder<R> {
         let mut header_parser = GzHeaderParser::new();
 
         let state = match header_parser.parse(&mut r) {
@@ -244,17 +277,76 @@ impl<R: BufRead> GzDecoder<R> {
 
         GzDecoder {
             state,
-            reader: CrcReader::new(deflate::bufread::DeflateDecoder::new(r)),
+            reader: CrcReader::new(deflate::bufread::DeflateDecoder::new_with_decompress(
+                r,
+                decompression,
+            )),
             multi: false,
+            members: 1,
+            expected_size: None,
         }
     }
 
+    /// Creates a new decoder from the given reader like [`GzDecoder::new`],
+    /// once the uncompressed size was read from the trailer at the end of the
+    /// reader, see [`GzDecoder::expected_size`].
+    ///
+    /// The gzip data must extend to the end of the reader, starting from its
+    /// current position.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of seeking in or reading from `r`.
+    pub fn new_seekable(mut r: R) -> io::Result<GzDecoder<R>>
+    where
+        R: Seek,
+    {
+        let expected_size = super::seek_trailer_size(&mut r)?;
+        Ok(GzDecoder::new(r).with_expected_size(expected_size))
+    }
+
+    pub(crate) fn with_expected_size(mut self, size: Option<u64>) -> GzDecoder<R> {
+        self.expected_size = size;
+        self
+    }
+
     fn multi(mut self, flag: bool) -> GzDecoder<R> {
         self.multi = flag;
         self
     }
 }
 
+impl<'a> GzDecoder<&'a [u8]> {
+    /// Creates a new decoder for gzip data in memory like [`GzDecoder::new`],
+    /// once the uncompressed size was read from its trailer, see
+    /// [`GzDecoder::expected_size`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::prelude::*;
+    /// use flate2::bufread::GzDecoder;
+    /// use flate2::write::GzEncoder;
+    /// use flate2::Compression;
+    ///
+    /// let mut e = GzEncoder::new(Vec::new(), Compression::default());
+    /// e.write_all(b"hello world").unwrap();
+    /// let bytes = e.finish().unwrap();
+    ///
+    /// let mut d = GzDecoder::new_from_slice(&bytes);
+    /// assert_eq!(d.expected_size(), Some(11));
+    /// let mut s = String::new();
+    /// d.read_to_string(&mut s).unwrap();
+    /// assert_eq!(s, "hello world");
+    /// ```
+    pub fn new_from_slice(data: &'a [u8]) -> GzDecoder<&'a [u8]> {
+        let expected_size = data.len().checked_sub(4).and_then(|at| {
+            super::trailer_size(<[u8; 4]>::try_from(&data[at..]).unwrap(), data.len() as u64)
+        });
+        GzDecoder::new(data).with_expected_size(expected_size)
+    }
+}
+
 impl<R> GzDecoder<R> {
     /// Returns the header associated with this stream, if it was valid
     pub fn header(&self) -> Option<&GzHeader> {
@@ -292,8 +384,33 @@ impl<R> GzDecoder<R> {
     /// version of `r`'s data.
     pub fn reset(&mut self, r: R) -> R {
         reset_decoder_data(self);
+        self.expected_size = None;
         self.reader.get_mut().reset(r)
     }
+
+    /// Returns the size of the uncompressed data given by the trailer at the
+    /// end of the input, if the decoder was created with
+    /// [`GzDecoder::new_seekable`] or [`GzDecoder::new_from_slice`].
+    ///
+    /// The trailer only stores the size modulo 2^32, and only describes the
+    /// whole input if it consists of a single member. Trailers which the
+    /// input can't possibly expand to are ignored, so the size is at most
+    /// 1032 times the size of the input. [`read_to_end`](Read::read_to_end)
+    /// reserves it up front, and only grows the output if the data turns out
+    /// to be larger. The size may still be wrong in corrupt data, which is
+    /// only detected once the member was read.
+    pub fn expected_size(&self) -> Option<u64> {
+        self.expected_size
+    }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.reader.get_mut().set_limits(limits);
+        self.members = 1;
+    }
 }
 
 impl<R: BufRead> Read for GzDecoder<R> {
@@ -334,11 +451,16 @@ impl<R: BufRead> Read for GzDecoder<R> {
                                 .fill_buf()
                                 .map(|buf| buf.is_empty())?;
 
+                            let limits = deflate::bufread::decoder_limits(self.reader.get_ref());
                             if is_eof {
                                 self.state = GzState::End(Some(mem::take(header)));
+                            } else if let Err(err) = limits.check_members(self.members) {
+                                self.state = GzState::End(Some(mem::take(header)));
+                                return Err(err.into());
                             } else {
+                                self.members += 1;
                                 self.reader.reset();
-                                self.reader.get_mut().reset_data();
+                                deflate::bufread::next_member_data(self.reader.get_mut());
                                 self.state = GzState::Header(GzHeaderParser::new())
                             }
                         } else {
@@ -355,6 +477,18 @@ impl<R: BufRead> Read for GzDecoder<R> {
             }
         }
     }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        // Reserve the rest of the expected size, which is already capped to
+        // what the input can expand to, so the output is allocated once.
+        if let Some(expected) = self.expected_size {
+            let remaining = expected.saturating_sub(self.reader.get_ref().total_out());
+            buf.reserve_exact(usize::try_from(remaining).unwrap_or(0));
+        }
+        // `Take` reads through `read` and fills the reserved capacity first,
+        // while a plain call would recurse into this method.
+        Read::take(self, u64::MAX).read_to_end(buf)
+    }
 }
 
 impl<R: BufRead + Write> Write for GzDecoder<R> {
@@ -419,6 +553,15 @@ impl<R: BufRead> MultiGzDecoder<R> {
     pub fn new(r: R) -> MultiGzDecoder<R> {
         MultiGzDecoder(GzDecoder::new(r).multi(true))
     }
+
+    /// Creates a new decoder from the given reader, immediately parsing the
+    /// (first) gzip header, which inflates all members with the given
+    /// `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(r: R, decompression: Decompress) -> MultiGzDecoder<R> {
+        MultiGzDecoder(GzDecoder::new_with_decompress(r, decompression).multi(true))
+    }
 }
 
 impl<R> MultiGzDecoder<R> {
@@ -444,6 +587,14 @@ impl<R> MultiGzDecoder<R> {
     pub fn into_inner(self) -> R {
         self.0.into_inner()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.0.set_limits(limits);
+    }
 }
 
 impl<R: BufRead> Read for MultiGzDecoder<R> {
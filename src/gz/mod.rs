@@ -1,10 +1,10 @@
 use std::convert::TryFrom;
 use std::ffi::CString;
-use std::io::{BufRead, Error, ErrorKind, Read, Result, Write};
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 use std::time;
 
 use crate::bufreader::BufReader;
-use crate::{Compression, Crc};
+use crate::{Compress, Compression, Crc, DecompressError, Strategy};
 
 pub static FHCRC: u8 = 1 << 1;
 pub static FEXTRA: u8 = 1 << 2;
@@ -88,7 +88,7 @@ impl GzHeader {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub enum GzHeaderState {
     Start(u8, [u8; 10]),
     Xlen(Option<Box<Crc>>, u8, [u8; 2]),
@@ -100,7 +100,7 @@ pub enum GzHeaderState {
     Complete,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct GzHeaderParser {
     state: GzHeaderState,
     flags: u8,
@@ -108,7 +108,7 @@ pub struct GzHeaderParser {
 }
 
 impl GzHeaderParser {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         GzHeaderParser {
             state: GzHeaderState::Start(0, [0; 10]),
             flags: 0,
@@ -116,7 +116,7 @@ impl GzHeaderParser {
         }
     }
 
-    fn parse<R: BufRead>(&mut self, r: &mut R) -> Result<()> {
+    pub(crate) fn parse<R: BufRead>(&mut self, r: &mut R) -> Result<()> {
         loop {
             match &mut self.state {
                 GzHeaderState::Start(count, buffer) => {
@@ -208,7 +208,10 @@ impl GzHeaderParser {
                         let stored_crc = parse_le_u16(buffer);
                         let calced_crc = crc.sum() as u16;
                         if stored_crc != calced_crc {
-                            return Err(corrupt());
+                            return Err(gzip_error(
+                                crate::ErrorKind::InvalidHeader,
+                                "header crc mismatch",
+                            ));
                         }
                     }
                     self.state = GzHeaderState::Complete;
@@ -270,21 +273,58 @@ fn read_to_nul<R: BufRead>(r: &mut R, buffer: &mut Vec<u8>) -> Result<()> {
     }
 }
 
+// Returns the uncompressed size in the `trailer` of a gzip stream of
+// `compressed_len` bytes, unless that stream can't possibly expand to it.
+fn trailer_size(trailer: [u8; 4], compressed_len: u64) -> Option<u64> {
+    let size = u64::from(u32::from_le_bytes(trailer));
+    let max = compressed_len.saturating_mul(crate::oneshot::MAX_RATIO);
+    (compressed_len >= 18 && size <= max).then_some(size)
+}
+
+// Reads the uncompressed size from the trailer at the end of `r`, and returns
+// to the current position of `r` afterwards.
+fn seek_trailer_size<R: Read + Seek>(r: &mut R) -> Result<Option<u64>> {
+    let start = r.stream_position()?;
+    let end = r.seek(SeekFrom::End(0))?;
+    let mut size = None;
+    if end >= start + 18 {
+        let mut trailer = [0; 4];
+        r.seek(SeekFrom::End(-4))?;
+        r.read_exact(&mut trailer)?;
+        size = trailer_size(trailer, end - start);
+    }
+    r.seek(SeekFrom::Start(start))?;
+    Ok(size)
+}
+
 fn parse_le_u16(buffer: &[u8; 2]) -> u16 {
     u16::from_le_bytes(*buffer)
 }
 
+// The errors of the gzip framing are reported like those of the deflate
+// stream, so that their kind can be told from the `io::Error`.
+pub(crate) fn gzip_error(kind: crate::ErrorKind, msg: &'static str) -> Error {
+    DecompressError::new(kind, Some(msg)).into()
+}
+
 fn bad_header() -> Error {
-    Error::new(ErrorKind::InvalidInput, "invalid gzip header")
+    gzip_error(crate::ErrorKind::InvalidHeader, "invalid gzip header")
 }
 
 fn corrupt() -> Error {
-    Error::new(
-        ErrorKind::InvalidInput,
+    gzip_error(
+        crate::ErrorKind::ChecksumMismatch,
         "corrupt gzip stream does not have a matching checksum",
     )
 }
 
+fn truncated_trailer() -> Error {
+    gzip_error(
+        crate::ErrorKind::TruncatedInput,
+        "gzip stream ended before its trailer",
+    )
+}
+
 /// A builder structure to create a new gzip Encoder.
 ///
 /// This structure controls header configuration options such as the filename.
@@ -318,6 +358,7 @@ pub struct GzBuilder {
     comment: Option<CString>,
     operating_system: Option<u8>,
     mtime: u32,
+    strategy: Strategy,
 }
 
 impl GzBuilder {
@@ -373,12 +414,23 @@ impl GzBuilder {
         self
     }
 
+    /// Configure the compression strategy used by the encoder.
+    ///
+    /// This only affects how the data is compressed, not the gzip header.
+    /// Creating the encoder panics if the backend doesn't support the
+    /// strategy, see [`Compress::new_with_strategy`](crate::Compress::new_with_strategy).
+    pub fn strategy(mut self, strategy: Strategy) -> GzBuilder {
+        self.strategy = strategy;
+        self
+    }
+
     /// Consume this builder, creating a writer encoder in the process.
     ///
     /// The data written to the returned encoder will be compressed and then
     /// written out to the supplied parameter `w`.
     pub fn write<W: Write>(self, w: W, lvl: Compression) -> write::GzEncoder<W> {
-        write::gz_encoder(self.into_header(lvl), w, lvl)
+        let compress = Compress::new_with_strategy(lvl, false, self.strategy);
+        write::gz_encoder(self.into_header(lvl), w, compress)
     }
 
     /// Consume this builder, creating a reader encoder in the process.
@@ -397,16 +449,18 @@ impl GzBuilder {
     where
         R: BufRead,
     {
-        bufread::gz_encoder(self.into_header(lvl), r, lvl)
+        let compress = Compress::new_with_strategy(lvl, false, self.strategy);
+        bufread::gz_encoder(self.into_header(lvl), r, compress)
     }
 
-    fn into_header(self, lvl: Compression) -> Vec<u8> {
+    pub(crate) fn into_header(self, lvl: Compression) -> Vec<u8> {
         let GzBuilder {
             extra,
             filename,
             comment,
             operating_system,
             mtime,
+            strategy: _,
         } = self;
         let mut flg = 0;
         let mut header = vec![0u8; 10];
@@ -458,7 +512,7 @@ mod tests {
     use std::io::prelude::*;
 
     use super::{read, write, GzBuilder, GzHeaderParser};
-    use crate::{Compression, GzHeader};
+    use crate::{Compression, GzHeader, Strategy};
     use rand::{rng, Rng};
 
     #[test]
@@ -735,6 +789,9 @@ mod tests {
         let mut output = Vec::new();
         let error = decoder.read_to_end(&mut output).unwrap_err();
         assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        let inner = error.get_ref().unwrap();
+        let inner = inner.downcast_ref::<crate::DecompressError>().unwrap();
+        assert_eq!(inner.kind(), crate::ErrorKind::ChecksumMismatch);
     }
 
     #[test]
@@ -774,6 +831,58 @@ mod tests {
         assert_eq!(res, vec![0, 2, 4, 6]);
     }
 
+    #[test]
+    fn strategy() {
+        if !crate::backend_info().capabilities().strategies() {
+            return;
+        }
+        let r = b"aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbb".repeat(16);
+        for strategy in [Strategy::HuffmanOnly, Strategy::Rle] {
+            let mut e = GzBuilder::new()
+                .strategy(strategy)
+                .write(Vec::new(), Compression::default());
+            e.write_all(&r).unwrap();
+            let written = e.finish().unwrap();
+
+            let mut e = GzBuilder::new()
+                .strategy(strategy)
+                .read(&r[..], Compression::default());
+            let mut read = Vec::new();
+            e.read_to_end(&mut read).unwrap();
+            assert_eq!(read, written);
+
+            let mut d = read::GzDecoder::new(&written[..]);
+            let mut res = Vec::new();
+            d.read_to_end(&mut res).unwrap();
+            assert_eq!(res, r);
+        }
+    }
+
+    #[test]
+    fn set_level() {
+        let r = b"aaaaaaaaaaaaaaaabbbbbbbbbbbbbbbb".repeat(64);
+        let mut e = write::GzEncoder::new(Vec::new(), Compression::none());
+        e.set_level(Compression::best()).unwrap();
+        e.write_all(&r).unwrap();
+        e.set_level(Compression::none()).unwrap();
+        e.write_all(&r).unwrap();
+        let written = e.finish().unwrap();
+        assert!(written.len() > r.len());
+
+        let mut e = read::GzEncoder::new(&r[..], Compression::none());
+        e.set_level(Compression::best());
+        let mut read = Vec::new();
+        e.read_to_end(&mut read).unwrap();
+        assert!(read.len() < r.len() / 10);
+
+        for (data, expected) in [(written, r.repeat(2)), (read, r)] {
+            let mut d = read::GzDecoder::new(&data[..]);
+            let mut res = Vec::new();
+            d.read_to_end(&mut res).unwrap();
+            assert_eq!(res, expected);
+        }
+    }
+
     #[test]
     #[should_panic(expected = "gzip extra field length cannot exceed u16::MAX")]
     fn extra_too_long() {
@@ -812,4 +921,73 @@ mod tests {
         write!(f, "Hello world").unwrap();
         f.flush().unwrap();
     }
+
+    #[test]
+    fn expected_size() {
+        let data = b"hello expected size ".repeat(2000);
+        let mut e = write::GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let member = e.finish().unwrap();
+
+        let mut file = std::io::Cursor::new(member.clone());
+        let mut d = read::GzDecoder::new_seekable(&mut file).unwrap();
+        assert_eq!(d.expected_size(), Some(data.len() as u64));
+        let mut out = Vec::new();
+        d.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(out.capacity(), data.len());
+
+        let mut d = super::bufread::GzDecoder::new_from_slice(&member);
+        assert_eq!(d.expected_size(), Some(data.len() as u64));
+        let mut out = b"prefix".to_vec();
+        assert_eq!(d.read_to_end(&mut out).unwrap(), data.len());
+        assert_eq!(&out[6..], &data[..]);
+        assert_eq!(out.capacity(), 6 + data.len());
+
+        // Only the first member is decoded, and not checked against the
+        // trailer of the last one.
+        let mut multi = member.clone();
+        multi.extend_from_slice(&member[..member.len() - 4]);
+        multi.extend_from_slice(&7u32.to_le_bytes());
+        let mut d = super::bufread::GzDecoder::new_from_slice(&multi);
+        assert_eq!(d.expected_size(), Some(7));
+        let mut out = Vec::new();
+        d.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn corrupt_expected_size() {
+        let data = b"hello corrupt size ".repeat(100);
+        let mut e = write::GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let mut bytes = e.finish().unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(&(data.len() as u32 + 1).to_le_bytes());
+
+        let mut d = super::bufread::GzDecoder::new_from_slice(&bytes);
+        assert_eq!(d.expected_size(), Some(data.len() as u64 + 1));
+        let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.to_string(), super::corrupt().to_string());
+
+        let mut d = read::GzDecoder::new_seekable(std::io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(d.expected_size(), Some(data.len() as u64 + 1));
+        assert!(d.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn implausible_expected_size() {
+        let mut e = write::GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(b"hello").unwrap();
+        let mut bytes = e.finish().unwrap();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        let d = super::bufread::GzDecoder::new_from_slice(&bytes);
+        assert_eq!(d.expected_size(), None);
+
+        let d = super::bufread::GzDecoder::new_from_slice(&bytes[..10]);
+        assert_eq!(d.expected_size(), None);
+        let d = read::GzDecoder::new(&bytes[..]);
+        assert_eq!(d.expected_size(), None);
+    }
 }
@@ -4,7 +4,7 @@ use std::io::prelude::*;
 use super::bufread;
 use super::{GzBuilder, GzHeader};
 use crate::bufreader::BufReader;
-use crate::Compression;
+use crate::{Compress, Compression, Decompress, DecompressLimits};
 
 /// A gzip streaming encoder
 ///
@@ -51,9 +51,30 @@ impl<R: Read> GzEncoder<R> {
     pub fn new(r: R, level: Compression) -> GzEncoder<R> {
         GzBuilder::new().read(r, level)
     }
+
+    /// Creates a new encoder which compresses the data read from `r` with the
+    /// given `compression`, which must produce a raw deflate stream.
+    ///
+    /// The emitted header is the same as for [`GzEncoder::new`] with the
+    /// default compression level.
+    pub fn new_with_compress(r: R, compression: Compress) -> GzEncoder<R> {
+        gz_encoder(bufread::GzEncoder::new_with_compress(
+            BufReader::new(r),
+            compression,
+        ))
+    }
 }
 
 impl<R> GzEncoder<R> {
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: Compression) {
+        self.inner.set_level(level)
+    }
+
     /// Acquires a reference to the underlying reader.
     pub fn get_ref(&self) -> &R {
         self.inner.get_ref().get_ref()
@@ -145,9 +166,66 @@ impl<R: Read> GzDecoder<R> {
             inner: bufread::GzDecoder::new(BufReader::new(r)),
         }
     }
+
+    /// Creates a new decoder from the given reader, immediately parsing the
+    /// gzip header, which inflates the body with the given `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(r: R, decompression: Decompress) -> GzDecoder<R> {
+        GzDecoder {
+            inner: bufread::GzDecoder::new_with_decompress(BufReader::new(r), decompression),
+        }
+    }
+
+    /// Creates a new decoder from the given reader like [`GzDecoder::new`],
+    /// once the uncompressed size was read from the trailer at the end of the
+    /// reader, see [`GzDecoder::expected_size`].
+    ///
+    /// The gzip data must extend to the end of the reader, starting from its
+    /// current position. Reading the whole data with
+    /// [`read_to_end`](Read::read_to_end) then allocates the output only
+    /// once, unless the trailer is wrong.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of seeking in or reading from `r`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    /// use flate2::read::GzDecoder;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut d = GzDecoder::new_seekable(File::open("data.gz")?)?;
+    /// println!("expecting {:?} bytes", d.expected_size());
+    /// let mut data = Vec::new();
+    /// d.read_to_end(&mut data)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_seekable(mut r: R) -> io::Result<GzDecoder<R>>
+    where
+        R: Seek,
+    {
+        let expected_size = super::seek_trailer_size(&mut r)?;
+        Ok(GzDecoder {
+            inner: bufread::GzDecoder::new(BufReader::new(r)).with_expected_size(expected_size),
+        })
+    }
 }
 
 impl<R> GzDecoder<R> {
+    /// Returns the size of the uncompressed data given by the trailer at the
+    /// end of the input, if the decoder was created with
+    /// [`GzDecoder::new_seekable`].
+    ///
+    /// See [`bufread::GzDecoder::expected_size`] for the caveats.
+    pub fn expected_size(&self) -> Option<u64> {
+        self.inner.expected_size()
+    }
+
     /// Returns the header associated with this stream, if it was valid.
     pub fn header(&self) -> Option<&GzHeader> {
         self.inner.header()
@@ -195,12 +273,24 @@ impl<R> GzDecoder<R> {
         super::bufread::reset_decoder_data(&mut self.inner);
         self.inner.get_mut().reset(r)
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.set_limits(limits);
+    }
 }
 
 impl<R: Read> Read for GzDecoder<R> {
     fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
         self.inner.read(into)
     }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        self.inner.read_to_end(buf)
+    }
 }
 
 impl<R: Read + Write> Write for GzDecoder<R> {
@@ -268,6 +358,17 @@ impl<R: Read> MultiGzDecoder<R> {
             inner: bufread::MultiGzDecoder::new(BufReader::new(r)),
         }
     }
+
+    /// Creates a new decoder from the given reader, immediately parsing the
+    /// (first) gzip header, which inflates all members with the given
+    /// `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(r: R, decompression: Decompress) -> MultiGzDecoder<R> {
+        MultiGzDecoder {
+            inner: bufread::MultiGzDecoder::new_with_decompress(BufReader::new(r), decompression),
+        }
+    }
 }
 
 impl<R> MultiGzDecoder<R> {
@@ -293,6 +394,14 @@ impl<R> MultiGzDecoder<R> {
     pub fn into_inner(self) -> R {
         self.inner.into_inner().into_inner()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.set_limits(limits);
+    }
 }
 
 impl<R: Read> Read for MultiGzDecoder<R> {
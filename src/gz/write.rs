@@ -2,10 +2,10 @@ use std::cmp;
 use std::io;
 use std::io::prelude::*;
 
-use super::{corrupt, GzBuilder, GzHeader, GzHeaderParser};
+use super::{corrupt, truncated_trailer, GzBuilder, GzHeader, GzHeaderParser};
 use crate::crc::{Crc, CrcWriter};
 use crate::zio;
-use crate::{Compress, Compression, Decompress, Status};
+use crate::{Compress, Compression, Decompress, DecompressLimits, Format, Status};
 
 // Non-gzip writer paths flush through zio::Writer::dump, which converts
 // Ok(0) on a non-empty buffer into WriteZero. Gzip writes its header and footer
@@ -49,9 +49,9 @@ pub struct GzEncoder<W: Write> {
     header: Vec<u8>,
 }
 
-pub fn gz_encoder<W: Write>(header: Vec<u8>, w: W, lvl: Compression) -> GzEncoder<W> {
+pub fn gz_encoder<W: Write>(header: Vec<u8>, w: W, compress: Compress) -> GzEncoder<W> {
     GzEncoder {
-        inner: zio::Writer::new(w, Compress::new(lvl, false)),
+        inner: zio::Writer::new(w, compress),
         crc: Crc::new(),
         header,
         crc_bytes_written: 0,
@@ -70,6 +70,16 @@ impl<W: Write> GzEncoder<W> {
         GzBuilder::new().write(w, level)
     }
 
+    /// Creates a new encoder which compresses the data written to it with the
+    /// given `compression`, which must produce a raw deflate stream.
+    ///
+    /// The emitted header is the same as for [`GzEncoder::new`] with the
+    /// default compression level.
+    pub fn new_with_compress(w: W, compression: Compress) -> GzEncoder<W> {
+        let header = GzBuilder::new().into_header(Compression::default());
+        gz_encoder(header, w, compression)
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.inner.get_ref()
@@ -83,6 +93,23 @@ impl<W: Write> GzEncoder<W> {
         self.inner.get_mut()
     }
 
+    /// Changes the compression level used for the data written from now on.
+    ///
+    /// All data written so far is compressed with the previous level and
+    /// written out to the underlying writer first, much like a call to
+    /// `flush` which doesn't flush the underlying writer itself.
+    ///
+    /// # Errors
+    ///
+    /// This function will perform I/O to write out the data compressed so
+    /// far, and any I/O errors which occur will be returned from this
+    /// function. An error is also returned if the backend doesn't support
+    /// the change.
+    pub fn set_level(&mut self, level: Compression) -> io::Result<()> {
+        self.write_header()?;
+        self.inner.set_level(level)
+    }
+
     /// Attempt to finish this output stream, writing out final chunks of data.
     ///
     /// Note that this function can only be used once data has finished being
@@ -241,11 +268,40 @@ impl<W: Write> GzDecoder<W> {
         }
     }
 
+    /// Creates a new decoder which will write uncompressed data to the stream
+    /// `w`, inflating the body with the given `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(w: W, decompression: Decompress) -> GzDecoder<W> {
+        GzDecoder {
+            inner: zio::Writer::new(CrcWriter::new(w), decompression),
+            crc_bytes: Vec::with_capacity(CRC_BYTES_LEN),
+            header_parser: GzHeaderParser::new(),
+        }
+    }
+
+    /// Prepares this decoder for the next member once the current one has
+    /// finished.
+    fn reset_member(&mut self) {
+        self.inner.data.next_stream(Format::Deflate);
+        self.inner.get_mut().reset();
+        self.crc_bytes.clear();
+        self.header_parser = GzHeaderParser::new();
+    }
+
     /// Returns the header associated with this stream.
     pub fn header(&self) -> Option<&GzHeader> {
         self.header_parser.header()
     }
 
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.data.set_limits(limits);
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.inner.get_ref().get_ref()
@@ -303,7 +359,7 @@ impl<W: Write> GzDecoder<W> {
         self.inner.finish()?;
 
         if self.crc_bytes.len() != 8 {
-            return Err(corrupt());
+            return Err(truncated_trailer());
         }
 
         let crc = (self.crc_bytes[0] as u32)
@@ -384,6 +440,8 @@ impl<W: Read + Write> Read for GzDecoder<W> {
 #[derive(Debug)]
 pub struct MultiGzDecoder<W: Write> {
     inner: GzDecoder<W>,
+    // The number of members started, counting against the member limit.
+    members: u64,
 }
 
 impl<W: Write> MultiGzDecoder<W> {
@@ -392,6 +450,18 @@ impl<W: Write> MultiGzDecoder<W> {
     pub fn new(w: W) -> MultiGzDecoder<W> {
         MultiGzDecoder {
             inner: GzDecoder::new(w),
+            members: 1,
+        }
+    }
+
+    /// Creates a new decoder which will write uncompressed data to the stream
+    /// `w`, inflating all members with the given `decompression`.
+    ///
+    /// The `decompression` must expect a raw deflate stream.
+    pub fn new_with_decompress(w: W, decompression: Decompress) -> MultiGzDecoder<W> {
+        MultiGzDecoder {
+            inner: GzDecoder::new_with_decompress(w, decompression),
+            members: 1,
         }
     }
 
@@ -400,6 +470,15 @@ impl<W: Write> MultiGzDecoder<W> {
         self.inner.header()
     }
 
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.set_limits(limits);
+        self.members = 1;
+    }
+
     /// Acquires a reference to the underlying writer.
     pub fn get_ref(&self) -> &W {
         self.inner.get_ref()
@@ -460,10 +539,13 @@ impl<W: Write> Write for MultiGzDecoder<W> {
             match self.inner.write(buf) {
                 Ok(0) => {
                     // When the GzDecoder indicates that it has finished
-                    // create a new GzDecoder to handle additional data.
+                    // reset it to handle additional data, keeping its
+                    // decompressor.
                     self.inner.try_finish()?;
-                    let w = self.inner.inner.take_inner().into_inner();
-                    self.inner = GzDecoder::new(w);
+                    let limits = self.inner.inner.data.limits();
+                    limits.check_members(self.members)?;
+                    self.members += 1;
+                    self.inner.reset_member();
                     self.inner.write(buf)
                 }
                 res => res,
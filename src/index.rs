@@ -0,0 +1,818 @@
+//! Random access into compressed streams, with an index of access points.
+//!
+//! A deflate stream can normally only be decompressed from its start, since
+//! its data refers back to up to 32 KiB of earlier output. An [`Index`] is
+//! built by decompressing a stream once, recording an *access point* about
+//! every so many bytes of output: the position of a deflate block boundary in
+//! the compressed data, together with the 32 KiB of output before it. Later,
+//! decompression can resume at any access point, like zlib's `zran` example
+//! does, which is what [`SeekableGzDecoder`] does to implement [`Seek`].
+//!
+//! The index is independent of the compressed data and can be saved along
+//! with it, see [`Index::write_to`].
+//!
+//! The zlib-rs backend can't report block boundaries, so an index built with
+//! it has no access points, and seeking decompresses from the start.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io::prelude::*;
+//! use std::io::{Cursor, SeekFrom};
+//! use flate2::index::{Index, SeekableGzDecoder};
+//! use flate2::write::GzEncoder;
+//! use flate2::{Compression, Format};
+//!
+//! let data = (0..100_000).map(|i| format!("line {}\n", i)).collect::<String>();
+//! let mut e = GzEncoder::new(Vec::new(), Compression::default());
+//! e.write_all(data.as_bytes()).unwrap();
+//! let compressed = Cursor::new(e.finish().unwrap());
+//!
+//! let index = Index::builder(Format::Gzip)
+//!     .span(64 * 1024)
+//!     .build(compressed.clone())
+//!     .unwrap();
+//! let mut d = SeekableGzDecoder::new(compressed, index);
+//! d.seek(SeekFrom::Start(500_000)).unwrap();
+//! let mut s = String::new();
+//! d.read_to_string(&mut s).unwrap();
+//! assert_eq!(s, data[500_000..]);
+//! ```
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+use crate::{Decompress, DecompressError, ErrorKind, FlushDecompress, Format, Status};
+
+/// The magic bytes at the start of a serialized index.
+const MAGIC: [u8; 8] = *b"FL2INDEX";
+
+/// The version of the serialized index.
+const VERSION: u32 = 1;
+
+/// The size of the deflate window, and of the windows of the access points.
+const WINDOW_SIZE: usize = 32 * 1024;
+
+const BUF_SIZE: usize = 32 * 1024;
+
+/// A point at which decompression can resume.
+///
+/// It's the boundary of two deflate blocks, which may be in the middle of a
+/// byte of the compressed data.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AccessPoint {
+    output: u64,
+    input: u64,
+    bits: u8,
+    window: Vec<u8>,
+}
+
+impl AccessPoint {
+    /// Returns the offset of the access point in the decompressed data.
+    pub fn uncompressed_offset(&self) -> u64 {
+        self.output
+    }
+
+    /// Returns the offset of the first whole byte after the access point in
+    /// the compressed data.
+    pub fn compressed_offset(&self) -> u64 {
+        self.input
+    }
+
+    /// Returns the number of bits of the byte before the compressed offset
+    /// which already belong to the data after the access point, from 0 to 7.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Returns the decompressed data before the access point which the data
+    /// after it can refer to, at most 32 KiB.
+    pub fn window(&self) -> &[u8] {
+        &self.window
+    }
+}
+
+impl fmt::Debug for AccessPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessPoint")
+            .field("uncompressed_offset", &self.output)
+            .field("compressed_offset", &self.input)
+            .field("bits", &self.bits)
+            .field("window_len", &self.window.len())
+            .finish()
+    }
+}
+
+/// An index of access points into a compressed stream.
+///
+/// An index is created with an [`IndexBuilder`], which decompresses the whole
+/// stream, checking it along the way. A gzip stream may have multiple members.
+///
+/// # Serialization
+///
+/// [`Index::write_to`] stores the index in a stable format, which any later
+/// version of this crate reads with [`Index::read_from`]. All integers are
+/// little endian:
+///
+/// * the magic bytes `FL2INDEX` and the version 1 as a `u32`,
+/// * the format of the stream as a `u8`, 0 for raw deflate, 1 for zlib and 2
+///   for gzip,
+/// * the span, the size of the compressed stream and the size of the
+///   decompressed data as `u64`,
+/// * the number of access points as a `u64`, followed by each of them: its
+///   uncompressed and compressed offsets as `u64`, its bits as a `u8`, and
+///   the length of its window as a `u16` followed by the window.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Index {
+    format: Format,
+    span: u64,
+    compressed_len: u64,
+    uncompressed_len: u64,
+    points: Vec<AccessPoint>,
+}
+
+impl Index {
+    /// Creates a builder of an index of a stream in the given format.
+    pub fn builder(format: Format) -> IndexBuilder {
+        IndexBuilder {
+            format,
+            span: 1024 * 1024,
+        }
+    }
+
+    /// Returns the format of the indexed stream.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the least distance between access points the index was built
+    /// with.
+    pub fn span(&self) -> u64 {
+        self.span
+    }
+
+    /// Returns the size of the compressed stream.
+    pub fn compressed_len(&self) -> u64 {
+        self.compressed_len
+    }
+
+    /// Returns the size of the decompressed data.
+    pub fn uncompressed_len(&self) -> u64 {
+        self.uncompressed_len
+    }
+
+    /// Returns the access points, in the order of the stream.
+    pub fn access_points(&self) -> &[AccessPoint] {
+        &self.points
+    }
+
+    /// Returns the last access point at or before `offset` in the
+    /// decompressed data, if any.
+    fn point_before(&self, offset: u64) -> Option<&AccessPoint> {
+        let i = self.points.partition_point(|p| p.output <= offset);
+        self.points.get(i.checked_sub(1)?)
+    }
+
+    /// Writes the index to `w`, in the format described in the
+    /// [type documentation](Index#serialization).
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let format: u8 = match self.format {
+            Format::Deflate => 0,
+            Format::Zlib => 1,
+            Format::Gzip => 2,
+        };
+        w.write_all(&MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&[format])?;
+        for n in [
+            self.span,
+            self.compressed_len,
+            self.uncompressed_len,
+            self.points.len() as u64,
+        ] {
+            w.write_all(&n.to_le_bytes())?;
+        }
+        for point in &self.points {
+            w.write_all(&point.output.to_le_bytes())?;
+            w.write_all(&point.input.to_le_bytes())?;
+            w.write_all(&[point.bits])?;
+            w.write_all(&(point.window.len() as u16).to_le_bytes())?;
+            w.write_all(&point.window)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an index which was written by [`Index::write_to`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the data
+    /// isn't a valid index.
+    pub fn read_from<R: Read>(mut r: R) -> io::Result<Index> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid_index("not a serialized index"));
+        }
+        if read_u32(&mut r)? != VERSION {
+            return Err(invalid_index("unsupported index version"));
+        }
+        let format = match read_u8(&mut r)? {
+            0 => Format::Deflate,
+            1 => Format::Zlib,
+            2 => Format::Gzip,
+            _ => return Err(invalid_index("unknown format")),
+        };
+        let span = read_u64(&mut r)?;
+        let compressed_len = read_u64(&mut r)?;
+        let uncompressed_len = read_u64(&mut r)?;
+        let count = read_u64(&mut r)?;
+
+        // The count isn't trusted to allocate the points up front.
+        let mut points: Vec<AccessPoint> = Vec::new();
+        for _ in 0..count {
+            let output = read_u64(&mut r)?;
+            let input = read_u64(&mut r)?;
+            let bits = read_u8(&mut r)?;
+            let len = usize::from(u16::from_le_bytes(read_array(&mut r)?));
+            if bits > 7 || len > WINDOW_SIZE || output > uncompressed_len || input > compressed_len
+            {
+                return Err(invalid_index("invalid access point"));
+            }
+            if let Some(last) = points.last() {
+                if output < last.output || input < last.input {
+                    return Err(invalid_index("access points out of order"));
+                }
+            }
+            let mut window = vec![0; len];
+            r.read_exact(&mut window)?;
+            points.push(AccessPoint {
+                output,
+                input,
+                bits,
+                window,
+            });
+        }
+        Ok(Index {
+            format,
+            span,
+            compressed_len,
+            uncompressed_len,
+            points,
+        })
+    }
+}
+
+fn invalid_index(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+    read_array::<R, 1>(r).map(|[b]| b)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    read_array(r).map(u32::from_le_bytes)
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    read_array(r).map(u64::from_le_bytes)
+}
+
+/// A builder of an [`Index`], created by [`Index::builder`].
+#[derive(Copy, Clone, Debug)]
+pub struct IndexBuilder {
+    format: Format,
+    span: u64,
+}
+
+impl IndexBuilder {
+    /// Sets the least distance between access points in the decompressed
+    /// data, defaults to 1 MiB.
+    ///
+    /// Access points can only be at deflate block boundaries, so they're
+    /// usually a bit further apart. Each one takes up to 32 KiB, while seeking
+    /// decompresses up to about `span` bytes before the target.
+    pub fn span(mut self, span: u64) -> IndexBuilder {
+        self.span = span;
+        self
+    }
+
+    /// Builds the index of the stream read from `r`, which is decompressed
+    /// and checked in full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `r` and of the decompression, including one for
+    /// data after the end of the stream (or of the last gzip member).
+    pub fn build<R: Read>(self, mut r: R) -> io::Result<Index> {
+        let mut decompress = Decompress::builder().format(self.format).build()?;
+        let mut points = Vec::new();
+        // The offsets of the current gzip member.
+        let (mut member_in, mut member_out) = (0, 0);
+        let mut input = Input::new();
+        let mut output = vec![0; BUF_SIZE];
+        loop {
+            let (before_in, before_out) = (decompress.total_in(), decompress.total_out());
+            let status =
+                decompress.decompress(input.fill(&mut r)?, &mut output, FlushDecompress::Block)?;
+            input.consume((decompress.total_in() - before_in) as usize);
+
+            if status == Status::StreamEnd {
+                member_in += decompress.total_in();
+                member_out += decompress.total_out();
+                if input.fill(&mut r)?.is_empty() {
+                    break;
+                }
+                if self.format != Format::Gzip {
+                    let err = DecompressError::new(
+                        ErrorKind::StreamFinished,
+                        Some("unexpected data after the end of the stream"),
+                    );
+                    return Err(err.at(member_in, member_out).into());
+                }
+                decompress.next_stream(Format::Gzip);
+                continue;
+            }
+
+            let position = decompress.position();
+            let out = member_out + decompress.total_out();
+            // Nothing but the trailer follows the last block.
+            if position.is_block_boundary()
+                && !position.is_last_block()
+                && points
+                    .last()
+                    .map_or(true, |p: &AccessPoint| out - p.output >= self.span)
+            {
+                points.push(AccessPoint {
+                    output: out,
+                    input: member_in + decompress.total_in(),
+                    bits: position.unused_bits(),
+                    window: decompress.get_dictionary(),
+                });
+            }
+
+            let progress = decompress.total_in() > before_in || decompress.total_out() > before_out;
+            if !progress && input.eof {
+                let (total_in, total_out) = (member_in + decompress.total_in(), out);
+                return Err(DecompressError::truncated(total_in, total_out).into());
+            }
+        }
+        Ok(Index {
+            format: self.format,
+            span: self.span,
+            compressed_len: member_in,
+            uncompressed_len: member_out,
+            points,
+        })
+    }
+}
+
+/// The buffered input of the index builder and the seekable decoder.
+struct Input {
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    eof: bool,
+}
+
+impl Input {
+    fn new() -> Input {
+        Input {
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
+            eof: false,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.pos = 0;
+        self.len = 0;
+        self.eof = false;
+    }
+
+    /// Returns the buffered input, reading more from `r` if there's none.
+    fn fill<R: Read>(&mut self, r: &mut R) -> io::Result<&[u8]> {
+        if self.pos == self.len && !self.eof {
+            self.pos = 0;
+            self.len = loop {
+                match r.read(&mut self.buf) {
+                    Ok(n) => break n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            };
+            self.eof = self.len == 0;
+        }
+        Ok(&self.buf[self.pos..self.len])
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.pos += n;
+    }
+}
+
+/// Where a [`SeekableGzDecoder`] is in its stream.
+enum State {
+    /// In the deflate stream which was entered at an access point, whose
+    /// checksum can't be checked.
+    Resumed,
+    /// In a stream read from its start, the whole stream or a later gzip
+    /// member.
+    Stream,
+    /// Skipping what's left of the trailer of the stream, before the next
+    /// gzip member if any.
+    Trailer(usize),
+    Done,
+}
+
+/// A decoder which seeks in the decompressed data of a stream, using an
+/// [`Index`] of it.
+///
+/// This structure implements [`Read`] like [`read::MultiGzDecoder`], and
+/// [`Seek`] to any offset in the decompressed data by resuming decompression
+/// at the last access point before it. Despite its name, it decodes zlib and
+/// raw deflate streams too, depending on the format of the index.
+///
+/// The checksum of a stream (or gzip member) can only be checked if it's read
+/// from its start, which isn't the case after seeking into the middle of it.
+///
+/// [`read::MultiGzDecoder`]: crate::read::MultiGzDecoder
+pub struct SeekableGzDecoder<R> {
+    inner: R,
+    index: Index,
+    decompress: Decompress,
+    input: Input,
+    state: State,
+    // The offset in the decompressed data, and whether the decompressor is
+    // there, rather than to be resumed before it.
+    pos: u64,
+    positioned: bool,
+}
+
+impl<R: Read + Seek> SeekableGzDecoder<R> {
+    /// Creates a new decoder of the stream in `r`, using its `index`.
+    ///
+    /// The offsets of the index are those from the start of `r`.
+    pub fn new(r: R, index: Index) -> SeekableGzDecoder<R> {
+        SeekableGzDecoder {
+            inner: r,
+            index,
+            decompress: Decompress::new(false),
+            input: Input::new(),
+            state: State::Done,
+            pos: 0,
+            positioned: false,
+        }
+    }
+
+    /// Returns the index of the stream.
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consumes this decoder, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Resumes decompression at the last access point before `self.pos`, or
+    /// at the start of the stream, and decompresses up to it.
+    fn resume(&mut self) -> io::Result<()> {
+        self.input.clear();
+        let Some(point) = self.index.point_before(self.pos) else {
+            self.inner.seek(SeekFrom::Start(0))?;
+            self.decompress = Decompress::builder().format(self.index.format).build()?;
+            self.state = State::Stream;
+            return self.skip(self.pos);
+        };
+        self.decompress = Decompress::new(false);
+        if point.bits > 0 {
+            self.inner.seek(SeekFrom::Start(point.input - 1))?;
+            let mut byte = [0];
+            self.inner.read_exact(&mut byte)?;
+            let value = u16::from(byte[0] >> (8 - point.bits));
+            self.decompress.prime(point.bits, value)?;
+        } else {
+            self.inner.seek(SeekFrom::Start(point.input))?;
+        }
+        if !point.window.is_empty() {
+            self.decompress.set_dictionary(&point.window)?;
+        }
+        self.state = State::Resumed;
+        self.skip(self.pos - point.output)
+    }
+
+    /// Decompresses and discards `len` bytes.
+    fn skip(&mut self, mut len: u64) -> io::Result<()> {
+        self.positioned = true;
+        let mut scratch = [0; BUF_SIZE];
+        while len > 0 {
+            let n = usize::try_from(len).map_or(BUF_SIZE, |n| cmp::min(n, BUF_SIZE));
+            match self.decode(&mut scratch[..n])? {
+                0 => break,
+                n => len -= n as u64,
+            }
+        }
+        Ok(())
+    }
+
+    fn truncated(&self) -> io::Error {
+        let d = &self.decompress;
+        DecompressError::truncated(d.total_in(), d.total_out()).into()
+    }
+
+    /// Decompresses from the current state into `into`.
+    fn decode(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.state {
+                State::Resumed | State::Stream => {
+                    let input = self.input.fill(&mut self.inner)?;
+                    let eof = input.is_empty();
+                    let (before_in, before_out) =
+                        (self.decompress.total_in(), self.decompress.total_out());
+                    let status = self
+                        .decompress
+                        .decompress(input, into, FlushDecompress::None)?;
+                    self.input
+                        .consume((self.decompress.total_in() - before_in) as usize);
+                    let n = (self.decompress.total_out() - before_out) as usize;
+
+                    if status == Status::StreamEnd {
+                        // The decompressor of a whole stream reads its trailer.
+                        self.state = match (&self.state, self.index.format) {
+                            (State::Resumed, Format::Gzip) => State::Trailer(8),
+                            (State::Resumed, Format::Zlib) => State::Trailer(4),
+                            (State::Stream, Format::Gzip) => State::Trailer(0),
+                            _ => State::Done,
+                        };
+                    } else if n == 0 && eof {
+                        return Err(self.truncated());
+                    }
+                    if n > 0 {
+                        return Ok(n);
+                    }
+                }
+                State::Trailer(0) => {
+                    // Only a gzip stream has more members.
+                    let more = !self.input.fill(&mut self.inner)?.is_empty();
+                    if more {
+                        self.decompress = Decompress::new_gzip(15);
+                        self.state = State::Stream;
+                    } else {
+                        self.state = State::Done;
+                    }
+                }
+                State::Trailer(left) => {
+                    let input = self.input.fill(&mut self.inner)?;
+                    if input.is_empty() {
+                        return Err(self.truncated());
+                    }
+                    let n = cmp::min(left, input.len());
+                    self.input.consume(n);
+                    self.state = State::Trailer(left - n);
+                }
+                State::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableGzDecoder<R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if into.is_empty() || self.pos >= self.index.uncompressed_len {
+            return Ok(0);
+        }
+        if !self.positioned {
+            self.resume()?;
+        }
+        let n = self.decode(into)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// Seeks in the decompressed data. Seeking beyond its end is allowed, after
+/// which nothing is read.
+impl<R: Read + Seek> Seek for SeekableGzDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+            SeekFrom::End(n) => self.index.uncompressed_len.checked_add_signed(n),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        // Seeking a bit forward is faster by decompressing up to the target
+        // than by resuming at an access point.
+        let point = self.index.point_before(target).map_or(0, |p| p.output);
+        if self.positioned && target >= self.pos && point <= self.pos {
+            let end = cmp::min(target, self.index.uncompressed_len);
+            self.skip(end.saturating_sub(self.pos))?;
+        } else {
+            self.positioned = false;
+        }
+        self.pos = target;
+        Ok(target)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for SeekableGzDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeekableGzDecoder")
+            .field("inner", &self.inner)
+            .field("pos", &self.pos)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{compress_to_vec, Compression};
+
+    const FORMATS: [Format; 3] = [Format::Deflate, Format::Zlib, Format::Gzip];
+
+    fn lines(count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| format!("line {} says {}\n", i, i * 7919 % 10007).into_bytes())
+            .collect()
+    }
+
+    fn index(compressed: &[u8], format: Format) -> Index {
+        Index::builder(format)
+            .span(32 * 1024)
+            .build(compressed)
+            .unwrap()
+    }
+
+    fn read_at<R: Read + Seek>(d: &mut SeekableGzDecoder<R>, pos: SeekFrom, len: usize) -> Vec<u8> {
+        d.seek(pos).unwrap();
+        let mut out = Vec::new();
+        d.take(len as u64).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn seek() {
+        let data = lines(60_000);
+        let len = data.len() as u64;
+        for format in FORMATS {
+            let compressed = compress_to_vec(&data, format, Compression::default()).unwrap();
+            let index = index(&compressed, format);
+            // zlib-rs doesn't report block boundaries.
+            #[cfg(any(feature = "any_c_zlib", not(feature = "zlib-rs")))]
+            assert!(index.access_points().len() > 4);
+            assert_eq!(index.uncompressed_len(), len);
+            assert_eq!(index.compressed_len(), compressed.len() as u64);
+
+            let mut d = SeekableGzDecoder::new(Cursor::new(&compressed), index);
+            for &pos in &[len / 2, 0, 1, 12_345, len - 10, 700_000, 32 * 1024, len / 3] {
+                let out = read_at(&mut d, SeekFrom::Start(pos), 1000);
+                let end = cmp::min(pos + 1000, len) as usize;
+                assert_eq!(out, data[pos as usize..end], "{:?} at {}", format, pos);
+            }
+            // Every access point, and right around it.
+            let offsets = d
+                .index()
+                .access_points()
+                .iter()
+                .map(|p| p.uncompressed_offset())
+                .collect::<Vec<_>>();
+            for pos in offsets {
+                for pos in [pos.saturating_sub(1), pos, cmp::min(pos + 1, len)] {
+                    let out = read_at(&mut d, SeekFrom::Start(pos), 100);
+                    let end = cmp::min(pos + 100, len) as usize;
+                    assert_eq!(out, data[pos as usize..end]);
+                }
+            }
+
+            assert_eq!(
+                read_at(&mut d, SeekFrom::End(-5), 100),
+                data[data.len() - 5..]
+            );
+            d.seek(SeekFrom::Start(1000)).unwrap();
+            assert_eq!(read_at(&mut d, SeekFrom::Current(50), 10), data[1050..1060]);
+            assert_eq!(d.stream_position().unwrap(), 1060);
+            assert!(read_at(&mut d, SeekFrom::End(10), 10).is_empty());
+            assert!(d.seek(SeekFrom::Current(-10_000_000)).is_err());
+
+            // Reading everything checks the checksum.
+            let mut out = Vec::new();
+            d.rewind().unwrap();
+            d.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn gzip_members() {
+        let parts = [lines(30_000), Vec::new(), lines(20_000)];
+        let data = parts.concat();
+        let compressed = parts
+            .iter()
+            .flat_map(|part| compress_to_vec(part, Format::Gzip, Compression::fast()).unwrap())
+            .collect::<Vec<_>>();
+        let index = index(&compressed, Format::Gzip);
+        assert_eq!(index.uncompressed_len(), data.len() as u64);
+
+        let mut d = SeekableGzDecoder::new(Cursor::new(&compressed), index);
+        let boundary = parts[0].len();
+        for pos in [boundary - 10, boundary - 40_000, boundary + 40_000, 100] {
+            let mut out = Vec::new();
+            d.seek(SeekFrom::Start(pos as u64)).unwrap();
+            d.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data[pos..]);
+        }
+
+        // A corrupt member after the access point is detected.
+        let mut corrupt = compressed.clone();
+        let len = corrupt.len();
+        corrupt[len - 5] ^= 1;
+        let index = d.index().clone();
+        let mut d = SeekableGzDecoder::new(Cursor::new(&corrupt), index);
+        d.seek(SeekFrom::Start(boundary as u64 - 10)).unwrap();
+        assert!(d.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    // Not supported by the stable API of zlib-rs.
+    #[cfg(any(feature = "any_c_zlib", not(feature = "zlib-rs")))]
+    #[test]
+    fn serialization() {
+        let data = lines(30_000);
+        for format in FORMATS {
+            let compressed = compress_to_vec(&data, format, Compression::best()).unwrap();
+            let index = index(&compressed, format);
+            let mut serialized = Vec::new();
+            index.write_to(&mut serialized).unwrap();
+            assert_eq!(&serialized[..12], b"FL2INDEX\x01\0\0\0");
+            assert_eq!(Index::read_from(&serialized[..]).unwrap(), index);
+
+            assert!(Index::read_from(&serialized[..serialized.len() - 1]).is_err());
+            let mut bad = serialized.clone();
+            bad[0] = b'X';
+            assert!(Index::read_from(&bad[..]).is_err());
+            let mut bad = serialized.clone();
+            bad[12] = 3;
+            assert!(Index::read_from(&bad[..]).is_err());
+            // The bits of the first access point.
+            let mut bad = serialized.clone();
+            bad[61] = 8;
+            assert!(Index::read_from(&bad[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn build_errors() {
+        let data = lines(10_000);
+        for format in FORMATS {
+            let compressed = compress_to_vec(&data, format, Compression::default()).unwrap();
+            let truncated = &compressed[..compressed.len() - 1];
+            assert!(Index::builder(format).build(truncated).is_err());
+            if format != Format::Deflate {
+                let mut corrupt = compressed.clone();
+                let len = corrupt.len();
+                corrupt[len - 5] ^= 1;
+                assert!(Index::builder(format).build(&corrupt[..]).is_err());
+            }
+        }
+        let zlib = compress_to_vec(&data, Format::Zlib, Compression::default()).unwrap();
+        let trailing = [&zlib[..], b"more"].concat();
+        assert!(Index::builder(Format::Zlib).build(&trailing[..]).is_err());
+    }
+}
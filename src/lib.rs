@@ -49,6 +49,10 @@
 //! * zlib-rs
 //! * miniz_oxide
 //!
+//! Use [`backend_info()`] to find out at runtime which backend is active and which
+//! features it supports. The other selected backends are still available through the types of
+//! the [`backend`] module.
+//!
 //! # Organization
 //!
 //! This crate consists of three main modules: `bufread`, `read`, and `write`. Each module
@@ -88,6 +92,16 @@
 //! ```
 //!
 //!
+//! Data which is already in memory as a whole can be compressed and decompressed with
+//! [`compress_to_vec`] and [`decompress_to_vec`] and their variants, without any streams.
+//!
+//! The [`bgzf`] module reads and writes BGZF, the blocked gzip format of genomics tools, which
+//! can be read from any position. With the `parallel` feature, the `Par` encoders and decoders
+//! such as `write::ParGzEncoder` compress and decompress on multiple threads.
+//! The [`index`] module allows the same for any gzip, zlib or raw deflate stream, by
+//! decompressing it once to build an index of positions at which decompression can resume.
+//! The [`dictzip`] module reads and writes the random access gzip files of `dictd`.
+//!
 //! Note that types which operate over a specific trait often implement the mirroring trait as well.
 //! For example a `bufread::DeflateDecoder<T>` *also* implements the
 //! [`Write`] trait if `T: Write`. That is, the "dual trait" is forwarded directly
@@ -125,18 +139,37 @@
 #[cfg(not(feature = "any_impl",))]
 compile_error!("You need to choose a zlib backend");
 
+pub use crate::backend::{backend_info, BackendInfo, Capabilities};
 pub use crate::crc::{Crc, CrcReader, CrcWriter};
 pub use crate::gz::GzBuilder;
 pub use crate::gz::GzHeader;
-pub use crate::mem::{Compress, CompressError, Decompress, DecompressError, Status};
-pub use crate::mem::{FlushCompress, FlushDecompress};
+pub use crate::mem::{
+    compress_bound, Compress, CompressBuilder, CompressError, Decompress, DecompressBuilder,
+    DecompressError, Format, Status,
+};
+pub use crate::mem::{
+    DecompressLimits, DecompressPosition, ErrorKind, FlushCompress, FlushDecompress, Strategy,
+};
+pub use crate::oneshot::{
+    compress_into_slice, compress_into_vec, compress_to_vec, decompress_into_slice,
+    decompress_into_vec, decompress_to_vec,
+};
+#[cfg(feature = "parallel")]
+pub use crate::par::ParBuilder;
 
+pub mod backend;
+pub mod bgzf;
 mod bufreader;
 mod crc;
 mod deflate;
+pub mod dictzip;
 mod ffi;
 mod gz;
+pub mod index;
 mod mem;
+mod oneshot;
+#[cfg(feature = "parallel")]
+mod par;
 mod zio;
 mod zlib;
 
@@ -157,6 +190,8 @@ pub mod read {
     pub use crate::gz::read::GzDecoder;
     pub use crate::gz::read::GzEncoder;
     pub use crate::gz::read::MultiGzDecoder;
+    #[cfg(feature = "parallel")]
+    pub use crate::par::read::ParMultiGzDecoder;
     pub use crate::zlib::read::ZlibDecoder;
     pub use crate::zlib::read::ZlibEncoder;
 }
@@ -171,6 +206,12 @@ pub mod write {
     pub use crate::gz::write::GzDecoder;
     pub use crate::gz::write::GzEncoder;
     pub use crate::gz::write::MultiGzDecoder;
+    #[cfg(feature = "parallel")]
+    pub use crate::par::write::ParDeflateEncoder;
+    #[cfg(feature = "parallel")]
+    pub use crate::par::write::ParGzEncoder;
+    #[cfg(feature = "parallel")]
+    pub use crate::par::write::ParZlibEncoder;
     pub use crate::zlib::write::ZlibDecoder;
     pub use crate::zlib::write::ZlibEncoder;
 }
@@ -259,6 +300,41 @@ fn random_bytes() -> impl Iterator<Item = u8> {
     iter::repeat(()).map(|_| rand::rng().random())
 }
 
+/// A writer which fails every other call and takes at most 1000 bytes
+/// otherwise, to test that the encoders can be retried.
+#[cfg(test)]
+#[derive(Default)]
+struct FlakyWriter {
+    data: Vec<u8>,
+    fail: bool,
+}
+
+#[cfg(test)]
+impl std::io::Write for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.fail = !self.fail;
+        if self.fail {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "flaky"));
+        }
+        let n = buf.len().min(1000);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Asserts that `err` is the error of writing to a finished encoder.
+#[cfg(test)]
+fn assert_write_after_finish(err: std::io::Error) {
+    let inner = err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<CompressError>());
+    assert_eq!(inner.map(|e| e.kind()), Some(ErrorKind::StreamFinished));
+}
+
 #[allow(rustdoc::bare_urls)]
 #[doc = include_str!("../README.md")]
 mod readme {}
@@ -1,9 +1,11 @@
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt;
 use std::io;
 use std::mem::MaybeUninit;
 
-use crate::ffi::{self, Backend, Deflate, DeflateBackend, ErrorMessage, Inflate, InflateBackend};
+use crate::backend::{DeflateBackend, InflateBackend};
+use crate::ffi::{self, Deflate, ErrorMessage, Inflate};
 use crate::Compression;
 
 /// Raw in-memory compression stream for blocks of data.
@@ -20,7 +22,7 @@ use crate::Compression;
 /// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 #[derive(Debug)]
 pub struct Compress {
-    inner: Deflate,
+    inner: Box<dyn DeflateBackend>,
 }
 
 /// Raw in-memory decompression stream for blocks of data.
@@ -37,7 +39,171 @@ pub struct Compress {
 /// [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 #[derive(Debug)]
 pub struct Decompress {
-    inner: Inflate,
+    inner: Box<dyn InflateBackend>,
+    // The format of the stream, which is only known once `new_auto` saw
+    // enough of it.
+    format: Option<Format>,
+    // The first byte of an auto-detected stream, if it didn't tell the
+    // format on its own.
+    held: Option<u8>,
+    limits: LimitState,
+}
+
+/// Limits on the data produced by a decompressor, which protect against
+/// decompression bombs: small inputs which decompress to huge outputs.
+///
+/// The limits are set with [`Decompress::set_limits`], or with the
+/// `set_limits` method of the decoders. Decompression fails with an error of
+/// kind [`ErrorKind::LimitExceeded`] once a limit is exceeded, without
+/// returning the data beyond the limit. No limits are set by default.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::read::GzDecoder;
+/// use flate2::write::GzEncoder;
+/// use flate2::{Compression, DecompressError, DecompressLimits, ErrorKind};
+///
+/// let mut e = GzEncoder::new(Vec::new(), Compression::best());
+/// e.write_all(&vec![0; 1 << 20]).unwrap();
+/// let bomb = e.finish().unwrap();
+///
+/// let mut d = GzDecoder::new(&bomb[..]);
+/// d.set_limits(DecompressLimits::new().max_output(64 * 1024));
+/// let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+/// let err = err.get_ref().unwrap().downcast_ref::<DecompressError>().unwrap();
+/// assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct DecompressLimits {
+    max_output: Option<u64>,
+    max_ratio: Option<u64>,
+    max_members: Option<u64>,
+}
+
+/// The limits of a `Decompress`, and the data of the streams before the
+/// current one which counts against them.
+#[derive(Copy, Clone, Debug, Default)]
+struct LimitState {
+    limits: DecompressLimits,
+    previous_in: u64,
+    previous_out: u64,
+}
+
+/// A builder for [`Compress`] objects, created with [`Compress::builder`].
+///
+/// The builder collects every parameter of a compression stream before the
+/// stream is allocated. Parameters which aren't configured keep their zlib
+/// defaults: the default compression level, the zlib format, a window of 15
+/// bits, a memory level of 8 and the default strategy.
+///
+/// # Examples
+///
+/// ```
+/// use flate2::{Compress, Compression, Format, Strategy};
+///
+/// let compress = Compress::builder()
+///     .level(Compression::fast())
+///     .format(Format::Deflate)
+///     .strategy(Strategy::Rle)
+///     .build();
+/// // Not every backend supports strategies.
+/// assert_eq!(
+///     compress.is_ok(),
+///     flate2::backend_info().capabilities().strategies()
+/// );
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct CompressBuilder {
+    pub(crate) level: Compression,
+    pub(crate) format: Format,
+    pub(crate) window_bits: u8,
+    pub(crate) mem_level: u8,
+    pub(crate) strategy: Strategy,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) keep_dictionary: bool,
+}
+
+/// A builder for [`Decompress`] objects, created with [`Decompress::builder`].
+///
+/// Parameters which aren't configured keep their defaults: the zlib format,
+/// a window of 15 bits and no memory limit.
+///
+/// # Examples
+///
+/// ```
+/// use flate2::{Decompress, Format};
+///
+/// let decompress = Decompress::builder()
+///     .format(Format::Gzip)
+///     .memory_limit(128 * 1024)
+///     .build()
+///     .unwrap();
+/// assert!(decompress.memory_usage() <= 128 * 1024);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct DecompressBuilder {
+    format: Format,
+    window_bits: u8,
+    memory_limit: Option<usize>,
+}
+
+/// The framing of a compressed stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Format {
+    /// A raw deflate stream without any header or trailer.
+    Deflate,
+
+    /// A deflate stream wrapped in a zlib header and an Adler-32 trailer.
+    Zlib,
+
+    /// A deflate stream wrapped in a gzip header and a CRC-32 trailer.
+    ///
+    /// The header written by [`Compress`] is a minimal one, use [`GzBuilder`]
+    /// to configure the header fields.
+    ///
+    /// [`GzBuilder`]: crate::GzBuilder
+    Gzip,
+}
+
+impl Format {
+    /// The largest size of the header and trailer written by [`Compress`].
+    fn wrapper_len(self) -> usize {
+        match self {
+            Format::Deflate => 0,
+            // Including the id of a preset dictionary.
+            Format::Zlib => 6 + 4,
+            Format::Gzip => 18,
+        }
+    }
+}
+
+/// Returns an upper bound on the size of the output of compressing
+/// `input_len` bytes in the given `format`.
+///
+/// The bound holds for any parameters of a [`Compress`] object created for
+/// `format`, as long as all input is compressed before the stream is
+/// finished with [`FlushCompress::Finish`] and no other flushes are used.
+/// Use [`Compress::bound`] for a tighter bound that takes the parameters of a
+/// specific stream into account.
+///
+/// # Examples
+///
+/// ```
+/// use flate2::{compress_bound, Compress, Compression, FlushCompress, Format, Status};
+///
+/// let input = b"hello world";
+/// let mut output = Vec::with_capacity(compress_bound(input.len(), Format::Zlib));
+/// let mut compress = Compress::new(Compression::default(), true);
+/// let status = compress
+///     .compress_vec(input, &mut output, FlushCompress::Finish)
+///     .unwrap();
+/// assert_eq!(status, Status::StreamEnd);
+/// ```
+pub fn compress_bound(input_len: usize, format: Format) -> usize {
+    ffi::deflate_bound(input_len).saturating_add(format.wrapper_len())
 }
 
 /// Values which indicate the form of flushing to be used when compressing
@@ -82,6 +248,18 @@ pub enum FlushCompress {
     /// The return value may indicate that the stream is not yet done and more
     /// data has yet to be processed.
     Finish = ffi::MZ_FINISH as isize,
+
+    /// The current deflate block is completed and emitted, but unlike
+    /// `Flush::Sync` no empty stored block follows it and up to seven bits of
+    /// the output are held back until the next call.
+    ///
+    /// The next block starts right after this one, so this can be used to
+    /// control block boundaries without aligning the output.
+    ///
+    /// The zlib backends, including zlib-rs, support this. The miniz_oxide
+    /// backend doesn't, and performs a `Flush::Sync` instead: the output is
+    /// aligned, and the block is followed by an empty stored block.
+    Block = ffi::MZ_BLOCK as isize,
 }
 
 /// Values which indicate the form of flushing to be used when
@@ -109,19 +287,204 @@ pub enum FlushDecompress {
     /// The return value may indicate that the stream is not yet done and more
     /// data has yet to be processed.
     Finish = ffi::MZ_FINISH as isize,
+
+    /// Decompression stops at the end of the current deflate block, or right
+    /// after the zlib or gzip header if it wasn't read yet.
+    ///
+    /// This returns early even if more input and output space is available,
+    /// and [`Decompress::position`] reports where decompression stopped. This
+    /// is used to build an index of the block boundaries of a stream.
+    ///
+    /// All backends stop at block boundaries, but the zlib-rs backend can't
+    /// report them through [`Decompress::position`], see
+    /// [`Capabilities::block_boundaries`](crate::backend::Capabilities::block_boundaries).
+    Block = ffi::MZ_BLOCK as isize,
+
+    /// Like `FlushDecompress::Block`, but decompression also stops right after
+    /// each block header, before any data of the block is decoded.
+    ///
+    /// The miniz_oxide backend only stops at the end of blocks, as with
+    /// `FlushDecompress::Block`.
+    Trees = ffi::MZ_TREES as isize,
+}
+
+/// Where a decompressor stopped in its input, as returned by
+/// [`Decompress::position`].
+///
+/// This is mostly useful with [`FlushDecompress::Block`] to find the
+/// boundaries of deflate blocks, at which decompression can later be resumed
+/// with a dictionary and the unused bits of the last consumed byte.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct DecompressPosition {
+    pub(crate) unused_bits: u8,
+    pub(crate) block_boundary: bool,
+    pub(crate) last_block: bool,
+    pub(crate) after_header: bool,
+}
+
+impl DecompressPosition {
+    /// Decodes the `data_type` field that zlib sets after each call to
+    /// `inflate`.
+    #[cfg(feature = "any_c_zlib")]
+    pub(crate) fn from_data_type(data_type: i32) -> DecompressPosition {
+        DecompressPosition {
+            unused_bits: (data_type & 63) as u8,
+            block_boundary: data_type & 128 != 0,
+            last_block: data_type & 64 != 0,
+            after_header: data_type & 256 != 0,
+        }
+    }
+
+    /// Returns the number of bits of the consumed input which weren't decoded
+    /// yet.
+    ///
+    /// At a block boundary these are the high bits of the last consumed byte,
+    /// and the next block starts with them.
+    pub fn unused_bits(&self) -> u8 {
+        self.unused_bits
+    }
+
+    /// Returns whether the decompressor stopped at the end of a deflate block,
+    /// or right after the zlib or gzip header, before the first block.
+    ///
+    /// This is never the case after the last block, once the end of the stream
+    /// has been reached.
+    pub fn is_block_boundary(&self) -> bool {
+        self.block_boundary
+    }
+
+    /// Returns whether the current (or just completed) block is the last block
+    /// of the stream.
+    pub fn is_last_block(&self) -> bool {
+        self.last_block
+    }
+
+    /// Returns whether the decompressor stopped right after the header of a
+    /// block, as requested with [`FlushDecompress::Trees`].
+    pub fn is_after_block_header(&self) -> bool {
+        self.after_header
+    }
+}
+
+/// The strategy used by a compressor to tune its compression algorithm.
+///
+/// The strategy only affects the compression ratio and speed, never the
+/// correctness of the compressed data: any decompressor can read data
+/// compressed with any strategy.
+///
+/// The zlib-rs backend only supports [`Strategy::Default`], and returns an
+/// error of kind [`ErrorKind::Unsupported`] for the other strategies, see
+/// [`Capabilities::strategies`](crate::Capabilities::strategies).
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[non_exhaustive]
+pub enum Strategy {
+    /// The strategy suitable for most data.
+    #[default]
+    Default,
+
+    /// Intended for data produced by a filter or predictor, such as PNG
+    /// scanlines: small values with a somewhat random distribution.
+    ///
+    /// Forces more Huffman coding and less string matching; it is somewhat
+    /// intermediate between `Default` and `HuffmanOnly`.
+    Filtered,
+
+    /// Forces Huffman coding only, without any string matching.
+    HuffmanOnly,
+
+    /// Limits match distances to one, i.e. run-length encoding.
+    ///
+    /// Designed to be almost as fast as `HuffmanOnly`, but gives better
+    /// compression for PNG image data.
+    Rle,
+
+    /// Prevents the use of dynamic Huffman codes, allowing for a simpler
+    /// decoder in special applications.
+    Fixed,
+}
+
+/// The kind of a [`DecompressError`] or [`CompressError`].
+///
+/// Backends set the kind from their return codes. zlib and zlib-rs only tell
+/// why the data is invalid in the [message](DecompressError::message), so they
+/// report any invalid compressed data as [`ErrorKind::InvalidData`]. The more
+/// specific kinds come from flate2 itself, e.g. for the gzip header and
+/// trailer of the decoders, and from the miniz_oxide backend.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The zlib or gzip header is invalid, e.g. it fails its header check,
+    /// names an unknown compression method or asks for a larger window than
+    /// allowed.
+    InvalidHeader,
+
+    /// A deflate block uses the reserved block type.
+    InvalidBlockType,
+
+    /// The length of a stored block doesn't match its complement.
+    InvalidStoredLength,
+
+    /// The Huffman codes of a deflate block are invalid, or the data refers
+    /// to a code which doesn't exist.
+    InvalidHuffmanCode,
+
+    /// A distance refers back to before the start of the window, or uses an
+    /// invalid distance code.
+    InvalidDistance,
+
+    /// The checksum or length in the trailer of a stream doesn't match the
+    /// decompressed data.
+    ChecksumMismatch,
+
+    /// The compressed data is invalid in some other way, or the backend
+    /// doesn't tell how.
+    InvalidData,
+
+    /// The input ended before the end of the stream.
+    TruncatedInput,
+
+    /// More data was passed in after the stream was finished.
+    StreamFinished,
+
+    /// The stream needs a preset dictionary, see
+    /// [`DecompressError::needs_dictionary`].
+    NeedsDictionary,
+
+    /// A parameter is out of range or not supported in the current state.
+    InvalidParameter,
+
+    /// Memory couldn't be allocated, or the allocation would exceed the
+    /// memory limit of the stream.
+    OutOfMemory,
+
+    /// The operation isn't supported by the backend.
+    Unsupported,
+
+    /// The decompressed data exceeds a limit set with [`DecompressLimits`].
+    LimitExceeded,
+
+    /// The output buffer passed to a one-shot function such as
+    /// [`decompress_into_slice`](crate::decompress_into_slice) is too small.
+    BufferTooSmall,
+
+    /// Any other error.
+    Other,
 }
 
 /// The inner state for an error when decompressing
 #[derive(Clone, Debug)]
 pub(crate) enum DecompressErrorInner {
-    General { msg: ErrorMessage },
+    General { msg: ErrorMessage, kind: ErrorKind },
     NeedsDictionary(u32),
 }
 
 /// Error returned when a decompression object finds that the input stream of
 /// bytes was not a valid input stream of bytes.
 #[derive(Clone, Debug)]
-pub struct DecompressError(pub(crate) DecompressErrorInner);
+pub struct DecompressError {
+    pub(crate) inner: DecompressErrorInner,
+    offsets: Option<(u64, u64)>,
+}
 
 impl DecompressError {
     /// Indicates whether decompression failed due to requiring a dictionary.
@@ -129,23 +492,89 @@ impl DecompressError {
     /// The resulting integer is the Adler-32 checksum of the dictionary
     /// required.
     pub fn needs_dictionary(&self) -> Option<u32> {
-        match self.0 {
+        match self.inner {
             DecompressErrorInner::NeedsDictionary(adler) => Some(adler),
             _ => None,
         }
     }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self.inner {
+            DecompressErrorInner::General { kind, .. } => kind,
+            DecompressErrorInner::NeedsDictionary(_) => ErrorKind::NeedsDictionary,
+        }
+    }
+
+    /// Returns the number of compressed bytes consumed before the error was
+    /// detected, if known.
+    ///
+    /// This is the `total_in` of the decompressor after the call that failed.
+    pub fn input_offset(&self) -> Option<u64> {
+        self.offsets.map(|(input, _)| input)
+    }
+
+    /// Returns the number of decompressed bytes produced before the error was
+    /// detected, if known.
+    pub fn output_offset(&self) -> Option<u64> {
+        self.offsets.map(|(_, output)| output)
+    }
+
+    /// Creates an error of the given kind with an optional message, e.g. for
+    /// a custom [backend](crate::backend).
+    ///
+    /// Use [`DecompressError::new_needs_dictionary`] for an error of kind
+    /// [`ErrorKind::NeedsDictionary`].
+    pub fn new(kind: ErrorKind, message: Option<&'static str>) -> DecompressError {
+        DecompressError {
+            inner: DecompressErrorInner::General {
+                msg: ErrorMessage(message),
+                kind,
+            },
+            offsets: None,
+        }
+    }
+
+    /// Creates an error for a stream which needs the dictionary with the
+    /// Adler-32 checksum `adler`.
+    pub fn new_needs_dictionary(adler: u32) -> DecompressError {
+        DecompressError {
+            inner: DecompressErrorInner::NeedsDictionary(adler),
+            offsets: None,
+        }
+    }
+
+    #[cfg(feature = "miniz_oxide")]
+    pub(crate) fn static_message(&self) -> Option<&'static str> {
+        match &self.inner {
+            DecompressErrorInner::General { msg, .. } => msg.0,
+            _ => None,
+        }
+    }
+
+    pub(crate) fn at(mut self, total_in: u64, total_out: u64) -> DecompressError {
+        self.offsets = Some((total_in, total_out));
+        self
+    }
+
+    /// The error for a stream which ended early, after `total_in` compressed
+    /// bytes were decompressed to `total_out` bytes.
+    pub(crate) fn truncated(total_in: u64, total_out: u64) -> DecompressError {
+        DecompressError::new(ErrorKind::TruncatedInput, None).at(total_in, total_out)
+    }
 }
 
 #[inline]
-pub(crate) fn decompress_failed<T>(msg: ErrorMessage) -> Result<T, DecompressError> {
-    Err(DecompressError(DecompressErrorInner::General { msg }))
+pub(crate) fn decompress_failed<T>(
+    kind: ErrorKind,
+    msg: ErrorMessage,
+) -> Result<T, DecompressError> {
+    Err(DecompressError::new(kind, msg.0))
 }
 
 #[inline]
 pub(crate) fn decompress_need_dict<T>(adler: u32) -> Result<T, DecompressError> {
-    Err(DecompressError(DecompressErrorInner::NeedsDictionary(
-        adler,
-    )))
+    Err(DecompressError::new_needs_dictionary(adler))
 }
 
 /// Error returned when a compression object is used incorrectly or otherwise
@@ -153,11 +582,23 @@ pub(crate) fn decompress_need_dict<T>(adler: u32) -> Result<T, DecompressError>
 #[derive(Clone, Debug)]
 pub struct CompressError {
     pub(crate) msg: ErrorMessage,
+    kind: ErrorKind,
+    offsets: Option<(u64, u64)>,
 }
 
 #[inline]
-pub(crate) fn compress_failed<T>(msg: ErrorMessage) -> Result<T, CompressError> {
-    Err(CompressError { msg })
+pub(crate) fn compress_failed<T>(kind: ErrorKind, msg: ErrorMessage) -> Result<T, CompressError> {
+    Err(CompressError::new(kind, msg.0))
+}
+
+/// The error of writing to an encoder which was already finished.
+pub(crate) fn write_after_finish() -> io::Error {
+    let msg = Some("write after the stream was finished");
+    CompressError::new(ErrorKind::StreamFinished, msg).into()
+}
+
+fn low_bits(bits: u8) -> u16 {
+    ((1u32 << bits) - 1) as u16
 }
 
 /// Possible status results of compressing some data or successfully
@@ -189,6 +630,347 @@ pub enum Status {
     StreamEnd,
 }
 
+/// The default memory level used by zlib, balancing speed and memory usage.
+pub(crate) const DEFAULT_MEM_LEVEL: u8 = 8;
+
+impl CompressBuilder {
+    /// Configures the compression level, defaults to [`Compression::default`].
+    pub fn level(mut self, level: Compression) -> CompressBuilder {
+        self.level = level;
+        self
+    }
+
+    /// Configures the framing of the compressed data, defaults to
+    /// [`Format::Zlib`].
+    pub fn format(mut self, format: Format) -> CompressBuilder {
+        self.format = format;
+        self
+    }
+
+    /// Configures the base-2 logarithm of the sliding window size, which must
+    /// be between 9 and 15. Defaults to 15.
+    ///
+    /// Data compressed with a smaller window can only refer back to fewer
+    /// bytes, which reduces memory usage at the cost of compression ratio.
+    /// The miniz_oxide backend limits the compression level and strategy to
+    /// stay within smaller windows.
+    pub fn window_bits(mut self, window_bits: u8) -> CompressBuilder {
+        self.window_bits = window_bits;
+        self
+    }
+
+    /// Configures how much memory is allocated for the internal compression
+    /// state, which must be between 1 and 9. Defaults to 8.
+    ///
+    /// A memory level of 1 uses minimum memory but is slow and reduces the
+    /// compression ratio, a memory level of 9 uses maximum memory for optimal
+    /// speed.
+    ///
+    /// Only the C zlib backends support memory levels other than the default.
+    /// The miniz_oxide and zlib-rs backends return an error of kind
+    /// [`ErrorKind::Unsupported`] from [`CompressBuilder::build`], see
+    /// [`Capabilities::mem_level`](crate::Capabilities::mem_level).
+    pub fn mem_level(mut self, mem_level: u8) -> CompressBuilder {
+        self.mem_level = mem_level;
+        self
+    }
+
+    /// Configures the compression strategy, defaults to [`Strategy::Default`].
+    pub fn strategy(mut self, strategy: Strategy) -> CompressBuilder {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Limits the memory of the internal compression state to `limit`
+    /// bytes. Not limited by default.
+    ///
+    /// [`CompressBuilder::build`] returns an error of kind
+    /// [`ErrorKind::OutOfMemory`] if the state doesn't fit. The C zlib
+    /// backends count each allocation, all of which are made when the stream
+    /// is created. The miniz_oxide and zlib-rs backends don't report their
+    /// allocations, so the limit is checked against a static estimate of the
+    /// state instead, the one [`Compress::memory_usage`] returns. It includes
+    /// the history kept for [`CompressBuilder::keep_dictionary`].
+    pub fn memory_limit(mut self, limit: usize) -> CompressBuilder {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Configures whether the compressor keeps its most recent input for
+    /// [`Compress::get_dictionary`] with the miniz_oxide and zlib-rs backends,
+    /// which don't expose their window. Defaults to `false`.
+    ///
+    /// Keeping it copies all input into a history of the size of the window.
+    /// Without it, `get_dictionary` returns an empty dictionary with these
+    /// backends. The C zlib backends ignore this parameter, as they always
+    /// know their window.
+    pub fn keep_dictionary(mut self, keep: bool) -> CompressBuilder {
+        self.keep_dictionary = keep;
+        self
+    }
+
+    /// Creates a new [`Compress`] object with the configured parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parameter is out of range, or if the selected
+    /// backend doesn't support the configured parameters.
+    pub fn build(&self) -> Result<Compress, CompressError> {
+        Ok(Compress {
+            inner: Box::new(self.make(Deflate::make)?),
+        })
+    }
+
+    /// Checks the parameters, and creates the state of a compressor with
+    /// them through the `make` function of a backend.
+    pub(crate) fn make<T>(
+        &self,
+        make: impl FnOnce(&CompressBuilder) -> Result<T, CompressError>,
+    ) -> Result<T, CompressError> {
+        if !(9..=15).contains(&self.window_bits) {
+            return compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("window_bits must be within 9 ..= 15")),
+            );
+        }
+        if !(1..=9).contains(&self.mem_level) {
+            return compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("mem_level must be within 1 ..= 9")),
+            );
+        }
+        make(self)
+    }
+
+    /// Returns the window bits in the convention used by zlib, where negative
+    /// values select a raw stream and values above 15 select gzip framing.
+    #[cfg(feature = "any_zlib")]
+    pub(crate) fn zlib_window_bits(&self) -> i32 {
+        let window_bits = i32::from(self.window_bits);
+        match self.format {
+            Format::Deflate => -window_bits,
+            Format::Zlib => window_bits,
+            Format::Gzip => window_bits + 16,
+        }
+    }
+}
+
+impl Default for CompressBuilder {
+    fn default() -> CompressBuilder {
+        CompressBuilder {
+            level: Compression::default(),
+            format: Format::Zlib,
+            window_bits: ffi::MZ_DEFAULT_WINDOW_BITS as u8,
+            mem_level: DEFAULT_MEM_LEVEL,
+            strategy: Strategy::Default,
+            memory_limit: None,
+            keep_dictionary: false,
+        }
+    }
+}
+
+impl DecompressBuilder {
+    /// Configures the framing of the compressed data, defaults to
+    /// [`Format::Zlib`].
+    pub fn format(mut self, format: Format) -> DecompressBuilder {
+        self.format = format;
+        self
+    }
+
+    /// Configures the base-2 logarithm of the sliding window size, which must
+    /// be between 9 and 15. Defaults to 15.
+    ///
+    /// Streams whose zlib header asks for a larger window are rejected.
+    pub fn window_bits(mut self, window_bits: u8) -> DecompressBuilder {
+        self.window_bits = window_bits;
+        self
+    }
+
+    /// Limits the memory of the internal decompression state to `limit`
+    /// bytes. Not limited by default.
+    ///
+    /// An allocation which would exceed the limit fails with an error of kind
+    /// [`ErrorKind::OutOfMemory`], either from [`DecompressBuilder::build`]
+    /// or from the call to [`Decompress::decompress`] which needed it. The C
+    /// zlib backends count each allocation, and allocate the window once the
+    /// first data is decompressed. The miniz_oxide and zlib-rs backends don't
+    /// report their allocations, so the limit is only checked against a
+    /// static estimate of the state when the stream is created, the one
+    /// [`Decompress::memory_usage`] returns. For zlib-rs it includes the copy
+    /// of the window kept for [`Decompress::get_dictionary`], as its stable
+    /// API doesn't expose the window.
+    pub fn memory_limit(mut self, limit: usize) -> DecompressBuilder {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Creates a new [`Decompress`] object with the configured parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a parameter is out of range, or if the state
+    /// doesn't fit into the memory limit.
+    pub fn build(&self) -> Result<Decompress, DecompressError> {
+        Ok(Decompress {
+            inner: Box::new(self.make(Inflate::make)?),
+            format: Some(self.format),
+            held: None,
+            limits: LimitState::default(),
+        })
+    }
+
+    /// Checks the parameters, and creates the state of a decompressor with
+    /// them through the `make` function of a backend, which takes whether
+    /// there's a zlib header, the window bits in the convention of zlib and
+    /// the memory limit.
+    pub(crate) fn make<T>(
+        &self,
+        make: impl FnOnce(bool, u8, Option<usize>) -> Result<T, DecompressError>,
+    ) -> Result<T, DecompressError> {
+        if !(9..=15).contains(&self.window_bits) {
+            return decompress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("window_bits must be within 9 ..= 15")),
+            );
+        }
+        let (zlib_header, window_bits) = match self.format {
+            Format::Deflate => (false, self.window_bits),
+            Format::Zlib => (true, self.window_bits),
+            Format::Gzip => (true, self.window_bits + 16),
+        };
+        make(zlib_header, window_bits, self.memory_limit)
+    }
+}
+
+impl Default for DecompressBuilder {
+    fn default() -> DecompressBuilder {
+        DecompressBuilder {
+            format: Format::Zlib,
+            window_bits: ffi::MZ_DEFAULT_WINDOW_BITS as u8,
+            memory_limit: None,
+        }
+    }
+}
+
+impl DecompressLimits {
+    /// The output below which [`DecompressLimits::max_ratio`] isn't checked.
+    const RATIO_GRACE: u64 = 64 * 1024;
+
+    /// Creates a set of limits without any limit.
+    pub fn new() -> DecompressLimits {
+        DecompressLimits::default()
+    }
+
+    /// Limits the decompressed data to `bytes` bytes.
+    ///
+    /// For the gzip decoders this covers all members together.
+    pub fn max_output(mut self, bytes: u64) -> DecompressLimits {
+        self.max_output = Some(bytes);
+        self
+    }
+
+    /// Limits the decompressed data to `ratio` times the compressed data.
+    ///
+    /// The start of a stream can legitimately expand a lot, so the ratio is
+    /// only checked once more than 64 KiB were decompressed. Only the deflate
+    /// data counts as compressed data, not the headers and trailers.
+    pub fn max_ratio(mut self, ratio: u64) -> DecompressLimits {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    /// Limits the number of members which the multi-member gzip decoders
+    /// decode.
+    ///
+    /// This doesn't apply to [`Decompress`] and the other decoders, which
+    /// only decode a single stream.
+    pub fn max_members(mut self, members: u64) -> DecompressLimits {
+        self.max_members = Some(members);
+        self
+    }
+
+    /// Returns the limit on the decompressed data, if any.
+    pub fn get_max_output(&self) -> Option<u64> {
+        self.max_output
+    }
+
+    /// Returns the limit on the ratio of decompressed to compressed data, if
+    /// any.
+    pub fn get_max_ratio(&self) -> Option<u64> {
+        self.max_ratio
+    }
+
+    /// Returns the limit on the number of gzip members, if any.
+    pub fn get_max_members(&self) -> Option<u64> {
+        self.max_members
+    }
+
+    /// Returns an error if another gzip member would exceed the limit, given
+    /// the number of members decoded so far.
+    pub(crate) fn check_members(&self, members: u64) -> Result<(), DecompressError> {
+        match self.max_members {
+            Some(max) if members >= max => Err(DecompressError::new(
+                ErrorKind::LimitExceeded,
+                Some("gzip stream exceeds the member limit"),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl LimitState {
+    /// Returns how much of an output buffer of `len` bytes may be used,
+    /// leaving room for one byte beyond the limit to detect exceeding it.
+    fn output_len(&self, total_out: u64, len: usize) -> usize {
+        match self.limits.max_output {
+            Some(max) => {
+                let used = self.previous_out.wrapping_add(total_out);
+                let left = max.saturating_sub(used);
+                len.min(usize::try_from(left.saturating_add(1)).unwrap_or(usize::MAX))
+            }
+            None => len,
+        }
+    }
+
+    fn check(&self, total_in: u64, total_out: u64) -> Result<(), DecompressError> {
+        let input = self.previous_in.wrapping_add(total_in);
+        let output = self.previous_out.wrapping_add(total_out);
+        let msg = match self.limits {
+            DecompressLimits {
+                max_output: Some(max),
+                ..
+            } if output > max => "decompressed data exceeds the output limit",
+            DecompressLimits {
+                max_ratio: Some(ratio),
+                ..
+            } if output > DecompressLimits::RATIO_GRACE && output / ratio.max(1) > input => {
+                "decompressed data exceeds the ratio limit"
+            }
+            _ => return Ok(()),
+        };
+        Err(DecompressError::new(ErrorKind::LimitExceeded, Some(msg)))
+    }
+}
+
+/// Creates a decompressor of the default backend without a memory limit.
+fn inflate(zlib_header: bool, window_bits: u8) -> Box<dyn InflateBackend> {
+    let inflate = Inflate::make(zlib_header, window_bits, None);
+    Box::new(inflate.expect("failed to create decompression stream"))
+}
+
+/// Whether `cmf` is a valid first byte of a zlib header.
+fn zlib_cmf(cmf: u8) -> bool {
+    cmf & 0x0f == 8 && cmf >> 4 <= 7
+}
+
+fn format_from_bool(zlib_header: bool) -> Format {
+    if zlib_header {
+        Format::Zlib
+    } else {
+        Format::Deflate
+    }
+}
+
 impl Compress {
     /// Creates a new object ready for compressing data that it's given.
     ///
@@ -196,9 +978,54 @@ impl Compress {
     /// to be performed, and the `zlib_header` argument indicates whether the
     /// output data should have a zlib header or not.
     pub fn new(level: Compression, zlib_header: bool) -> Compress {
-        Compress {
-            inner: Deflate::make(level, zlib_header, ffi::MZ_DEFAULT_WINDOW_BITS as u8),
-        }
+        Compress::new_with_strategy(level, zlib_header, Strategy::Default)
+    }
+
+    /// Creates a new object ready for compressing data that it's given,
+    /// using the specified compression strategy.
+    ///
+    /// The `level` and `zlib_header` arguments have the same meaning as in
+    /// [`Compress::new`]. The `strategy` argument tunes the compression
+    /// algorithm for the kind of data being compressed, see [`Strategy`].
+    ///
+    /// # Panics
+    ///
+    /// If the backend doesn't support the strategy, which is the case for any
+    /// strategy but [`Strategy::Default`] with the zlib-rs backend. Use
+    /// [`Compress::builder`] to get an error instead.
+    pub fn new_with_strategy(
+        level: Compression,
+        zlib_header: bool,
+        strategy: Strategy,
+    ) -> Compress {
+        Compress::builder()
+            .level(level)
+            .format(format_from_bool(zlib_header))
+            .strategy(strategy)
+            .build()
+            .expect("failed to create compression stream")
+    }
+
+    /// Returns a builder to create a [`Compress`] object with full control
+    /// over its parameters.
+    ///
+    /// See [`CompressBuilder`] for the available parameters and their
+    /// defaults.
+    pub fn builder() -> CompressBuilder {
+        CompressBuilder::default()
+    }
+
+    /// Returns the number of bytes of memory held by the internal state of
+    /// this compressor.
+    ///
+    /// This doesn't include the memory of the `Compress` object itself, and
+    /// is 0 for a custom [backend](crate::backend) which doesn't report it.
+    ///
+    /// Only the C zlib backends count what they allocate. For the miniz_oxide
+    /// and zlib-rs backends this is a static estimate, which only depends on
+    /// the parameters of the stream and doesn't change as it's used.
+    pub fn memory_usage(&self) -> usize {
+        self.inner.memory_usage()
     }
 
     /// Creates a new object ready for compressing data that it's given.
@@ -213,7 +1040,6 @@ impl Compress {
     ///
     /// If `window_bits` does not fall into the range 9 ..= 15,
     /// this function will panic.
-    #[cfg(feature = "any_zlib")]
     pub fn new_with_window_bits(
         level: Compression,
         zlib_header: bool,
@@ -223,9 +1049,12 @@ impl Compress {
             window_bits > 8 && window_bits < 16,
             "window_bits must be within 9 ..= 15"
         );
-        Compress {
-            inner: Deflate::make(level, zlib_header, window_bits),
-        }
+        Compress::builder()
+            .level(level)
+            .format(format_from_bool(zlib_header))
+            .window_bits(window_bits)
+            .build()
+            .expect("failed to create compression stream")
     }
 
     /// Creates a new object ready for compressing data that it's given.
@@ -240,14 +1069,27 @@ impl Compress {
     ///
     /// If `window_bits` does not fall into the range 9 ..= 15,
     /// this function will panic.
-    #[cfg(feature = "any_zlib")]
     pub fn new_gzip(level: Compression, window_bits: u8) -> Compress {
         assert!(
             window_bits > 8 && window_bits < 16,
             "window_bits must be within 9 ..= 15"
         );
+        Compress::builder()
+            .level(level)
+            .format(Format::Gzip)
+            .window_bits(window_bits)
+            .build()
+            .expect("failed to create compression stream")
+    }
+
+    /// Creates a new object which compresses data with the given backend
+    /// instead of the one selected through cargo features.
+    ///
+    /// The backend is responsible for the framing of its output, see the
+    /// [`backend`](crate::backend) module.
+    pub fn with_backend<B: DeflateBackend + 'static>(backend: B) -> Compress {
         Compress {
-            inner: Deflate::make(level, true, window_bits + 16),
+            inner: Box::new(backend),
         }
     }
 
@@ -263,35 +1105,64 @@ impl Compress {
         self.inner.total_out()
     }
 
+    /// Returns an upper bound on the size of the output of compressing
+    /// `input_len` bytes with this object.
+    ///
+    /// The bound takes the parameters of this object into account, and is
+    /// meant to size the output buffer of a single call to [`Compress::compress`]
+    /// with [`FlushCompress::Finish`] right after the object was created or
+    /// reset. If that call receives all `input_len` bytes and an output buffer of
+    /// this size, it's guaranteed to return [`Status::StreamEnd`]. Flushes other
+    /// than [`FlushCompress::None`] and [`FlushCompress::Finish`] may produce
+    /// more output than this bound.
+    ///
+    /// See also [`compress_bound`], which only depends on the format.
+    pub fn bound(&self, input_len: usize) -> usize {
+        self.inner.bound(input_len)
+    }
+
     /// Specifies the compression dictionary to use.
     ///
-    /// Returns the Adler-32 checksum of the dictionary.
-    #[cfg(feature = "any_c_zlib")]
+    /// Returns the Adler-32 checksum of the dictionary. The miniz_oxide
+    /// backend can only set a dictionary before any data was compressed.
     pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
-        // SAFETY: The field `inner` must always be accessed as a raw pointer,
-        // since it points to a cyclic structure. No copies of `inner` can be
-        // retained for longer than the lifetime of `self.inner.inner.stream_wrapper`.
-        let stream = self.inner.inner.stream_wrapper.inner;
-        let rc = unsafe {
-            (*stream).msg = std::ptr::null_mut();
-            assert!(dictionary.len() < ffi::uInt::MAX as usize);
-            ffi::deflateSetDictionary(stream, dictionary.as_ptr(), dictionary.len() as ffi::uInt)
-        };
+        self.inner.set_dictionary(dictionary)
+    }
 
-        match rc {
-            ffi::MZ_STREAM_ERROR => compress_failed(self.inner.inner.msg()),
-            #[allow(clippy::unnecessary_cast)]
-            ffi::MZ_OK => Ok(unsafe { (*stream).adler } as u32),
-            c => panic!("unknown return code: {}", c),
+    /// Inserts bits into the compressed output ahead of the data compressed
+    /// next, similar to zlib's `deflatePrime`.
+    ///
+    /// The low `bits` bits of `value` are written least significant bit first,
+    /// and `bits` may be at most 16. This is used to splice deflate streams,
+    /// by priming a compressor with the incomplete last byte of the preceding
+    /// stream.
+    ///
+    /// The miniz_oxide and zlib-rs backends return an error of kind
+    /// [`ErrorKind::Unsupported`], see
+    /// [`Capabilities::prime`](crate::backend::Capabilities::prime).
+    pub fn prime(&mut self, bits: u8, value: u16) -> Result<(), CompressError> {
+        if bits > 16 {
+            return compress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("bits must be within 0 ..= 16")),
+            );
         }
+        self.inner.prime(bits, value & low_bits(bits))
     }
 
-    /// Specifies the compression dictionary to use.
+    /// Returns the data the compressor currently uses as its dictionary,
+    /// similar to zlib's `deflateGetDictionary`.
     ///
-    /// Returns the Adler-32 checksum of the dictionary.
-    #[cfg(all(not(feature = "any_c_zlib"), feature = "zlib-rs"))]
-    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, CompressError> {
-        self.inner.set_dictionary(dictionary)
+    /// This is the most recent input of the stream, up to the size of the
+    /// window (32 KiB by default), including a dictionary set with
+    /// `set_dictionary`. Another compressor can continue with the same
+    /// context by using it as its own dictionary.
+    ///
+    /// The miniz_oxide and zlib-rs backends only keep the dictionary if the
+    /// compressor was built with [`CompressBuilder::keep_dictionary`].
+    /// Without it, the dictionary is empty.
+    pub fn get_dictionary(&self) -> Vec<u8> {
+        self.inner.get_dictionary()
     }
 
     /// Quickly resets this compressor without having to reallocate anything.
@@ -301,42 +1172,55 @@ impl Compress {
         self.inner.reset();
     }
 
+    /// Creates an independent copy of this compressor, including all of its
+    /// internal state, similar to zlib's `deflateCopy`.
+    ///
+    /// The copy continues the stream from the same point as this compressor,
+    /// which allows taking a checkpoint of a stream to roll back to later.
+    ///
+    /// This requires mutable access as some backends can't copy a stream
+    /// through a shared reference. An error is returned if the copy couldn't
+    /// be allocated, and one of kind [`ErrorKind::Unsupported`] with the
+    /// zlib-rs backend, whose stable API can't copy a stream. See
+    /// [`Capabilities::try_clone`](crate::Capabilities::try_clone).
+    pub fn try_clone(&mut self) -> Result<Compress, CompressError> {
+        Ok(Compress {
+            inner: self.inner.try_clone()?,
+        })
+    }
+
     /// Dynamically updates the compression level.
     ///
     /// This can be used to switch between compression levels for different
     /// kinds of data, or it can be used in conjunction with a call to reset
-    /// to reuse the compressor.
+    /// to reuse the compressor. The compression strategy is left unchanged.
     ///
-    /// This may return an error if there wasn't enough output space to complete
-    /// the compression of the available input data before changing the
-    /// compression level. Flushing the stream before calling this method
-    /// ensures that the function will succeed on the first call.
-    #[cfg(feature = "any_zlib")]
+    /// This may return an error if the available input data hasn't been
+    /// completely compressed yet, as it needs to be compressed with the
+    /// previous level first. Flushing the stream (e.g. with
+    /// [`FlushCompress::Sync`]) and consuming all of its output before calling
+    /// this method ensures that the function will succeed on the first call.
+    ///
+    /// The miniz_oxide backend can't change the level of a compressor which
+    /// already produced output while a strategy other than
+    /// [`Strategy::Default`] is in use.
     pub fn set_level(&mut self, level: Compression) -> Result<(), CompressError> {
-        #[cfg(all(not(feature = "any_c_zlib"), feature = "zlib-rs"))]
-        {
-            self.inner.set_level(level)
-        }
-
-        #[cfg(feature = "any_c_zlib")]
-        {
-            use std::os::raw::c_int;
-            // SAFETY: The field `inner` must always be accessed as a raw pointer,
-            // since it points to a cyclic structure. No copies of `inner` can be
-            // retained for longer than the lifetime of `self.inner.inner.stream_wrapper`.
-            let stream = self.inner.inner.stream_wrapper.inner;
-            unsafe {
-                (*stream).msg = std::ptr::null_mut();
-            }
-            let rc =
-                unsafe { ffi::deflateParams(stream, level.0 as c_int, ffi::MZ_DEFAULT_STRATEGY) };
+        let strategy = self.inner.strategy();
+        self.inner.set_params(level, strategy)
+    }
 
-            match rc {
-                ffi::MZ_OK => Ok(()),
-                ffi::MZ_BUF_ERROR => compress_failed(self.inner.inner.msg()),
-                c => panic!("unknown return code: {}", c),
-            }
-        }
+    /// Dynamically updates the compression level and strategy, similar to
+    /// zlib's `deflateParams`.
+    ///
+    /// The same restrictions as for [`Compress::set_level`] apply: the stream
+    /// should be flushed before calling this method. The miniz_oxide backend
+    /// can only switch to [`Strategy::Default`] once compression started.
+    pub fn set_params(
+        &mut self,
+        level: Compression,
+        strategy: Strategy,
+    ) -> Result<(), CompressError> {
+        self.inner.set_params(level, strategy)
     }
 
     /// Compresses the input data into the output, consuming only as much
@@ -352,7 +1236,8 @@ impl Compress {
         output: &mut [u8],
         flush: FlushCompress,
     ) -> Result<Status, CompressError> {
-        self.inner.compress(input, output, flush)
+        let res = self.inner.compress(input, output, flush);
+        res.map_err(|e| e.at(self.total_in(), self.total_out()))
     }
 
     /// Similar to [`Self::compress`] but accepts uninitialized buffer.
@@ -366,7 +1251,8 @@ impl Compress {
         output: &mut [MaybeUninit<u8>],
         flush: FlushCompress,
     ) -> Result<Status, CompressError> {
-        self.inner.compress_uninit(input, output, flush)
+        let res = self.inner.compress_uninit(input, output, flush);
+        res.map_err(|e| e.at(self.total_in(), self.total_out()))
     }
 
     /// Compresses the input data into the extra space of the output, consuming
@@ -402,7 +1288,10 @@ impl Decompress {
     /// to have a zlib header or not.
     pub fn new(zlib_header: bool) -> Decompress {
         Decompress {
-            inner: Inflate::make(zlib_header, ffi::MZ_DEFAULT_WINDOW_BITS as u8),
+            inner: inflate(zlib_header, ffi::MZ_DEFAULT_WINDOW_BITS as u8),
+            format: Some(format_from_bool(zlib_header)),
+            held: None,
+            limits: LimitState::default(),
         }
     }
 
@@ -411,19 +1300,22 @@ impl Decompress {
     /// The `zlib_header` argument indicates whether the input data is expected
     /// to have a zlib header or not. The `window_bits` parameter indicates the
     /// base-2 logarithm of the sliding window size and must be between 9 and 15.
+    /// Streams whose zlib header asks for a larger window are rejected.
     ///
     /// # Panics
     ///
     /// If `window_bits` does not fall into the range 9 ..= 15,
     /// this function will panic.
-    #[cfg(feature = "any_zlib")]
     pub fn new_with_window_bits(zlib_header: bool, window_bits: u8) -> Decompress {
         assert!(
             window_bits > 8 && window_bits < 16,
             "window_bits must be within 9 ..= 15"
         );
         Decompress {
-            inner: Inflate::make(zlib_header, window_bits),
+            inner: inflate(zlib_header, window_bits),
+            format: Some(format_from_bool(zlib_header)),
+            held: None,
+            limits: LimitState::default(),
         }
     }
 
@@ -436,21 +1328,130 @@ impl Decompress {
     ///
     /// If `window_bits` does not fall into the range 9 ..= 15,
     /// this function will panic.
-    #[cfg(feature = "any_zlib")]
     pub fn new_gzip(window_bits: u8) -> Decompress {
         assert!(
             window_bits > 8 && window_bits < 16,
             "window_bits must be within 9 ..= 15"
         );
         Decompress {
-            inner: Inflate::make(true, window_bits + 16),
+            inner: inflate(true, window_bits + 16),
+            format: Some(Format::Gzip),
+            held: None,
+            limits: LimitState::default(),
+        }
+    }
+
+    /// Creates a new object ready for decompressing data in any of the
+    /// supported formats, which is detected from the start of the input.
+    ///
+    /// A gzip header is recognized by its magic bytes and a zlib header by its
+    /// header check, anything else is decompressed as a raw deflate stream.
+    /// A raw deflate stream only looks like it starts with a zlib header if
+    /// its first block is a stored block with non-zero padding bits, which
+    /// isn't written by common compressors. The detected format is returned
+    /// by [`Decompress::format`].
+    ///
+    /// The format can mostly be told from the first byte. If it can't, that
+    /// byte is consumed and held back until the next one is passed in.
+    pub fn new_auto() -> Decompress {
+        Decompress {
+            inner: inflate(true, ffi::MZ_DEFAULT_WINDOW_BITS as u8),
+            format: None,
+            held: None,
+            limits: LimitState::default(),
+        }
+    }
+
+    /// Creates a new object which decompresses data with the given backend
+    /// instead of the one selected through cargo features.
+    ///
+    /// The backend is expected to decode streams in the given `format`, which
+    /// is returned by [`Decompress::format`]. See the
+    /// [`backend`](crate::backend) module.
+    pub fn with_backend<B: InflateBackend + 'static>(backend: B, format: Format) -> Decompress {
+        Decompress {
+            inner: Box::new(backend),
+            format: Some(format),
+            held: None,
+            limits: LimitState::default(),
         }
     }
 
+    /// Returns a builder to create a [`Decompress`] object with full control
+    /// over its parameters.
+    ///
+    /// See [`DecompressBuilder`] for the available parameters and their
+    /// defaults.
+    pub fn builder() -> DecompressBuilder {
+        DecompressBuilder::default()
+    }
+
+    /// Returns the number of bytes of memory held by the internal state of
+    /// this decompressor.
+    ///
+    /// This doesn't include the memory of the `Decompress` object itself, and
+    /// is 0 for a custom [backend](crate::backend) which doesn't report it.
+    ///
+    /// Only the C zlib backends count what they allocate, so the window only
+    /// shows up once it's needed. For the miniz_oxide and zlib-rs backends
+    /// this is a static estimate, which only depends on the parameters of the
+    /// stream and doesn't change as it's used.
+    pub fn memory_usage(&self) -> usize {
+        self.inner.memory_usage()
+    }
+
+    /// Returns the format of the compressed data.
+    ///
+    /// This is `None` for a decompressor created with [`Decompress::new_auto`]
+    /// until it saw enough input to detect the format.
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+
+    /// Detects the format of the stream from the start of `input`, or returns
+    /// the status to return if more input is needed.
+    fn detect_format(&mut self, input: &[u8]) -> Option<Status> {
+        let mut start = [0; 2];
+        let held = usize::from(self.held.is_some());
+        if let Some(byte) = self.held {
+            start[0] = byte;
+        }
+        let len = (held + input.len()).min(start.len());
+        start[held..len].copy_from_slice(&input[..len - held]);
+
+        let format = match start[..len] {
+            [0x1f, 0x8b] => Format::Gzip,
+            [cmf, flg] if zlib_cmf(cmf) && u16::from_be_bytes([cmf, flg]) % 31 == 0 => Format::Zlib,
+            [byte] if byte == 0x1f || zlib_cmf(byte) => {
+                self.held = Some(byte);
+                return Some(if input.is_empty() {
+                    Status::BufError
+                } else {
+                    Status::Ok
+                });
+            }
+            [] => return Some(Status::BufError),
+            _ => Format::Deflate,
+        };
+        if format != Format::Zlib {
+            self.inner.reset_format(format);
+        }
+        self.format = Some(format);
+
+        // A single byte never decompresses to anything.
+        if let Some(byte) = self.held.take() {
+            let res = self
+                .inner
+                .decompress(&[byte], &mut [], FlushDecompress::None);
+            debug_assert!(res.is_ok() && self.inner.total_in() == 1);
+        }
+        None
+    }
+
     /// Returns the total number of input bytes which have been processed by
     /// this decompression object.
     pub fn total_in(&self) -> u64 {
-        self.inner.total_in()
+        self.inner.total_in() + u64::from(self.held.is_some())
     }
 
     /// Returns the total number of output bytes which have been produced by
@@ -487,7 +1488,18 @@ impl Decompress {
         output: &mut [u8],
         flush: FlushDecompress,
     ) -> Result<Status, DecompressError> {
-        self.inner.decompress(input, output, flush)
+        if self.format.is_none() {
+            if let Some(status) = self.detect_format(input) {
+                return Ok(status);
+            }
+        }
+        let len = self.limits.output_len(self.total_out(), output.len());
+        let res = self.inner.decompress(input, &mut output[..len], flush);
+        let res = res.and_then(|status| {
+            self.limits.check(self.total_in(), self.total_out())?;
+            Ok(status)
+        });
+        res.map_err(|e| e.at(self.total_in(), self.total_out()))
     }
 
     /// Similar to [`Self::decompress`] but accepts uninitialized buffer
@@ -501,7 +1513,20 @@ impl Decompress {
         output: &mut [MaybeUninit<u8>],
         flush: FlushDecompress,
     ) -> Result<Status, DecompressError> {
-        self.inner.decompress_uninit(input, output, flush)
+        if self.format.is_none() {
+            if let Some(status) = self.detect_format(input) {
+                return Ok(status);
+            }
+        }
+        let len = self.limits.output_len(self.total_out(), output.len());
+        let res = self
+            .inner
+            .decompress_uninit(input, &mut output[..len], flush);
+        let res = res.and_then(|status| {
+            self.limits.check(self.total_in(), self.total_out())?;
+            Ok(status)
+        });
+        res.map_err(|e| e.at(self.total_in(), self.total_out()))
     }
 
     /// Decompresses the input data into the extra space in the output vector
@@ -536,29 +1561,6 @@ impl Decompress {
     }
 
     /// Specifies the decompression dictionary to use.
-    #[cfg(feature = "any_c_zlib")]
-    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
-        // SAFETY: The field `inner` must always be accessed as a raw pointer,
-        // since it points to a cyclic structure. No copies of `inner` can be
-        // retained for longer than the lifetime of `self.inner.inner.stream_wrapper`.
-        let stream = self.inner.inner.stream_wrapper.inner;
-        let rc = unsafe {
-            (*stream).msg = std::ptr::null_mut();
-            assert!(dictionary.len() < ffi::uInt::MAX as usize);
-            ffi::inflateSetDictionary(stream, dictionary.as_ptr(), dictionary.len() as ffi::uInt)
-        };
-
-        #[allow(clippy::unnecessary_cast)]
-        match rc {
-            ffi::MZ_STREAM_ERROR => decompress_failed(self.inner.inner.msg()),
-            ffi::MZ_DATA_ERROR => decompress_need_dict(unsafe { (*stream).adler } as u32),
-            ffi::MZ_OK => Ok(unsafe { (*stream).adler } as u32),
-            c => panic!("unknown return code: {}", c),
-        }
-    }
-
-    /// Specifies the decompression dictionary to use.
-    #[cfg(all(not(feature = "any_c_zlib"), feature = "zlib-rs"))]
     pub fn set_dictionary(&mut self, dictionary: &[u8]) -> Result<u32, DecompressError> {
         self.inner.set_dictionary(dictionary)
     }
@@ -570,9 +1572,142 @@ impl Decompress {
     /// previously existing resources.
     ///
     /// The argument provided here indicates whether the reset state will
-    /// attempt to decode a zlib header first or not.
+    /// attempt to decode a zlib header first or not, also for a decompressor
+    /// created with [`Decompress::new_auto`]. A gzip decompressor keeps
+    /// expecting gzip framing if it's set.
     pub fn reset(&mut self, zlib_header: bool) {
-        self.inner.reset(zlib_header);
+        let format = match self.format {
+            Some(Format::Gzip) if zlib_header => Format::Gzip,
+            _ => format_from_bool(zlib_header),
+        };
+        self.next_stream(format);
+        self.limits.previous_in = 0;
+        self.limits.previous_out = 0;
+    }
+
+    /// Resets this decompressor for the next stream of a sequence, such as
+    /// the next member of a gzip file, whose data still counts against the
+    /// limits.
+    pub(crate) fn next_stream(&mut self, format: Format) {
+        self.limits.previous_in = self.limits.previous_in.wrapping_add(self.total_in());
+        self.limits.previous_out = self.limits.previous_out.wrapping_add(self.total_out());
+        self.inner.reset_format(format);
+        self.format = Some(format);
+        self.held = None;
+    }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// The limits apply to the data decompressed from now on, up to the next
+    /// [`Decompress::reset`]. Setting the limits again starts counting anew.
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.limits = LimitState {
+            limits,
+            previous_in: 0u64.wrapping_sub(self.total_in()),
+            previous_out: 0u64.wrapping_sub(self.total_out()),
+        };
+    }
+
+    /// Returns the limits on the decompressed data.
+    pub fn limits(&self) -> DecompressLimits {
+        self.limits.limits
+    }
+
+    /// Skips invalid compressed data until a possible full flush point, similar
+    /// to zlib's `inflateSync`.
+    ///
+    /// This allows recovering most of a stream which was compressed with
+    /// [`FlushCompress::Full`] points after a part of it was damaged. The
+    /// input is searched for the empty stored block emitted by a full flush,
+    /// after which the decompressor continues with the following block. On
+    /// success the number of skipped bytes, including the marker, is returned
+    /// and `total_in` is advanced by the same amount.
+    ///
+    /// An error is returned if the input contains no full flush point. All of
+    /// it is skipped in that case, and calling this again with the following
+    /// input continues the search. As the marker may also appear in regular
+    /// compressed data, the point found may not actually be a full flush
+    /// point, which is detected as an error when decompressing the following
+    /// data.
+    ///
+    /// Data following a full flush point doesn't refer to any previous data,
+    /// but the checksum of a zlib stream can't be verified anymore.
+    pub fn sync(&mut self, input: &[u8]) -> Result<usize, DecompressError> {
+        self.inner.sync(input)
+    }
+
+    /// Reports where decompression stopped in the input consumed so far,
+    /// similar to zlib's `data_type` field.
+    ///
+    /// See [`DecompressPosition`] for the available information. With the
+    /// miniz_oxide backend, the unused bits are only known at block boundaries
+    /// and reported as zero elsewhere. The zlib-rs backend never reports a
+    /// block boundary, see
+    /// [`Capabilities::block_boundaries`](crate::backend::Capabilities::block_boundaries).
+    pub fn position(&self) -> DecompressPosition {
+        self.inner.position()
+    }
+
+    /// Returns the sliding window of the decompressor, similar to zlib's
+    /// `inflateGetDictionary`.
+    ///
+    /// This is the most recent output of the stream, up to the size of the
+    /// window (32 KiB by default). Saved together with the
+    /// [`position`](Self::position) at a block boundary, it allows resuming
+    /// decompression there later with a raw decompressor, using
+    /// `set_dictionary` and [`prime`](Self::prime).
+    ///
+    /// The C zlib backends don't update the window with the output of a call
+    /// with [`FlushDecompress::Finish`] that completes the stream. The zlib-rs
+    /// backend keeps a copy of the window, as its stable API doesn't expose
+    /// it.
+    pub fn get_dictionary(&self) -> Vec<u8> {
+        self.inner.get_dictionary()
+    }
+
+    /// Inserts bits into the input of the decompressor ahead of the data
+    /// passed next, similar to zlib's `inflatePrime`.
+    ///
+    /// The low `bits` bits of `value` are used least significant bit first,
+    /// and `bits` may be at most 16. This is used to resume decompression at a
+    /// block boundary in the middle of a byte: a new raw decompressor is primed
+    /// with the [unused bits](DecompressPosition::unused_bits) of the last
+    /// consumed byte, `prime(bits, byte >> (8 - bits))`, and continues with the
+    /// following input.
+    ///
+    /// The miniz_oxide backend only supports priming at the start of a raw
+    /// deflate stream or at a block boundary, with at most 7 bits pending, and
+    /// returns an error of kind [`ErrorKind::Unsupported`] otherwise. The
+    /// zlib-rs backend always does, see
+    /// [`Capabilities::prime`](crate::backend::Capabilities::prime).
+    pub fn prime(&mut self, bits: u8, value: u16) -> Result<(), DecompressError> {
+        if bits > 16 {
+            return decompress_failed(
+                ErrorKind::InvalidParameter,
+                ErrorMessage(Some("bits must be within 0 ..= 16")),
+            );
+        }
+        self.inner.prime(bits, value & low_bits(bits))
+    }
+
+    /// Creates an independent copy of this decompressor, including all of its
+    /// internal state, similar to zlib's `inflateCopy`.
+    ///
+    /// The copy continues the stream from the same point as this
+    /// decompressor, e.g. to decode two different continuations of a stream.
+    ///
+    /// This requires mutable access as some backends can't copy a stream
+    /// through a shared reference. An error is returned if the copy couldn't
+    /// be allocated, and one of kind [`ErrorKind::Unsupported`] with the
+    /// zlib-rs backend, whose stable API can't copy a stream. See
+    /// [`Capabilities::try_clone`](crate::Capabilities::try_clone).
+    pub fn try_clone(&mut self) -> Result<Decompress, DecompressError> {
+        Ok(Decompress {
+            inner: self.inner.try_clone()?,
+            format: self.format,
+            held: self.held,
+            limits: self.limits,
+        })
     }
 }
 
@@ -581,27 +1716,40 @@ impl Error for DecompressError {}
 impl DecompressError {
     /// Retrieve the implementation's message about why the operation failed, if one exists.
     pub fn message(&self) -> Option<&str> {
-        match &self.0 {
-            DecompressErrorInner::General { msg } => msg.get(),
+        match &self.inner {
+            DecompressErrorInner::General { msg, .. } => msg.get(),
             _ => None,
         }
     }
 }
 
+/// The error is kept as the inner error of the `io::Error`, where it can be
+/// retrieved with [`io::Error::get_ref`] and downcasting. Truncated input is
+/// reported as [`io::ErrorKind::UnexpectedEof`], invalid input as
+/// [`io::ErrorKind::InvalidInput`].
 impl From<DecompressError> for io::Error {
     fn from(data: DecompressError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, data)
+        let kind = match data.kind() {
+            ErrorKind::TruncatedInput => io::ErrorKind::UnexpectedEof,
+            ErrorKind::OutOfMemory => io::ErrorKind::OutOfMemory,
+            ErrorKind::Other => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, data)
     }
 }
 
 impl fmt::Display for DecompressError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let msg = match &self.0 {
-            DecompressErrorInner::General { msg } => msg.get(),
+        let msg = match &self.inner {
+            DecompressErrorInner::General { msg, .. } => msg.get(),
             DecompressErrorInner::NeedsDictionary { .. } => Some("requires a dictionary"),
         };
         match msg {
             Some(msg) => write!(f, "deflate decompression error: {msg}"),
+            None if self.kind() == ErrorKind::TruncatedInput => {
+                write!(f, "incomplete deflate stream")
+            }
             None => write!(f, "deflate decompression error"),
         }
     }
@@ -614,11 +1762,47 @@ impl CompressError {
     pub fn message(&self) -> Option<&str> {
         self.msg.get()
     }
+
+    /// Creates an error of the given kind with an optional message, e.g. for
+    /// a custom [backend](crate::backend).
+    pub fn new(kind: ErrorKind, message: Option<&'static str>) -> CompressError {
+        CompressError {
+            msg: ErrorMessage(message),
+            kind,
+            offsets: None,
+        }
+    }
+
+    /// Returns the kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the number of uncompressed bytes consumed before the error
+    /// occurred, if known.
+    pub fn input_offset(&self) -> Option<u64> {
+        self.offsets.map(|(input, _)| input)
+    }
+
+    /// Returns the number of compressed bytes produced before the error
+    /// occurred, if known.
+    pub fn output_offset(&self) -> Option<u64> {
+        self.offsets.map(|(_, output)| output)
+    }
+
+    pub(crate) fn at(mut self, total_in: u64, total_out: u64) -> CompressError {
+        self.offsets = Some((total_in, total_out));
+        self
+    }
 }
 
 impl From<CompressError> for io::Error {
     fn from(data: CompressError) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, data)
+        let kind = match data.kind() {
+            ErrorKind::OutOfMemory => io::ErrorKind::OutOfMemory,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, data)
     }
 }
 
@@ -658,12 +1842,13 @@ unsafe fn write_to_spare_capacity_of_vec<T>(
 
 #[cfg(test)]
 mod tests {
-    use std::io::Write;
+    use std::io::{Read, Write};
 
-    use crate::write;
-    use crate::{Compression, Decompress, FlushDecompress};
+    use crate::{read, write};
+    use crate::{Compression, Decompress, DecompressError, ErrorKind, FlushDecompress};
 
-    use crate::{Compress, FlushCompress};
+    use super::format_from_bool;
+    use crate::{Compress, FlushCompress, Format, Status, Strategy};
 
     #[test]
     fn issue51() {
@@ -722,7 +1907,6 @@ mod tests {
         assert!(dst.starts_with(string));
     }
 
-    #[cfg(feature = "any_zlib")]
     #[test]
     fn test_gzip_flate() {
         let string = "hello, hello!".as_bytes();
@@ -748,6 +1932,153 @@ mod tests {
         assert_eq!(&decoded[..decoder.total_out() as usize], string);
     }
 
+    #[test]
+    fn gzip_framing() {
+        let input = b"hello, hello! ".repeat(1000);
+        let mut gz = crate::GzBuilder::new()
+            .filename("hello.txt")
+            .comment("greetings")
+            .extra(vec![1, 2, 3])
+            .write(Vec::new(), Compression::default());
+        gz.write_all(&input).unwrap();
+        let with_fields = gz.finish().unwrap();
+
+        let mut encoder = Compress::new_gzip(Compression::best(), 12);
+        let mut encoded = Vec::with_capacity(1024);
+        encoder
+            .compress_vec(&input, &mut encoded, FlushCompress::Finish)
+            .unwrap();
+        let mut decoded = Vec::new();
+        crate::read::GzDecoder::new(&encoded[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, input);
+
+        // One byte at a time, so the header and trailer are split.
+        for encoded in [encoded, with_fields] {
+            let mut d = Decompress::new_gzip(15);
+            let mut decoded = Vec::with_capacity(input.len());
+            for i in 0..encoded.len() {
+                let status = d
+                    .decompress_vec(&encoded[i..=i], &mut decoded, FlushDecompress::None)
+                    .unwrap();
+                assert_eq!(status == Status::StreamEnd, i == encoded.len() - 1);
+            }
+            assert_eq!(d.total_in(), encoded.len() as u64);
+            assert_eq!(decoded, input);
+
+            let mut corrupt = encoded.clone();
+            *corrupt.last_mut().unwrap() ^= 1;
+            let mut d = Decompress::new_gzip(15);
+            let mut decoded = Vec::with_capacity(input.len());
+            assert!(d
+                .decompress_vec(&corrupt, &mut decoded, FlushDecompress::Finish)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn gzip_reset() {
+        let input = b"hello, hello! ".repeat(100);
+        let mut c = Compress::new_gzip(Compression::default(), 15);
+        let mut encoded = Vec::with_capacity(c.bound(input.len()));
+        c.compress_vec(&input, &mut encoded, FlushCompress::Finish)
+            .unwrap();
+
+        let limited = Decompress::builder()
+            .format(Format::Gzip)
+            .memory_limit(1 << 20)
+            .build()
+            .unwrap();
+        for mut d in [Decompress::new_gzip(15), Decompress::new_auto(), limited] {
+            for _ in 0..2 {
+                let mut decoded = Vec::with_capacity(input.len());
+                let status = d
+                    .decompress_vec(&encoded, &mut decoded, FlushDecompress::Finish)
+                    .unwrap();
+                assert_eq!(status, Status::StreamEnd);
+                assert_eq!(decoded, input);
+                assert_eq!(d.format(), Some(Format::Gzip));
+                d.reset(true);
+            }
+            d.reset(false);
+            assert_eq!(d.format(), Some(Format::Deflate));
+        }
+
+        // The next member of a gzip file continues the counters.
+        let mut d = Decompress::new_gzip(15);
+        d.next_stream(Format::Gzip);
+        let mut decoded = Vec::with_capacity(input.len());
+        d.decompress_vec(&encoded, &mut decoded, FlushDecompress::Finish)
+            .unwrap();
+        assert_eq!(decoded, input);
+        assert_eq!(d.total_in(), encoded.len() as u64);
+    }
+
+    #[test]
+    fn auto_format() {
+        let input = b"hello, hello! ".repeat(100);
+        for format in [Format::Deflate, Format::Zlib, Format::Gzip] {
+            let mut c = Compress::builder().format(format).build().unwrap();
+            let mut encoded = Vec::with_capacity(c.bound(input.len()));
+            c.compress_vec(&input, &mut encoded, FlushCompress::Finish)
+                .unwrap();
+
+            let mut d = Decompress::new_auto();
+            let mut decoded = Vec::with_capacity(input.len());
+            d.decompress_vec(&encoded, &mut decoded, FlushDecompress::Finish)
+                .unwrap();
+            assert_eq!(d.format(), Some(format));
+            assert_eq!(decoded, input);
+
+            // The first byte alone may not tell the format.
+            let mut d = Decompress::new_auto();
+            let mut decoded = Vec::with_capacity(input.len());
+            let status = d
+                .decompress_vec(&[], &mut decoded, FlushDecompress::None)
+                .unwrap();
+            assert_eq!(status, Status::BufError);
+            assert_eq!(d.format(), None);
+            for i in 0..encoded.len() {
+                d.decompress_vec(&encoded[i..=i], &mut decoded, FlushDecompress::None)
+                    .unwrap();
+                assert_eq!(d.total_in(), i as u64 + 1);
+                if i > 0 {
+                    assert_eq!(d.format(), Some(format));
+                }
+            }
+            assert_eq!(decoded, input);
+        }
+    }
+
+    #[test]
+    fn window_bits() {
+        let input = (0..10_000).map(|i| i.to_string()).collect::<String>();
+        let compress = |window_bits| {
+            let mut c = Compress::new_with_window_bits(Compression::best(), true, window_bits);
+            let mut encoded = Vec::with_capacity(c.bound(input.len()));
+            c.compress_vec(input.as_bytes(), &mut encoded, FlushCompress::Finish)
+                .unwrap();
+            encoded
+        };
+        let decompress = |encoded: &[u8], window_bits| {
+            let mut d = Decompress::new_with_window_bits(true, window_bits);
+            let mut decoded = Vec::with_capacity(input.len());
+            d.decompress_vec(encoded, &mut decoded, FlushDecompress::Finish)
+                .map(|_| decoded)
+        };
+
+        for window_bits in [9, 12, 15] {
+            let encoded = compress(window_bits);
+            assert_eq!(encoded[0] >> 4, window_bits - 8);
+            assert_eq!(decompress(&encoded, window_bits).unwrap(), input.as_bytes());
+            assert_eq!(decompress(&encoded, 15).unwrap(), input.as_bytes());
+            if window_bits > 9 {
+                assert!(decompress(&encoded, window_bits - 1).is_err());
+            }
+        }
+    }
+
     #[cfg(feature = "any_zlib")]
     #[test]
     fn test_error_message() {
@@ -762,6 +2093,682 @@ mod tests {
         assert_eq!(err.message(), Some("invalid stored block lengths"));
     }
 
+    #[test]
+    fn test_error_message_matches_zlib() {
+        let mut output = [0; 128];
+        let mut decoder = Decompress::new(true);
+        let err = decoder
+            .decompress(&[0x78, 0x00], &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert_eq!(err.message(), Some("incorrect header check"));
+        assert_eq!(
+            err.to_string(),
+            "deflate decompression error: incorrect header check"
+        );
+
+        let mut decoder = Decompress::new(true);
+        let err = decoder
+            .decompress(&[0x79, 0x18], &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert_eq!(err.message(), Some("unknown compression method"));
+
+        let mut encoder = write::ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello world").unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        *compressed.last_mut().unwrap() ^= 1;
+        let mut decoder = Decompress::new(true);
+        let err = decoder
+            .decompress(&compressed, &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert_eq!(err.message(), Some("incorrect data check"));
+
+        let mut encoder = write::GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello world").unwrap();
+        let mut compressed = encoder.finish().unwrap();
+        *compressed.last_mut().unwrap() ^= 1;
+        let mut decoder = Decompress::new_gzip(15);
+        let err = decoder
+            .decompress(&compressed, &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert_eq!(err.message(), Some("incorrect length check"));
+
+        // Invalid deflate blocks. miniz_oxide doesn't tell why it failed to
+        // decompress them.
+        let cases: [(&[u8], &str); 7] = [
+            (&[0x07], "invalid block type"),
+            (&[0x01, 0, 0, 0, 0], "invalid stored block lengths"),
+            (&[0x03, 0x02], "invalid distance too far back"),
+            (&[0x4b, 0x04, 0x3e], "invalid distance code"),
+            (&[0x1b, 0x03], "invalid literal/length code"),
+            (&[0xf5, 0, 0], "too many length or distance symbols"),
+            (
+                &[0x05, 0, 0x02, 0x24, 0, 0, 0, 0, 0, 0, 0, 0],
+                "invalid bit length repeat",
+            ),
+        ];
+        let block_message = |msg| {
+            if cfg!(feature = "any_zlib") {
+                msg
+            } else {
+                "invalid deflate data"
+            }
+        };
+        for (input, msg) in cases {
+            let mut decoder = Decompress::new(false);
+            let err = decoder
+                .decompress(input, &mut output, FlushDecompress::Finish)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(err.message(), Some(block_message(msg)));
+        }
+
+        // A block which is split across calls.
+        let mut decoder = Decompress::new(false);
+        let err = [0x4b, 0x04, 0x3e]
+            .chunks(1)
+            .find_map(|input| {
+                decoder
+                    .decompress(input, &mut output, FlushDecompress::None)
+                    .err()
+            })
+            .unwrap();
+        assert_eq!(err.message(), Some(block_message("invalid distance code")));
+    }
+
+    fn compress_with_strategy(strategy: Strategy, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len() * 2 + 64);
+        let mut c = Compress::new_with_strategy(Compression::default(), false, strategy);
+        c.compress_vec(input, &mut output, FlushCompress::Finish)
+            .unwrap();
+        assert_eq!(c.total_in(), input.len() as u64);
+        output
+    }
+
+    #[test]
+    fn test_strategy() {
+        let input = b"the quick brown fox jumps over the lazy dog. ".repeat(50);
+
+        // The stable API of zlib-rs doesn't support strategies.
+        if !crate::backend_info().capabilities().strategies() {
+            let err = Compress::builder()
+                .strategy(Strategy::HuffmanOnly)
+                .build()
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            let mut c = Compress::new(Compression::default(), false);
+            let err = c
+                .set_params(Compression::best(), Strategy::Rle)
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            return;
+        }
+
+        let default = compress_with_strategy(Strategy::Default, &input);
+        let huffman_only = compress_with_strategy(Strategy::HuffmanOnly, &input);
+        let fixed = compress_with_strategy(Strategy::Fixed, &input);
+        // Without string matching the repetitions can't be exploited.
+        assert!(huffman_only.len() > default.len() * 4);
+
+        // The first block is the final block, compressed with fixed Huffman codes.
+        assert_eq!(fixed[0] & 0b111, 0b011);
+
+        for output in [default, huffman_only, fixed] {
+            let mut decoded = Vec::with_capacity(input.len());
+            let mut d = Decompress::new(false);
+            d.decompress_vec(&output, &mut decoded, FlushDecompress::Finish)
+                .unwrap();
+            assert_eq!(decoded, input);
+        }
+    }
+
+    #[test]
+    fn builder_rejects_invalid_parameters() {
+        let err = Compress::builder().window_bits(16).build().unwrap_err();
+        assert_eq!(err.message(), Some("window_bits must be within 9 ..= 15"));
+
+        let err = Compress::builder().mem_level(0).build().unwrap_err();
+        assert_eq!(err.message(), Some("mem_level must be within 1 ..= 9"));
+    }
+
+    #[cfg(feature = "any_c_zlib")]
+    #[test]
+    fn builder_mem_level() {
+        let input = b"hello world, hello world".repeat(100);
+        for mem_level in 1..=9 {
+            let mut c = Compress::builder()
+                .format(Format::Gzip)
+                .window_bits(10)
+                .mem_level(mem_level)
+                .build()
+                .unwrap();
+            let mut encoded = Vec::with_capacity(1024);
+            c.compress_vec(&input, &mut encoded, FlushCompress::Finish)
+                .unwrap();
+
+            let mut decoded = Vec::with_capacity(input.len());
+            let mut d = Decompress::new_gzip(10);
+            d.decompress_vec(&encoded, &mut decoded, FlushDecompress::Finish)
+                .unwrap();
+            assert_eq!(decoded, input);
+        }
+    }
+
+    #[cfg(not(feature = "any_c_zlib"))]
+    #[test]
+    fn builder_unsupported_parameters() {
+        let err = Compress::builder().mem_level(9).build().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn memory_limit() {
+        let err = Compress::builder().memory_limit(1024).build().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+        let err = Decompress::builder()
+            .memory_limit(1024)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+
+        let limit = 1 << 20;
+        let input = b"hello world, hello world".repeat(1000);
+        let mut c = Compress::builder()
+            .format(Format::Gzip)
+            .memory_limit(limit)
+            .build()
+            .unwrap();
+        let estimate = c.memory_usage();
+        let mut encoded = Vec::with_capacity(input.len());
+        c.compress_vec(&input, &mut encoded, FlushCompress::Finish)
+            .unwrap();
+        assert!(c.memory_usage() > 0);
+        assert!(c.memory_usage() <= limit);
+        // Only the C backends report what they allocate.
+        if cfg!(not(feature = "any_c_zlib")) {
+            assert_eq!(c.memory_usage(), estimate);
+        }
+
+        let mut d = Decompress::builder()
+            .format(Format::Gzip)
+            .memory_limit(limit)
+            .build()
+            .unwrap();
+        let estimate = d.memory_usage();
+        let mut decoded = Vec::with_capacity(input.len());
+        d.decompress_vec(&encoded, &mut decoded, FlushDecompress::Finish)
+            .unwrap();
+        assert_eq!(decoded, input);
+        assert!(d.memory_usage() > 0);
+        assert!(d.memory_usage() <= limit);
+        if cfg!(not(feature = "any_c_zlib")) {
+            assert_eq!(d.memory_usage(), estimate);
+        }
+
+        if crate::backend_info().capabilities().try_clone() {
+            let copy = d.try_clone().unwrap();
+            assert_eq!(copy.memory_usage(), d.memory_usage());
+            drop(copy);
+            assert!(d.memory_usage() > 0);
+        }
+    }
+
+    // The C backends only allocate the window once a call stops in the middle
+    // of a stream.
+    #[cfg(feature = "any_c_zlib")]
+    #[test]
+    fn memory_limit_for_window() {
+        let mut encoded = Vec::with_capacity(100);
+        let mut c = Compress::new(Compression::default(), true);
+        c.compress_vec(b"hello world", &mut encoded, FlushCompress::Finish)
+            .unwrap();
+
+        let mut d = Decompress::builder()
+            .memory_limit(16 * 1024)
+            .build()
+            .unwrap();
+        let mut decoded = Vec::with_capacity(100);
+        let err = d
+            .decompress_vec(&encoded[..5], &mut decoded, FlushDecompress::None)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    }
+
+    #[test]
+    fn bound() {
+        let formats = [Format::Deflate, Format::Zlib, Format::Gzip];
+        let random = crate::random_bytes().take(100_000).collect::<Vec<_>>();
+
+        for format in formats {
+            for len in [0, 1, 10, 1000, 100_000] {
+                for level in [0, 1, 6, 9] {
+                    for strategy in [Strategy::Default, Strategy::Fixed] {
+                        if strategy != Strategy::Default
+                            && !crate::backend_info().capabilities().strategies()
+                        {
+                            continue;
+                        }
+                        let mut c = Compress::builder()
+                            .format(format)
+                            .level(Compression::new(level))
+                            .strategy(strategy)
+                            .build()
+                            .unwrap();
+                        let bound = c.bound(len);
+                        assert!(bound <= crate::compress_bound(len, format));
+
+                        let mut output = Vec::with_capacity(bound);
+                        let status = c
+                            .compress_vec(&random[..len], &mut output, FlushCompress::Finish)
+                            .unwrap();
+                        assert_eq!(status, Status::StreamEnd, "{format:?} {len} {level}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn set_params_mid_stream() {
+        let input = b"the quick brown fox jumps over the lazy dog. ".repeat(200);
+        let (first, second) = input.split_at(input.len() / 2);
+
+        let mut c = Compress::new(Compression::best(), true);
+        let mut output = Vec::with_capacity(c.bound(input.len()));
+        c.compress_vec(first, &mut output, FlushCompress::Sync)
+            .unwrap();
+        let compressed_first = output.len();
+        assert!(compressed_first < first.len() / 10);
+
+        if cfg!(feature = "any_c_zlib") {
+            c.set_params(Compression::none(), Strategy::HuffmanOnly)
+                .unwrap();
+        } else {
+            let err = c
+                .set_params(Compression::none(), Strategy::HuffmanOnly)
+                .unwrap_err();
+            assert!(err.message().is_some());
+            c.set_level(Compression::none()).unwrap();
+        }
+        c.compress_vec(second, &mut output, FlushCompress::Finish)
+            .unwrap();
+        // The second half is stored without compression.
+        assert!(output.len() - compressed_first > second.len());
+
+        let mut d = Decompress::new(true);
+        let mut decoded = Vec::with_capacity(input.len());
+        let status = d
+            .decompress_vec(&output, &mut decoded, FlushDecompress::Finish)
+            .unwrap();
+        assert_eq!(status, Status::StreamEnd);
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn try_clone() {
+        let record = b"a record which is appended to the stream. ".repeat(20);
+        let rejected = b"a record which would exceed the budget. ".repeat(20);
+
+        let mut c = Compress::new(Compression::default(), true);
+        // Not supported by the stable API of zlib-rs.
+        if !crate::backend_info().capabilities().try_clone() {
+            let err = c.try_clone().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            let err = Decompress::new(true).try_clone().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            return;
+        }
+        let mut output = Vec::with_capacity(c.bound(record.len() * 2 + rejected.len()));
+        c.compress_vec(&record, &mut output, FlushCompress::Sync)
+            .unwrap();
+        let checkpoint_len = output.len();
+        let mut checkpoint = c.try_clone().unwrap();
+        assert_eq!(checkpoint.total_in(), c.total_in());
+        assert_eq!(checkpoint.total_out(), c.total_out());
+
+        c.compress_vec(&rejected, &mut output, FlushCompress::Sync)
+            .unwrap();
+        drop(c);
+        output.truncate(checkpoint_len);
+        checkpoint
+            .compress_vec(&record, &mut output, FlushCompress::Finish)
+            .unwrap();
+
+        // Decompress the first half, then continue with two decompressors.
+        let mut d = Decompress::new(true);
+        let mut first = Vec::with_capacity(record.len() * 2);
+        d.decompress_vec(&output[..checkpoint_len], &mut first, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(first, record);
+
+        let mut forked = d.try_clone().unwrap();
+        for d in [&mut d, &mut forked] {
+            let mut rest = Vec::with_capacity(record.len());
+            let status = d
+                .decompress_vec(
+                    &output[checkpoint_len..],
+                    &mut rest,
+                    FlushDecompress::Finish,
+                )
+                .unwrap();
+            assert_eq!(status, Status::StreamEnd);
+            assert_eq!(rest, record);
+            assert_eq!(d.total_out(), record.len() as u64 * 2);
+        }
+    }
+
+    #[test]
+    fn sync() {
+        let records = [
+            b"the first record. ".repeat(20),
+            b"the second record, which gets damaged. ".repeat(20),
+            b"the third record. ".repeat(20),
+        ];
+        for format in [Format::Zlib, Format::Deflate, Format::Gzip] {
+            let mut c = Compress::builder().format(format).build().unwrap();
+            let mut output = Vec::with_capacity(c.bound(2000));
+            let mut flush_points = Vec::new();
+            for (i, record) in records.iter().enumerate() {
+                let flush = if i == records.len() - 1 {
+                    FlushCompress::Finish
+                } else {
+                    FlushCompress::Full
+                };
+                c.compress_vec(record, &mut output, flush).unwrap();
+                flush_points.push(output.len());
+            }
+            let (first, second) = (flush_points[0], flush_points[1]);
+            output[first + 3] ^= 0xff;
+
+            let mut d = Decompress::builder().format(format).build().unwrap();
+            let mut decoded = Vec::with_capacity(2000);
+            d.decompress_vec(&output[..first], &mut decoded, FlushDecompress::None)
+                .unwrap();
+            assert_eq!(decoded, records[0]);
+
+            // The marker of the second full flush point is split between calls.
+            assert!(d.sync(&output[first..second - 2]).is_err());
+            assert_eq!(d.sync(&output[second - 2..]).unwrap(), 2);
+            assert_eq!(d.total_in(), second as u64);
+
+            decoded.clear();
+            let status = d
+                .decompress_vec(&output[second..], &mut decoded, FlushDecompress::Finish)
+                .unwrap();
+            assert_eq!(status, Status::StreamEnd);
+            assert_eq!(decoded, records[2]);
+        }
+    }
+
+    #[test]
+    fn block_boundaries() {
+        let chunks = (0..4)
+            .map(|i| format!("{} ", i).repeat(1000 * (i + 1)).into_bytes())
+            .collect::<Vec<_>>();
+        let mut c = Compress::new(Compression::default(), false);
+        let mut compressed = Vec::with_capacity(c.bound(20_000));
+        let mut ends = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let flush = if i + 1 == chunks.len() {
+                FlushCompress::Finish
+            } else {
+                FlushCompress::Block
+            };
+            c.compress_vec(chunk, &mut compressed, flush).unwrap();
+            ends.push(c.total_in());
+        }
+        ends.pop();
+
+        // The stable API of zlib-rs stops at block boundaries, but can't
+        // report them.
+        let reported = crate::backend_info().capabilities().block_boundaries();
+        let mut d = Decompress::new(false);
+        let mut decoded = Vec::with_capacity(chunks.concat().len());
+        let mut boundaries = Vec::new();
+        loop {
+            let input = &compressed[d.total_in() as usize..];
+            let status = d
+                .decompress_vec(input, &mut decoded, FlushDecompress::Block)
+                .unwrap();
+            let position = d.position();
+            if status == Status::StreamEnd {
+                assert_eq!(position.is_last_block(), reported);
+                assert!(!position.is_block_boundary());
+                break;
+            }
+            assert_eq!(position.is_block_boundary(), reported);
+            assert!(position.unused_bits() < 8);
+            boundaries.push(d.total_out());
+        }
+        assert_eq!(decoded, chunks.concat());
+        assert!(ends.iter().all(|end| boundaries.contains(end)));
+
+        // Stop right after the header of the first block.
+        #[cfg(feature = "any_c_zlib")]
+        {
+            let mut d = Decompress::new(false);
+            d.decompress_vec(&compressed, &mut decoded, FlushDecompress::Trees)
+                .unwrap();
+            assert!(d.position().is_after_block_header());
+            assert_eq!(d.total_out(), 0);
+        }
+    }
+
+    // Not supported by the stable API of zlib-rs.
+    #[cfg(any(feature = "any_c_zlib", not(feature = "zlib-rs")))]
+    #[test]
+    fn prime() {
+        let data = b"hello hello prime".repeat(100);
+        let mut c = Compress::new(Compression::default(), false);
+        let mut plain = Vec::with_capacity(c.bound(data.len()));
+        c.compress_vec(&data, &mut plain, FlushCompress::Finish)
+            .unwrap();
+
+        for bits in 1..8 {
+            let value = 0b0101_0101 & ((1 << bits) - 1);
+            let mut c = Compress::new(Compression::default(), false);
+            let compressed = if crate::backend_info().capabilities().prime() {
+                c.prime(bits, value).unwrap();
+                let mut compressed = Vec::with_capacity(c.bound(data.len()) + 8);
+                let status = c
+                    .compress_vec(&data, &mut compressed, FlushCompress::Finish)
+                    .unwrap();
+                assert_eq!(status, Status::StreamEnd);
+                compressed
+            } else {
+                let err = c.prime(bits, value).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::Unsupported);
+                // Insert the bits ahead of the stream by hand.
+                let mut compressed = Vec::with_capacity(plain.len() + 1);
+                let mut acc = u32::from(value);
+                for &byte in &plain {
+                    acc |= u32::from(byte) << bits;
+                    compressed.push(acc as u8);
+                    acc >>= 8;
+                }
+                compressed.push(acc as u8);
+                compressed
+            };
+            assert_eq!(u16::from(compressed[0]) & ((1 << bits) - 1), value);
+
+            // Resume in the middle of the first byte, right after the primed bits.
+            let mut d = Decompress::new(false);
+            d.prime(8 - bits, u16::from(compressed[0] >> bits)).unwrap();
+            let mut decoded = Vec::with_capacity(data.len());
+            let status = d
+                .decompress_vec(&compressed[1..], &mut decoded, FlushDecompress::Finish)
+                .unwrap();
+            assert_eq!(status, Status::StreamEnd);
+            assert_eq!(decoded, data);
+        }
+
+        let mut c = Compress::new(Compression::default(), false);
+        assert!(c.prime(17, 0).is_err());
+        let mut d = Decompress::new(false);
+        assert!(d.prime(17, 0).is_err());
+
+        // miniz_oxide can only prime the decompressor at block boundaries.
+        if cfg!(not(feature = "any_zlib")) {
+            let mut d = Decompress::new(false);
+            let mut decoded = Vec::with_capacity(data.len());
+            d.decompress_vec(&plain[..10], &mut decoded, FlushDecompress::None)
+                .unwrap();
+            let err = d.prime(3, 0).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+        }
+    }
+
+    // Not supported by the stable API of zlib-rs.
+    #[cfg(any(feature = "any_c_zlib", not(feature = "zlib-rs")))]
+    #[test]
+    fn prime_resume_at_block_boundary() {
+        let first = b"the first block ".repeat(50);
+        let second = b"and the second one ".repeat(50);
+
+        // Without string matching the second block doesn't refer to the
+        // first one, so it can be decoded on its own.
+        let mut c = Compress::builder()
+            .format(Format::Deflate)
+            .strategy(Strategy::HuffmanOnly)
+            .build()
+            .unwrap();
+        let mut compressed = Vec::with_capacity(c.bound(first.len() + second.len()) * 2);
+        c.compress_vec(&first, &mut compressed, FlushCompress::Block)
+            .unwrap();
+        c.compress_vec(&second, &mut compressed, FlushCompress::Finish)
+            .unwrap();
+
+        let mut d = Decompress::new(false);
+        let mut decoded = Vec::with_capacity(first.len() + second.len());
+        while d.total_out() < first.len() as u64 {
+            let input = &compressed[d.total_in() as usize..];
+            d.decompress_vec(input, &mut decoded, FlushDecompress::Block)
+                .unwrap();
+        }
+        assert!(d.position().is_block_boundary());
+        let offset = d.total_in() as usize;
+        let bits = d.position().unused_bits();
+
+        let mut d = Decompress::new(false);
+        if bits > 0 {
+            d.prime(bits, u16::from(compressed[offset - 1] >> (8 - bits)))
+                .unwrap();
+        }
+        decoded.clear();
+        let status = d
+            .decompress_vec(&compressed[offset..], &mut decoded, FlushDecompress::Finish)
+            .unwrap();
+        assert_eq!(status, Status::StreamEnd);
+        assert_eq!(decoded, second);
+    }
+
+    #[test]
+    fn get_dictionary() {
+        let data = (0..20_000).map(|i| i.to_string()).collect::<String>();
+        let data = data.as_bytes();
+        let window = &data[data.len() - 32 * 1024..];
+
+        let mut c = Compress::builder()
+            .format(Format::Deflate)
+            .keep_dictionary(true)
+            .build()
+            .unwrap();
+        assert!(c.get_dictionary().is_empty());
+        let mut compressed = Vec::with_capacity(c.bound(data.len()));
+        c.compress_vec(&data[..100], &mut compressed, FlushCompress::None)
+            .unwrap();
+        assert_eq!(c.get_dictionary(), &data[..100]);
+        c.compress_vec(&data[100..], &mut compressed, FlushCompress::Finish)
+            .unwrap();
+        let dictionary = c.get_dictionary();
+        assert!(!dictionary.is_empty() && dictionary.len() <= window.len());
+        assert!(data.ends_with(&dictionary));
+
+        // In a single call, and in small steps.
+        let mut d = Decompress::new(false);
+        assert!(d.get_dictionary().is_empty());
+        let mut decoded = Vec::with_capacity(data.len());
+        d.decompress_vec(&compressed, &mut decoded, FlushDecompress::None)
+            .unwrap();
+        assert_eq!(d.get_dictionary(), window);
+
+        let mut d = Decompress::new(false);
+        let mut decoded = Vec::with_capacity(data.len());
+        for chunk in compressed.chunks(1000) {
+            d.decompress_vec(chunk, &mut decoded, FlushDecompress::None)
+                .unwrap();
+            let len = decoded.len().min(window.len());
+            assert_eq!(d.get_dictionary(), &decoded[decoded.len() - len..]);
+        }
+        assert_eq!(decoded, data);
+
+        // The Rust backends only keep the dictionary of a compressor on
+        // request.
+        if cfg!(not(feature = "any_c_zlib")) {
+            let mut c = Compress::new(Compression::default(), false);
+            c.compress_vec(&data[..100], &mut compressed, FlushCompress::None)
+                .unwrap();
+            assert!(c.get_dictionary().is_empty());
+        }
+    }
+
+    #[test]
+    fn set_dictionary_round_trip() {
+        let dictionary = (0..10_000).map(|i| i.to_string()).collect::<String>();
+        let dictionary = dictionary.as_bytes();
+        let data = &dictionary[dictionary.len() - 20_000..];
+
+        for zlib_header in [true, false] {
+            let mut c = Compress::builder()
+                .format(format_from_bool(zlib_header))
+                .keep_dictionary(true)
+                .build()
+                .unwrap();
+            for _ in 0..2 {
+                let adler = c.set_dictionary(dictionary).unwrap();
+                #[cfg(any(feature = "any_c_zlib", not(feature = "zlib-rs")))]
+                assert_eq!(
+                    c.get_dictionary(),
+                    &dictionary[dictionary.len() - 32 * 1024..]
+                );
+
+                // Small output buffers, so the header and trailer are split.
+                let mut compressed = Vec::new();
+                loop {
+                    compressed.reserve(3);
+                    let input = &data[c.total_in() as usize..];
+                    if c.compress_vec(input, &mut compressed, FlushCompress::Finish)
+                        .unwrap()
+                        == Status::StreamEnd
+                    {
+                        break;
+                    }
+                }
+                assert_eq!(c.total_out(), compressed.len() as u64);
+                assert!(compressed.len() < 1000);
+                c.reset();
+
+                let mut d = Decompress::new(zlib_header);
+                let mut decoded = Vec::with_capacity(data.len());
+                if zlib_header {
+                    let err = d
+                        .decompress_vec(&compressed, &mut decoded, FlushDecompress::Finish)
+                        .unwrap_err();
+                    assert_eq!(err.needs_dictionary(), Some(adler));
+                    assert!(d.set_dictionary(b"wrong").is_err());
+                    assert_eq!(d.set_dictionary(dictionary).unwrap(), adler);
+                } else {
+                    d.set_dictionary(dictionary).unwrap();
+                }
+                let input = &compressed[d.total_in() as usize..];
+                let status = d
+                    .decompress_vec(input, &mut decoded, FlushDecompress::Finish)
+                    .unwrap();
+                assert_eq!(status, Status::StreamEnd);
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
     fn compress_with_flush(flush: FlushCompress) -> Vec<u8> {
         let incompressible = (0..=255).collect::<Vec<u8>>();
         let mut output = vec![0; 1024];
@@ -823,4 +2830,80 @@ mod tests {
         // Check that the second instance of incompressible input was also written uncompressed.
         assert_eq!(&output[266..][..5], &[1, 0, 1, 0xff, !1]);
     }
+
+    #[test]
+    fn error_kinds() {
+        let mut encoder = write::ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[7; 1000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut output = vec![0; 2000];
+
+        // zlib only tells the cause of invalid data in its message.
+        let detailed = |kind| {
+            if cfg!(feature = "any_zlib") {
+                ErrorKind::InvalidData
+            } else {
+                kind
+            }
+        };
+
+        let mut d = Decompress::new(true);
+        let err = d
+            .decompress(&[0x78, 0x00], &mut output, FlushDecompress::None)
+            .unwrap_err();
+        assert_eq!(err.kind(), detailed(ErrorKind::InvalidHeader));
+        assert_eq!(err.input_offset(), Some(d.total_in()));
+
+        let mut corrupt = compressed.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        let mut d = Decompress::new(true);
+        let err = d
+            .decompress(&corrupt, &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert_eq!(err.kind(), detailed(ErrorKind::ChecksumMismatch));
+        assert_eq!(err.output_offset(), Some(1000));
+
+        // A final block of the reserved type.
+        let mut d = Decompress::new(false);
+        let err = d
+            .decompress(&[0x07], &mut output, FlushDecompress::Finish)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::InvalidBlockType | ErrorKind::InvalidData
+        ));
+
+        let mut d = Decompress::new(false);
+        let err = d.prime(17, 0).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidParameter);
+        assert_eq!(err.input_offset(), None);
+    }
+
+    #[test]
+    fn error_kinds_through_io() {
+        let mut encoder = write::DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[7; 1000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let truncated = &compressed[..compressed.len() - 1];
+        let err = read::DeflateDecoder::new(truncated)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        let inner = err.get_ref().unwrap();
+        let inner = inner.downcast_ref::<DecompressError>().unwrap();
+        assert_eq!(inner.kind(), ErrorKind::TruncatedInput);
+        assert_eq!(inner.input_offset(), Some(truncated.len() as u64));
+
+        let mut decoder = write::ZlibDecoder::new(Vec::new());
+        let err = decoder.write_all(&[0x78, 0x00]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        let inner = err.get_ref().unwrap();
+        let inner = inner.downcast_ref::<DecompressError>().unwrap();
+        if cfg!(feature = "any_zlib") {
+            assert_eq!(inner.kind(), ErrorKind::InvalidData);
+        } else {
+            assert_eq!(inner.kind(), ErrorKind::InvalidHeader);
+        }
+    }
 }
@@ -0,0 +1,368 @@
+//! Functions which compress or decompress a whole slice at once.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use crate::mem::{CompressError, DecompressError, ErrorKind};
+use crate::{Compress, Compression, Decompress, DecompressLimits, FlushCompress, FlushDecompress};
+use crate::{Format, Status};
+
+/// The largest factor by which deflate can expand data.
+pub(crate) const MAX_RATIO: u64 = 1032;
+
+/// Compresses `data` into a new vector in the given `format`.
+///
+/// The output is allocated once, sized with the bound on the compressed size.
+///
+/// # Examples
+///
+/// ```
+/// use flate2::{compress_to_vec, decompress_to_vec, Compression, DecompressLimits, Format};
+///
+/// let data = b"hello world, hello world";
+/// let compressed = compress_to_vec(data, Format::Gzip, Compression::best()).unwrap();
+/// let decompressed =
+///     decompress_to_vec(&compressed, Format::Gzip, DecompressLimits::new()).unwrap();
+/// assert_eq!(decompressed, data);
+/// ```
+pub fn compress_to_vec(
+    data: &[u8],
+    format: Format,
+    level: Compression,
+) -> Result<Vec<u8>, CompressError> {
+    let mut output = Vec::new();
+    compress_into_vec(data, format, level, &mut output)?;
+    Ok(output)
+}
+
+/// Compresses `data` in the given `format`, appending the result to `output`.
+///
+/// Returns the number of bytes appended.
+pub fn compress_into_vec(
+    data: &[u8],
+    format: Format,
+    level: Compression,
+    output: &mut Vec<u8>,
+) -> Result<usize, CompressError> {
+    let mut compress = Compress::builder().level(level).format(format).build()?;
+    output.reserve(compress.bound(data.len()));
+    let start = output.len();
+    // The bound is large enough to compress everything in one call, but
+    // should it fall short the output grows like in `decompress_into_vec`.
+    loop {
+        let consumed = compress.total_in() as usize;
+        let status = compress.compress_vec(&data[consumed..], output, FlushCompress::Finish)?;
+        if status == Status::StreamEnd {
+            return Ok(output.len() - start);
+        }
+        output.reserve(cmp::max(output.len() - start, 32 * 1024));
+    }
+}
+
+/// Compresses `data` in the given `format` into `output`.
+///
+/// Returns the number of bytes written, or an error of kind
+/// [`ErrorKind::BufferTooSmall`] if they don't fit. An `output` of
+/// [`Compress::bound`] or [`compress_bound`](crate::compress_bound) bytes is
+/// always large enough.
+pub fn compress_into_slice(
+    data: &[u8],
+    format: Format,
+    level: Compression,
+    output: &mut [u8],
+) -> Result<usize, CompressError> {
+    let mut compress = Compress::builder().level(level).format(format).build()?;
+    let status = compress.compress(data, output, FlushCompress::Finish)?;
+    if status != Status::StreamEnd {
+        let err = CompressError::new(
+            ErrorKind::BufferTooSmall,
+            Some("output buffer is too small"),
+        );
+        return Err(err.at(compress.total_in(), compress.total_out()));
+    }
+    Ok(compress.total_out() as usize)
+}
+
+/// Decompresses `data` in the given `format` into a new vector, within the
+/// given `limits`.
+///
+/// Gzip data may consist of multiple members, which are decompressed one
+/// after the other like [`MultiGzDecoder`](crate::read::MultiGzDecoder) does.
+/// Any data after the end of a deflate or zlib stream is an error of kind
+/// [`ErrorKind::StreamFinished`], and data which ends early an error of kind
+/// [`ErrorKind::TruncatedInput`].
+///
+/// The output is sized from the uncompressed size in the trailer of gzip data,
+/// if that is plausible, and grows as needed otherwise.
+pub fn decompress_to_vec(
+    data: &[u8],
+    format: Format,
+    limits: DecompressLimits,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut output = Vec::new();
+    decompress_into_vec(data, format, limits, &mut output)?;
+    Ok(output)
+}
+
+/// Decompresses `data` in the given `format` within the given `limits`,
+/// appending the result to `output`.
+///
+/// Returns the number of bytes appended. See [`decompress_to_vec`] for
+/// details.
+pub fn decompress_into_vec(
+    data: &[u8],
+    format: Format,
+    limits: DecompressLimits,
+    output: &mut Vec<u8>,
+) -> Result<usize, DecompressError> {
+    let mut decompress = Decompress::builder().format(format).build()?;
+    decompress.set_limits(limits);
+    let start = output.len();
+    output.reserve(size_hint(data, format, limits));
+
+    let mut input = data;
+    let mut members = 1;
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(cmp::max(output.len() - start, 32 * 1024));
+        }
+        let before = decompress.total_in();
+        let status = decompress.decompress_vec(input, output, FlushDecompress::None)?;
+        input = &input[(decompress.total_in() - before) as usize..];
+
+        if status == Status::StreamEnd {
+            if input.is_empty() {
+                return Ok(output.len() - start);
+            }
+            next_stream(&mut decompress, format, &limits, &mut members)?;
+        } else if input.is_empty() && output.len() < output.capacity() {
+            return Err(DecompressError::truncated(
+                decompress.total_in(),
+                decompress.total_out(),
+            ));
+        }
+    }
+}
+
+/// Decompresses `data` in the given `format` into `output`.
+///
+/// Returns the number of bytes written, or an error of kind
+/// [`ErrorKind::BufferTooSmall`] if they don't fit. See
+/// [`decompress_to_vec`] for details.
+pub fn decompress_into_slice(
+    data: &[u8],
+    format: Format,
+    output: &mut [u8],
+) -> Result<usize, DecompressError> {
+    let mut decompress = Decompress::builder().format(format).build()?;
+    let limits = DecompressLimits::new();
+
+    let mut input = data;
+    let mut written = 0;
+    let mut members = 1;
+    loop {
+        let (before_in, before_out) = (decompress.total_in(), decompress.total_out());
+        let status = decompress.decompress(input, &mut output[written..], FlushDecompress::None)?;
+        input = &input[(decompress.total_in() - before_in) as usize..];
+        written += (decompress.total_out() - before_out) as usize;
+
+        if status == Status::StreamEnd {
+            if input.is_empty() {
+                return Ok(written);
+            }
+            next_stream(&mut decompress, format, &limits, &mut members)?;
+        } else if input.is_empty() && !has_more_output(&mut decompress, written == output.len())? {
+            return Err(DecompressError::truncated(
+                decompress.total_in(),
+                decompress.total_out(),
+            ));
+        } else if written == output.len() {
+            let err = DecompressError::new(
+                ErrorKind::BufferTooSmall,
+                Some("output buffer is too small"),
+            );
+            return Err(err.at(decompress.total_in(), decompress.total_out()));
+        }
+    }
+}
+
+/// Returns whether `decompress` holds back output after all input was passed
+/// in, which it can only do if the output buffer is `full`.
+fn has_more_output(decompress: &mut Decompress, full: bool) -> Result<bool, DecompressError> {
+    if !full {
+        return Ok(false);
+    }
+    let before = decompress.total_out();
+    decompress.decompress(&[], &mut [0], FlushDecompress::None)?;
+    Ok(decompress.total_out() > before)
+}
+
+/// Prepares `decompress` for the data after the end of a stream, which is
+/// only allowed for the next member of gzip data.
+fn next_stream(
+    decompress: &mut Decompress,
+    format: Format,
+    limits: &DecompressLimits,
+    members: &mut u64,
+) -> Result<(), DecompressError> {
+    if format != Format::Gzip {
+        let err = DecompressError::new(
+            ErrorKind::StreamFinished,
+            Some("unexpected data after the end of the stream"),
+        );
+        return Err(err.at(decompress.total_in(), decompress.total_out()));
+    }
+    limits.check_members(*members)?;
+    *members += 1;
+    decompress.next_stream(Format::Gzip);
+    Ok(())
+}
+
+/// Guesses the size of the decompressed `data`, without trusting the gzip
+/// trailer beyond what the data could possibly expand to.
+fn size_hint(data: &[u8], format: Format, limits: DecompressLimits) -> usize {
+    let len = data.len() as u64;
+    let guess = match format {
+        Format::Gzip if data.len() >= 18 => {
+            let trailer = <[u8; 4]>::try_from(&data[data.len() - 4..]).unwrap();
+            u64::from(u32::from_le_bytes(trailer))
+        }
+        _ => len.saturating_mul(4),
+    };
+    let mut hint = guess.min(len.saturating_mul(MAX_RATIO));
+    if let Some(max) = limits.get_max_output() {
+        hint = hint.min(max.saturating_add(1));
+    }
+    usize::try_from(hint).unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [Format; 3] = [Format::Deflate, Format::Zlib, Format::Gzip];
+
+    #[test]
+    fn round_trip() {
+        let data = b"hello one-shot world! ".repeat(5000);
+        for format in FORMATS {
+            let compressed = compress_to_vec(&data, format, Compression::default()).unwrap();
+            let limits = DecompressLimits::new();
+            assert_eq!(
+                decompress_to_vec(&compressed, format, limits).unwrap(),
+                data
+            );
+
+            let mut output = b"prefix".to_vec();
+            let n = decompress_into_vec(&compressed, format, limits, &mut output).unwrap();
+            assert_eq!(n, data.len());
+            assert_eq!(&output[..6], b"prefix");
+            assert_eq!(&output[6..], &data[..]);
+
+            let mut slice = vec![0; data.len()];
+            let n = decompress_into_slice(&compressed, format, &mut slice).unwrap();
+            assert_eq!(n, data.len());
+            assert_eq!(slice, data);
+
+            let mut output = b"prefix".to_vec();
+            let n = compress_into_vec(&data, format, Compression::best(), &mut output).unwrap();
+            assert_eq!(n, output.len() - 6);
+            assert_eq!(&output[..6], b"prefix");
+            assert_eq!(
+                decompress_to_vec(&output[6..], format, limits).unwrap(),
+                data
+            );
+
+            let mut output = vec![0; crate::compress_bound(data.len(), format)];
+            let n = compress_into_slice(&data, format, Compression::fast(), &mut output).unwrap();
+            assert_eq!(
+                decompress_to_vec(&output[..n], format, limits).unwrap(),
+                data
+            );
+        }
+    }
+
+    #[test]
+    fn empty() {
+        for format in FORMATS {
+            let compressed = compress_to_vec(&[], format, Compression::default()).unwrap();
+            let output = decompress_to_vec(&compressed, format, DecompressLimits::new()).unwrap();
+            assert!(output.is_empty());
+        }
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let data = crate::random_bytes().take(10_000).collect::<Vec<_>>();
+        for format in FORMATS {
+            let mut small = [0; 100];
+            let err = compress_into_slice(&data, format, Compression::best(), &mut small);
+            assert_eq!(err.unwrap_err().kind(), ErrorKind::BufferTooSmall);
+
+            let compressed = compress_to_vec(&data, format, Compression::best()).unwrap();
+            let mut small = vec![0; data.len() - 1];
+            let err = decompress_into_slice(&compressed, format, &mut small).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        }
+    }
+
+    #[test]
+    fn truncated_and_trailing_data() {
+        let data = b"hello world".repeat(100);
+        for format in FORMATS {
+            let compressed = compress_to_vec(&data, format, Compression::default()).unwrap();
+            let limits = DecompressLimits::new();
+
+            let truncated = &compressed[..compressed.len() - 1];
+            let err = decompress_to_vec(truncated, format, limits).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+            let mut output = vec![0; data.len()];
+            let err = decompress_into_slice(truncated, format, &mut output).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::TruncatedInput);
+
+            if format != Format::Gzip {
+                let mut trailing = compressed.clone();
+                trailing.push(0);
+                let err = decompress_to_vec(&trailing, format, limits).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::StreamFinished);
+            }
+        }
+    }
+
+    #[test]
+    fn gzip_members() {
+        let member = compress_to_vec(b"member", Format::Gzip, Compression::default()).unwrap();
+        let multi = member.repeat(3);
+
+        let output = decompress_to_vec(&multi, Format::Gzip, DecompressLimits::new()).unwrap();
+        assert_eq!(output, b"member".repeat(3));
+        let mut slice = [0; 18];
+        assert_eq!(
+            decompress_into_slice(&multi, Format::Gzip, &mut slice).unwrap(),
+            18
+        );
+
+        let limits = DecompressLimits::new().max_members(2);
+        let err = decompress_to_vec(&multi, Format::Gzip, limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        let limits = DecompressLimits::new().max_output(17);
+        let err = decompress_to_vec(&multi, Format::Gzip, limits).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn implausible_isize() {
+        let mut compressed =
+            compress_to_vec(b"hello", Format::Gzip, Compression::default()).unwrap();
+        let len = compressed.len();
+        compressed[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(size_hint(&compressed, Format::Gzip, DecompressLimits::new()) < 100_000);
+        let err = decompress_to_vec(&compressed, Format::Gzip, DecompressLimits::new());
+        let expected = if cfg!(feature = "any_zlib") {
+            ErrorKind::InvalidData
+        } else {
+            ErrorKind::ChecksumMismatch
+        };
+        assert_eq!(err.unwrap_err().kind(), expected);
+    }
+}
@@ -0,0 +1,204 @@
+//! Streams which compress or decompress on multiple threads.
+
+use std::io;
+use std::io::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use self::read::ParMultiGzDecoder;
+use self::write::{ParDeflateEncoder, ParGzEncoder, ParZlibEncoder};
+use crate::mem::{CompressError, ErrorKind};
+use crate::{bgzf, Compress, Compression};
+
+pub mod read;
+pub mod write;
+
+/// The size of the deflate window, which is all a block can refer back to.
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// A builder for the parallel encoders and decoders, configuring the number of
+/// threads and the size of the blocks the encoders compress.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::read::GzDecoder;
+/// use flate2::{Compression, ParBuilder};
+///
+/// let data = b"hello world\n".repeat(100_000);
+/// let mut e = ParBuilder::new()
+///     .threads(4)
+///     .block_size(64 * 1024)
+///     .write_gz(Vec::new(), Compression::default());
+/// e.write_all(&data).unwrap();
+/// let compressed = e.finish().unwrap();
+///
+/// let mut decompressed = Vec::new();
+/// GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+/// assert_eq!(decompressed, data);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ParBuilder {
+    threads: usize,
+    block_size: usize,
+}
+
+impl ParBuilder {
+    /// Creates a builder with the default configuration: one thread per CPU
+    /// and blocks of 128 KiB.
+    pub fn new() -> ParBuilder {
+        ParBuilder {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            block_size: 128 * 1024,
+        }
+    }
+
+    /// Sets the number of threads which compress blocks or decompress
+    /// members.
+    ///
+    /// The threads are started with the first block or read, and stopped once
+    /// the encoder is finished or the stream is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    pub fn threads(mut self, threads: usize) -> ParBuilder {
+        assert!(threads > 0, "at least one thread is needed");
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the number of uncompressed bytes in each block.
+    ///
+    /// Larger blocks compress slightly better, since each block ends with a
+    /// sync flush, at the cost of holding more data in memory: up to twice
+    /// the number of threads blocks are in flight at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is smaller than 32 KiB.
+    pub fn block_size(mut self, block_size: usize) -> ParBuilder {
+        assert!(
+            block_size >= WINDOW_SIZE,
+            "the block size must be at least 32 KiB"
+        );
+        self.block_size = block_size;
+        self
+    }
+
+    /// Creates a gzip encoder which writes to `w`, with the same header as
+    /// [`GzEncoder::new`](crate::write::GzEncoder::new).
+    pub fn write_gz<W: Write>(self, w: W, level: Compression) -> ParGzEncoder<W> {
+        write::gz_encoder(w, level, self)
+    }
+
+    /// Creates a zlib encoder which writes to `w`.
+    pub fn write_zlib<W: Write>(self, w: W, level: Compression) -> ParZlibEncoder<W> {
+        write::zlib_encoder(w, level, self)
+    }
+
+    /// Creates a raw deflate encoder which writes to `w`.
+    pub fn write_deflate<W: Write>(self, w: W, level: Compression) -> ParDeflateEncoder<W> {
+        write::deflate_encoder(w, level, self)
+    }
+
+    /// Creates a [BGZF](crate::bgzf) writer which writes to `w`.
+    ///
+    /// The blocks of BGZF have a fixed size, so the block size of the builder
+    /// doesn't apply.
+    pub fn write_bgzf<W: Write>(self, w: W, level: Compression) -> bgzf::Writer<W> {
+        bgzf::writer(w, level, self.threads)
+    }
+
+    /// Creates a decoder for gzip data with any number of members, which
+    /// reads from `r`.
+    pub fn read_multi_gz<R: Read>(self, r: R) -> ParMultiGzDecoder<R> {
+        read::multi_gz_decoder(r, self)
+    }
+}
+
+impl Default for ParBuilder {
+    fn default() -> ParBuilder {
+        ParBuilder::new()
+    }
+}
+
+/// Compression threads, which take jobs from a shared queue and send back the
+/// results in the order they finish.
+pub(crate) struct Pool<J, B> {
+    jobs: Option<mpsc::Sender<J>>,
+    results: mpsc::Receiver<Result<B, CompressError>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static, B: Send + 'static> Pool<J, B> {
+    /// Starts `threads` threads, each running `work` with a raw deflate
+    /// compressor of the given level.
+    pub(crate) fn new<F>(threads: usize, level: Compression, work: F) -> Pool<J, B>
+    where
+        F: Fn(&mut Compress, J) -> Result<B, CompressError> + Clone + Send + 'static,
+    {
+        let (jobs, queue) = mpsc::channel::<J>();
+        let (done, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads)
+            .map(|_| {
+                let queue = queue.clone();
+                let done = done.clone();
+                let work = work.clone();
+                thread::spawn(move || {
+                    let mut compress = Compress::new(level, false);
+                    loop {
+                        // Only one idle worker waits for the next job at a time.
+                        let job = match queue.lock().map(|queue| queue.recv()) {
+                            Ok(Ok(job)) => job,
+                            _ => return,
+                        };
+                        let res =
+                            panic::catch_unwind(AssertUnwindSafe(|| work(&mut compress, job)));
+                        let res = res.unwrap_or_else(|_| {
+                            Err(CompressError::new(
+                                ErrorKind::Other,
+                                Some("compression thread panicked"),
+                            ))
+                        });
+                        if done.send(res).is_err() {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+        Pool {
+            jobs: Some(jobs),
+            results,
+            workers,
+        }
+    }
+
+    pub(crate) fn send(&self, job: J) -> io::Result<()> {
+        self.jobs.as_ref().unwrap().send(job).map_err(|_| stopped())
+    }
+
+    /// Waits for the next result of a job. The job of an error never
+    /// finishes, so the pool can't be waited for anymore after one.
+    pub(crate) fn recv(&self) -> io::Result<Result<B, CompressError>> {
+        self.results.recv().map_err(|_| stopped())
+    }
+}
+
+impl<J, B> Drop for Pool<J, B> {
+    fn drop(&mut self) {
+        // Closing the queue stops the workers once they finished their jobs.
+        drop(self.jobs.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "compression threads stopped")
+}
@@ -0,0 +1,772 @@
+//! A decoder which decompresses the members of a gzip file on multiple
+//! threads.
+//!
+//! The members of a gzip file are independent of each other, so each one can
+//! be decompressed on its own thread once its start is known. The start of a
+//! member is only known for sure once the previous one was decompressed, so
+//! the input is scanned for anything that looks like a gzip header and a job
+//! is started at each of them. The job at the end of the current member is
+//! the next one, while the jobs in between only found data which looked like
+//! a header and are cancelled. Each member still checks its own CRC and size,
+//! just like with [`MultiGzDecoder`](crate::read::MultiGzDecoder).
+
+use std::cmp;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use super::ParBuilder;
+use crate::mem::{DecompressError, DecompressLimits, ErrorKind};
+use crate::{Decompress, FlushDecompress, Status};
+
+/// The most input read at once.
+const INPUT_CHUNK: usize = 256 * 1024;
+
+/// The input read ahead of the current member, for each thread.
+const READ_AHEAD: u64 = 4 * 1024 * 1024;
+
+/// The size of the output chunks the jobs produce.
+const OUTPUT_CHUNK: usize = 64 * 1024;
+
+/// The most output buffered by a single job.
+const JOB_OUTPUT: usize = 2 * 1024 * 1024;
+
+pub fn multi_gz_decoder<R: Read>(r: R, config: ParBuilder) -> ParMultiGzDecoder<R> {
+    let mut jobs = BTreeMap::new();
+    jobs.insert(0, Job::new(0));
+    let state = State {
+        input: VecDeque::new(),
+        input_end: 0,
+        eof: false,
+        scan: 0,
+        current: 0,
+        jobs,
+        buffered: 0,
+        max_buffered: config.threads * JOB_OUTPUT,
+        max_jobs: config.threads * 4,
+        limits: DecompressLimits::new(),
+        shutdown: false,
+    };
+    ParMultiGzDecoder {
+        inner: r,
+        workers: Workers {
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                cond: Condvar::new(),
+            }),
+            handles: Vec::new(),
+        },
+        threads: config.threads,
+        read_ahead: config.threads as u64 * READ_AHEAD,
+        total_out: 0,
+        members: 1,
+        next_member: false,
+        done: false,
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+struct State {
+    /// The input which is still needed, from the position of the earliest
+    /// job on, each chunk with its offset in the input.
+    input: VecDeque<(u64, Arc<Vec<u8>>)>,
+    input_end: u64,
+    eof: bool,
+    /// Where to continue looking for gzip headers.
+    scan: u64,
+    /// The offset of the member currently being read.
+    current: u64,
+    /// The jobs by the offset they start at, the current member being the
+    /// first one.
+    jobs: BTreeMap<u64, Job>,
+    /// The output buffered by all jobs.
+    buffered: usize,
+    /// The most output buffered before the jobs other than the current one
+    /// stop.
+    max_buffered: usize,
+    /// The most jobs started at once.
+    max_jobs: usize,
+    limits: DecompressLimits,
+    shutdown: bool,
+}
+
+impl State {
+    /// Returns the chunk of input containing `pos`, and the offset of `pos`
+    /// in it.
+    fn input_at(&self, pos: u64) -> Option<(&Arc<Vec<u8>>, usize)> {
+        let i = self.input.partition_point(|(start, _)| *start <= pos);
+        let (start, chunk) = self.input.get(i.checked_sub(1)?)?;
+        let offset = (pos - start) as usize;
+        (offset < chunk.len()).then_some((chunk, offset))
+    }
+
+    fn byte_at(&self, pos: u64) -> Option<u8> {
+        self.input_at(pos).map(|(chunk, offset)| chunk[offset])
+    }
+
+    /// Returns the offset of the earliest input which is still held.
+    fn input_start(&self) -> u64 {
+        self.input
+            .front()
+            .map_or(self.input_end, |(start, _)| *start)
+    }
+
+    /// Drops the input before the position of every job, which a member
+    /// only gets too long to hold once it's decompressed. A failed job needs
+    /// no more input, and a finished one keeps the start of the next member.
+    fn drop_input(&mut self) {
+        let needed = self
+            .jobs
+            .values()
+            .filter(|job| !matches!(job.run, Run::Done(Err(_))))
+            .fold(self.scan, |needed, job| cmp::min(needed, job.pos));
+        while let Some((start, chunk)) = self.input.front() {
+            if start + chunk.len() as u64 > needed {
+                break;
+            }
+            self.input.pop_front();
+        }
+    }
+
+    fn push_input(&mut self, chunk: Vec<u8>) {
+        if chunk.is_empty() {
+            self.eof = true;
+        } else {
+            let len = chunk.len() as u64;
+            self.input.push_back((self.input_end, Arc::new(chunk)));
+            self.input_end += len;
+        }
+        self.scan();
+        self.unpark();
+    }
+
+    /// Starts a job at each gzip header in the input which wasn't scanned
+    /// yet, as long as there aren't too many jobs.
+    fn scan(&mut self) {
+        self.scan = cmp::max(self.scan, self.current);
+        while self.jobs.len() < self.max_jobs {
+            let Some((chunk, offset)) = self.input_at(self.scan) else {
+                return;
+            };
+            let Some(i) = chunk[offset..].iter().position(|&b| b == 0x1f) else {
+                self.scan += (chunk.len() - offset) as u64;
+                continue;
+            };
+            let pos = self.scan + i as u64;
+            let mut header = [0; 4];
+            for (n, byte) in header.iter_mut().enumerate() {
+                match self.byte_at(pos + n as u64) {
+                    Some(b) => *byte = b,
+                    None if self.eof => {
+                        self.scan = self.input_end;
+                        return;
+                    }
+                    None => {
+                        self.scan = pos;
+                        return;
+                    }
+                }
+            }
+            // The magic bytes, the deflate method and no reserved flags.
+            if header[..3] == [0x1f, 0x8b, 8] && header[3] & 0xe0 == 0 {
+                self.jobs.entry(pos).or_insert_with(|| Job::new(pos));
+            }
+            self.scan = pos + 1;
+        }
+    }
+
+    /// Moves on to the member at `end`, after the current one finished.
+    fn advance(&mut self, end: u64) {
+        let cancelled = self.jobs.range(self.current..end).map(|(&start, _)| start);
+        for start in cancelled.collect::<Vec<_>>() {
+            let job = self.jobs.remove(&start).unwrap();
+            self.buffered -= job.buffered;
+        }
+        self.current = end;
+        self.jobs.entry(end).or_insert_with(|| Job::new(end));
+        self.scan();
+        self.drop_input();
+        self.unpark();
+    }
+
+    /// Lets the parked jobs check again whether they can go on.
+    fn unpark(&mut self) {
+        for job in self.jobs.values_mut() {
+            if let Run::Parked(_) = job.run {
+                match mem::replace(&mut job.run, Run::Running) {
+                    Run::Parked(progress) => job.run = Run::Ready(Some(progress)),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+/// The decompression of what may be a member, starting at its key in
+/// [`State::jobs`].
+struct Job {
+    run: Run,
+    /// The position in the input the job goes on at, or where the member
+    /// ends once it's finished.
+    pos: u64,
+    output: VecDeque<Vec<u8>>,
+    /// How much of the first output chunk was read.
+    read: usize,
+    /// The output which wasn't read yet.
+    buffered: usize,
+}
+
+impl Job {
+    fn new(pos: u64) -> Job {
+        Job {
+            run: Run::Ready(None),
+            pos,
+            output: VecDeque::new(),
+            read: 0,
+            buffered: 0,
+        }
+    }
+}
+
+enum Run {
+    /// Waiting for a thread, with the progress so far if it was parked.
+    Ready(Option<Progress>),
+    Running,
+    /// Waiting for more input, or for its output to be read.
+    Parked(Progress),
+    /// Finished at the end of the member, or with an error.
+    Done(io::Result<u64>),
+}
+
+struct Progress {
+    decompress: Decompress,
+    pos: u64,
+}
+
+/// The threads of a decoder, which are stopped when it's dropped.
+struct Workers {
+    shared: Arc<Shared>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    fn start(&mut self, threads: usize) {
+        self.handles = (0..threads)
+            .map(|_| {
+                let shared = self.shared.clone();
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.cond.notify_all();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Runs the jobs ready to run, those of the earliest members first, until the
+/// decoder is dropped.
+fn work(shared: &Shared) {
+    let mut state = shared.lock();
+    loop {
+        if state.shutdown {
+            return;
+        }
+        let ready = state
+            .jobs
+            .iter_mut()
+            .find(|(_, job)| matches!(job.run, Run::Ready(_)));
+        let Some((&start, job)) = ready else {
+            state = shared.cond.wait(state).unwrap();
+            continue;
+        };
+        let progress = match mem::replace(&mut job.run, Run::Running) {
+            Run::Ready(Some(progress)) => progress,
+            Run::Ready(None) => {
+                let mut decompress = Decompress::new_gzip(15);
+                decompress.set_limits(state.limits);
+                Progress {
+                    decompress,
+                    pos: start,
+                }
+            }
+            _ => unreachable!(),
+        };
+        state = run(shared, state, start, progress);
+    }
+}
+
+/// Runs the job at `start` until it finishes, has to wait, or is cancelled.
+fn run<'a>(
+    shared: &'a Shared,
+    mut state: MutexGuard<'a, State>,
+    start: u64,
+    mut progress: Progress,
+) -> MutexGuard<'a, State> {
+    loop {
+        state.drop_input();
+        let full = start != state.current && state.buffered >= state.max_buffered;
+        let input = state.input_at(progress.pos).map(|(c, o)| (c.clone(), o));
+        let eof = state.eof;
+        let Some(job) = state.jobs.get_mut(&start) else {
+            return state;
+        };
+        let (chunk, offset) = match input {
+            _ if full || job.buffered >= JOB_OUTPUT => {
+                job.run = Run::Parked(progress);
+                return state;
+            }
+            Some(input) => input,
+            None if eof => {
+                let d = &progress.decompress;
+                let err = DecompressError::truncated(d.total_in(), d.total_out());
+                job.run = Run::Done(Err(err.into()));
+                shared.cond.notify_all();
+                return state;
+            }
+            None => {
+                job.run = Run::Parked(progress);
+                shared.cond.notify_all();
+                return state;
+            }
+        };
+        drop(state);
+
+        let mut output = Vec::with_capacity(OUTPUT_CHUNK);
+        let before = progress.decompress.total_in();
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let input = &chunk[offset..];
+            progress
+                .decompress
+                .decompress_vec(input, &mut output, FlushDecompress::None)
+        }));
+        progress.pos += progress.decompress.total_in() - before;
+
+        state = shared.lock();
+        let State { jobs, buffered, .. } = &mut *state;
+        let Some(job) = jobs.get_mut(&start) else {
+            return state;
+        };
+        job.pos = progress.pos;
+        if !output.is_empty() {
+            job.buffered += output.len();
+            *buffered += output.len();
+            job.output.push_back(output);
+            shared.cond.notify_all();
+        }
+        let end = match res {
+            Ok(Ok(Status::StreamEnd)) => Ok(progress.pos),
+            Ok(Ok(_)) => continue,
+            Ok(Err(err)) => Err(err.into()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "decompression thread panicked",
+            )),
+        };
+        job.run = Run::Done(end);
+        shared.cond.notify_all();
+        return state;
+    }
+}
+
+/// A gzip decoder which decompresses the members of a [gzip file] on multiple
+/// threads.
+///
+/// This structure exposes a [`Read`] interface like
+/// [`read::MultiGzDecoder`](crate::read::MultiGzDecoder), and yields the same
+/// output. It only speeds up data with many members, as written for instance
+/// by `pigz --independent` or by concatenating gzip files, since each member
+/// is decompressed on a single thread. Each member must be a complete gzip
+/// stream, whose CRC and size are checked, and any data after the last one
+/// is an error.
+///
+/// The input is read ahead by up to 4 MiB for each thread, and at most 2 MiB
+/// of output are buffered for each thread. See [`ParBuilder`] to configure
+/// the threads, which are started with the first read. Like the other `Par`
+/// types, this decoder needs the `parallel` feature.
+///
+/// [gzip file]: https://www.rfc-editor.org/rfc/rfc1952#page-5
+///
+/// # Examples
+///
+/// ```
+/// use std::io::prelude::*;
+/// use flate2::read::ParMultiGzDecoder;
+/// use flate2::write::GzEncoder;
+/// use flate2::Compression;
+///
+/// let mut compressed = Vec::new();
+/// for part in ["Hello", " World"] {
+///     let mut e = GzEncoder::new(Vec::new(), Compression::default());
+///     e.write_all(part.as_bytes()).unwrap();
+///     compressed.extend(e.finish().unwrap());
+/// }
+///
+/// let mut s = String::new();
+/// ParMultiGzDecoder::new(&compressed[..]).read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "Hello World");
+/// ```
+pub struct ParMultiGzDecoder<R> {
+    inner: R,
+    workers: Workers,
+    threads: usize,
+    read_ahead: u64,
+    total_out: u64,
+    // The number of members started, counting against the member limit.
+    members: u64,
+    // Whether the current member is yet to be counted.
+    next_member: bool,
+    done: bool,
+}
+
+impl<R: Read> ParMultiGzDecoder<R> {
+    /// Creates a new decoder from the given reader, with the default
+    /// configuration of [`ParBuilder::new`].
+    pub fn new(r: R) -> ParMultiGzDecoder<R> {
+        ParBuilder::new().read_multi_gz(r)
+    }
+}
+
+impl<R> ParMultiGzDecoder<R> {
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded). The
+    /// limits must be set before the first read.
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.workers.shared.lock().limits = limits;
+        self.members = 1;
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Acquires a mutable reference to the underlying stream.
+    ///
+    /// Note that mutation of the stream may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Stops the threads and returns the underlying stream.
+    ///
+    /// The input is read ahead of the output, so the stream may have been
+    /// read further than the data which was decompressed.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> ParMultiGzDecoder<R> {
+    fn read_input(&mut self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; INPUT_CHUNK];
+        loop {
+            match self.inner.read(&mut buf) {
+                Ok(n) => {
+                    buf.truncate(n);
+                    if n < INPUT_CHUNK / 2 {
+                        buf.shrink_to_fit();
+                    }
+                    return Ok(buf);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ParMultiGzDecoder<R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if into.is_empty() || self.done {
+            return Ok(0);
+        }
+        if self.workers.handles.is_empty() {
+            self.workers.start(self.threads);
+        }
+        let shared = self.workers.shared.clone();
+        let mut state = shared.lock();
+        loop {
+            let current = state.current;
+            if current == state.input_end && state.eof {
+                self.done = true;
+                return Ok(0);
+            }
+            if current < state.input_end && self.next_member {
+                self.next_member = false;
+                if let Err(err) = state.limits.check_members(self.members) {
+                    self.done = true;
+                    return Err(err.into());
+                }
+                self.members += 1;
+            }
+
+            let State {
+                jobs,
+                buffered,
+                max_buffered,
+                limits,
+                input_end,
+                ..
+            } = &mut *state;
+            let job = jobs.get_mut(&current).unwrap();
+            if let Some(chunk) = job.output.front() {
+                let mut n = cmp::min(chunk.len() - job.read, into.len());
+                if let Some(max) = limits.get_max_output() {
+                    let left = max.saturating_sub(self.total_out);
+                    if left == 0 {
+                        self.done = true;
+                        let msg = "decompressed data exceeds the output limit";
+                        return Err(
+                            DecompressError::new(ErrorKind::LimitExceeded, Some(msg)).into()
+                        );
+                    }
+                    n = n.min(usize::try_from(left).unwrap_or(usize::MAX));
+                }
+                into[..n].copy_from_slice(&chunk[job.read..job.read + n]);
+                job.read += n;
+                if job.read == chunk.len() {
+                    job.output.pop_front();
+                    job.read = 0;
+                }
+                let unblocked = (job.buffered >= JOB_OUTPUT && job.buffered - n < JOB_OUTPUT)
+                    || (*buffered >= *max_buffered && *buffered - n < *max_buffered);
+                job.buffered -= n;
+                *buffered -= n;
+                self.total_out += n as u64;
+                if unblocked {
+                    state.unpark();
+                    shared.cond.notify_all();
+                }
+                return Ok(n);
+            }
+
+            let mut end = None;
+            let wants_input = match &mut job.run {
+                Run::Done(Ok(e)) => {
+                    end = Some(*e);
+                    false
+                }
+                Run::Done(Err(_)) => {
+                    self.done = true;
+                    return match mem::replace(&mut job.run, Run::Running) {
+                        Run::Done(Err(err)) => Err(err),
+                        _ => unreachable!(),
+                    };
+                }
+                Run::Parked(progress) => progress.pos >= *input_end,
+                _ => false,
+            };
+            if let Some(end) = end {
+                state.advance(end);
+                self.next_member = true;
+                shared.cond.notify_all();
+                continue;
+            }
+            let read_ahead = state.input_end - state.input_start();
+            if !state.eof && (read_ahead < self.read_ahead || wants_input) {
+                drop(state);
+                let chunk = self.read_input()?;
+                state = shared.lock();
+                state.push_input(chunk);
+                shared.cond.notify_all();
+            } else {
+                state = shared.cond.wait(state).unwrap();
+            }
+        }
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for ParMultiGzDecoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParMultiGzDecoder")
+            .field("inner", &self.inner)
+            .field("threads", &self.threads)
+            .field("total_out", &self.total_out)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::MultiGzDecoder;
+    use crate::write::{GzEncoder, ParGzEncoder};
+    use crate::Compression;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(data).unwrap();
+        e.finish().unwrap()
+    }
+
+    /// Some data which doesn't compress, made of 32-bit random numbers.
+    fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len + 4);
+        while data.len() < len {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            data.extend_from_slice(&seed.to_le_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+
+    fn decode(builder: ParBuilder, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        builder
+            .read_multi_gz(compressed)
+            .read_to_end(&mut out)
+            .map(|_| out)
+    }
+
+    fn members(count: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut data = Vec::new();
+        let mut compressed = Vec::new();
+        for i in 0..count {
+            let member = format!("member {} ", i).repeat(i * 500);
+            compressed.extend(gzip(member.as_bytes()));
+            data.extend(member.into_bytes());
+        }
+        (data, compressed)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (data, compressed) = members(100);
+        for threads in [1, 2, 4] {
+            let builder = ParBuilder::new().threads(threads);
+            assert_eq!(decode(builder, &compressed).unwrap(), data);
+        }
+        assert_eq!(decode(ParBuilder::new(), &[]).unwrap(), b"");
+
+        let mut out = Vec::new();
+        MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn bounded_buffers() {
+        // Members which expand a lot, so that jobs have to wait for theirs to
+        // be read.
+        let member = gzip(&vec![7; 4 * 1024 * 1024]);
+        let compressed = member.repeat(6);
+        let out = decode(ParBuilder::new().threads(3), &compressed).unwrap();
+        assert_eq!(out.len(), 24 * 1024 * 1024);
+        assert!(out.iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn headers_in_data() {
+        // Stored data which looks like gzip members, including a whole one.
+        let mut data = noise(300_000, 1);
+        let fake = gzip(b"fake");
+        for at in [1000, 100_000, 250_000] {
+            data[at..at + fake.len()].copy_from_slice(&fake);
+        }
+        let mut e = GzEncoder::new(Vec::new(), Compression::none());
+        e.write_all(&data).unwrap();
+        let compressed = [e.finish().unwrap(), gzip(b"end")].concat();
+
+        let out = decode(ParBuilder::new().threads(4), &compressed).unwrap();
+        assert_eq!(out, [&data[..], b"end"].concat());
+    }
+
+    #[test]
+    fn par_encoder_members() {
+        let data = noise(1_000_000, 2);
+        let mut compressed = Vec::new();
+        for part in data.chunks(300_000) {
+            let mut e = ParGzEncoder::new(Vec::new(), Compression::fast());
+            e.write_all(part).unwrap();
+            compressed.extend(e.finish().unwrap());
+        }
+        assert_eq!(decode(ParBuilder::new(), &compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn corrupt_members() {
+        let (_, compressed) = members(10);
+
+        // The CRC of the last member.
+        let mut corrupt = compressed.clone();
+        let len = corrupt.len();
+        corrupt[len - 8] ^= 1;
+        assert!(decode(ParBuilder::new(), &corrupt).is_err());
+
+        // Its size.
+        let mut corrupt = compressed.clone();
+        corrupt[len - 4] ^= 1;
+        assert!(decode(ParBuilder::new(), &corrupt).is_err());
+
+        let truncated = &compressed[..len - 1];
+        assert!(decode(ParBuilder::new(), truncated).is_err());
+
+        let trailing = [&compressed[..], b"garbage"].concat();
+        assert!(decode(ParBuilder::new(), &trailing).is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let (data, compressed) = members(10);
+
+        let mut d = ParMultiGzDecoder::new(&compressed[..]);
+        d.set_limits(DecompressLimits::new().max_output(data.len() as u64 - 1));
+        let mut out = Vec::new();
+        let err = d.read_to_end(&mut out).unwrap_err();
+        assert_eq!(out, data[..data.len() - 1]);
+        let err = err.get_ref().unwrap().downcast_ref::<DecompressError>();
+        assert_eq!(err.unwrap().kind(), ErrorKind::LimitExceeded);
+
+        let mut d = ParMultiGzDecoder::new(&compressed[..]);
+        d.set_limits(DecompressLimits::new().max_members(9));
+        assert!(d.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn input_of_a_long_member_is_freed() {
+        let data = noise(12 << 20, 7);
+        let mut e = GzEncoder::new(Vec::new(), Compression::none());
+        e.write_all(&data).unwrap();
+        let compressed = e.finish().unwrap();
+
+        let mut d = ParBuilder::new().threads(1).read_multi_gz(&compressed[..]);
+        let mut out = vec![0; 8 << 20];
+        d.read_exact(&mut out).unwrap();
+        let held = {
+            let state = d.workers.shared.lock();
+            state.input_end - state.input_start()
+        };
+        assert!(held <= READ_AHEAD + INPUT_CHUNK as u64, "{}", held);
+        d.read_to_end(&mut out).unwrap();
+        assert!(out == data);
+    }
+}
@@ -0,0 +1,732 @@
+//! Encoders which compress blocks of their input on multiple threads.
+//!
+//! The input is split into blocks which are compressed independently, each
+//! with the last 32 KiB of the previous block as a dictionary so that matches
+//! can still reach back across block boundaries. Every block but the last one
+//! ends with a sync flush, which aligns the deflate stream to a byte boundary,
+//! so the compressed blocks can simply be concatenated into a single stream.
+//! The checksums of the blocks are combined into the one of the whole stream,
+//! which any decoder can read like the output of the single-threaded encoders.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::thread;
+
+use super::{ParBuilder, Pool, WINDOW_SIZE};
+use crate::crc::{self, Crc};
+use crate::gz::GzBuilder;
+use crate::mem::CompressError;
+use crate::{Compress, Compression, FlushCompress, Format, Status};
+
+pub fn gz_encoder<W: Write>(w: W, level: Compression, config: ParBuilder) -> ParGzEncoder<W> {
+    let header = GzBuilder::new().into_header(level);
+    ParGzEncoder {
+        inner: ParWriter::new(w, Format::Gzip, level, header, config),
+    }
+}
+
+pub fn zlib_encoder<W: Write>(w: W, level: Compression, config: ParBuilder) -> ParZlibEncoder<W> {
+    let header = zlib_header(level);
+    ParZlibEncoder {
+        inner: ParWriter::new(w, Format::Zlib, level, header, config),
+    }
+}
+
+pub fn deflate_encoder<W: Write>(
+    w: W,
+    level: Compression,
+    config: ParBuilder,
+) -> ParDeflateEncoder<W> {
+    ParDeflateEncoder {
+        inner: ParWriter::new(w, Format::Deflate, level, Vec::new(), config),
+    }
+}
+
+/// Returns the zlib header which the backend writes for `level`, the one of
+/// [`ZlibEncoder`](crate::write::ZlibEncoder).
+fn zlib_header(level: Compression) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    Compress::new(level, true)
+        .compress_vec(&[], &mut header, FlushCompress::Finish)
+        .expect("compressing nothing can't fail");
+    header.truncate(2);
+    header
+}
+
+const ADLER_MOD: u32 = 65521;
+
+/// Combines the Adler-32 checksums of two consecutive pieces of data, the
+/// second one being `len2` bytes long, like zlib's `adler32_combine`.
+fn adler32_combine(adler1: u32, adler2: u32, len2: u64) -> u32 {
+    let rem = (len2 % u64::from(ADLER_MOD)) as u32;
+    let mut sum1 = adler1 & 0xffff;
+    let mut sum2 = (rem * sum1) % ADLER_MOD;
+    sum1 += (adler2 & 0xffff) + ADLER_MOD - 1;
+    sum2 += (adler1 >> 16) + (adler2 >> 16) + ADLER_MOD - rem;
+    if sum1 >= ADLER_MOD {
+        sum1 -= ADLER_MOD;
+    }
+    if sum1 >= ADLER_MOD {
+        sum1 -= ADLER_MOD;
+    }
+    if sum2 >= ADLER_MOD << 1 {
+        sum2 -= ADLER_MOD << 1;
+    }
+    if sum2 >= ADLER_MOD {
+        sum2 -= ADLER_MOD;
+    }
+    sum1 | (sum2 << 16)
+}
+
+/// The checksum of a stream, or of a block of it.
+#[derive(Clone, Debug)]
+enum Check {
+    Crc(Crc),
+    Adler(u32),
+    None,
+}
+
+impl Check {
+    fn new(format: Format) -> Check {
+        match format {
+            Format::Gzip => Check::Crc(Crc::new()),
+            Format::Zlib => Check::Adler(1),
+            Format::Deflate => Check::None,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Check::Crc(crc) => crc.update(data),
+            Check::Adler(adler) => *adler = crc::adler32(*adler, data),
+            Check::None => {}
+        }
+    }
+
+    /// Appends the checksum of a block of `len` bytes.
+    fn combine(&mut self, block: &Check, len: u64) {
+        match (self, block) {
+            (Check::Crc(crc), Check::Crc(other)) => crc.combine(other),
+            (Check::Adler(adler), Check::Adler(other)) => {
+                *adler = adler32_combine(*adler, *other, len);
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns the trailer of the stream.
+    fn trailer(&self) -> Vec<u8> {
+        match self {
+            Check::Crc(crc) => {
+                let mut trailer = crc.sum().to_le_bytes().to_vec();
+                trailer.extend_from_slice(&crc.amount().to_le_bytes());
+                trailer
+            }
+            Check::Adler(adler) => adler.to_be_bytes().to_vec(),
+            Check::None => Vec::new(),
+        }
+    }
+}
+
+/// A block of input for a worker.
+struct Job {
+    index: u64,
+    dictionary: Vec<u8>,
+    data: Vec<u8>,
+    last: bool,
+}
+
+/// A compressed block, along with the checksum of its input.
+struct Block {
+    index: u64,
+    data: Vec<u8>,
+    check: Check,
+    len: u64,
+}
+
+/// Compresses the block of a job into a piece of a raw deflate stream.
+fn compress_block(
+    compress: &mut Compress,
+    format: Format,
+    job: Job,
+) -> Result<Block, CompressError> {
+    compress.reset();
+    if !job.dictionary.is_empty() {
+        compress.set_dictionary(&job.dictionary)?;
+    }
+    let flush = if job.last {
+        FlushCompress::Finish
+    } else {
+        FlushCompress::Sync
+    };
+    let mut input = &job.data[..];
+    let mut output = Vec::with_capacity(crate::compress_bound(input.len(), Format::Deflate) + 16);
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(4096);
+        }
+        let before = compress.total_in();
+        let status = compress.compress_vec(input, &mut output, flush)?;
+        input = &input[(compress.total_in() - before) as usize..];
+        let done = match status {
+            Status::StreamEnd => true,
+            _ => !job.last && input.is_empty() && output.len() < output.capacity(),
+        };
+        if done {
+            break;
+        }
+    }
+
+    let mut check = Check::new(format);
+    check.update(&job.data);
+    Ok(Block {
+        index: job.index,
+        data: output,
+        check,
+        len: job.data.len() as u64,
+    })
+}
+
+/// The engine of the parallel encoders, which hands out the blocks to the
+/// workers and writes the compressed blocks in order.
+struct ParWriter<W: Write> {
+    obj: Option<W>,
+    format: Format,
+    level: Compression,
+    config: ParBuilder,
+    pool: Option<Pool<Job, Block>>,
+    // The data of the block which is being filled.
+    block: Vec<u8>,
+    // The end of the data of the previous block.
+    dictionary: Vec<u8>,
+    next_index: u64,
+    next_write: u64,
+    done: BTreeMap<u64, Block>,
+    header: Vec<u8>,
+    // The compressed data which is ready to be written out.
+    buf: Vec<u8>,
+    check: Check,
+    // The error of a block which failed to compress. The stream can't be
+    // completed without the block, so it's returned by every later call.
+    failed: Option<CompressError>,
+    final_submitted: bool,
+    finished: bool,
+    total_in: u64,
+    total_out: u64,
+}
+
+impl<W: Write> ParWriter<W> {
+    fn new(
+        w: W,
+        format: Format,
+        level: Compression,
+        header: Vec<u8>,
+        config: ParBuilder,
+    ) -> ParWriter<W> {
+        ParWriter {
+            obj: Some(w),
+            format,
+            level,
+            config,
+            pool: None,
+            block: Vec::new(),
+            dictionary: Vec::new(),
+            next_index: 0,
+            next_write: 0,
+            done: BTreeMap::new(),
+            header,
+            buf: Vec::new(),
+            check: Check::new(format),
+            failed: None,
+            final_submitted: false,
+            finished: false,
+            total_in: 0,
+            total_out: 0,
+        }
+    }
+
+    fn get_ref(&self) -> &W {
+        self.obj.as_ref().unwrap()
+    }
+
+    fn get_mut(&mut self) -> &mut W {
+        self.obj.as_mut().unwrap()
+    }
+
+    /// Writes out the compressed data which is ready, keeping what wasn't
+    /// written if the writer fails.
+    fn dump(&mut self) -> io::Result<()> {
+        while !self.buf.is_empty() {
+            let n = self.obj.as_mut().unwrap().write(&self.buf)?;
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.buf.drain(..n);
+            self.total_out += n as u64;
+        }
+        Ok(())
+    }
+
+    /// Returns the error of a block which failed to compress, if any.
+    fn check_failed(&self) -> io::Result<()> {
+        match &self.failed {
+            Some(err) => Err(err.clone().into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Hands the current block to the workers, waiting for earlier blocks if
+    /// too many are in flight.
+    fn submit(&mut self, last: bool) -> io::Result<()> {
+        let max_in_flight = 2 * self.config.threads as u64;
+        while self.next_index - self.next_write - self.done.len() as u64 >= max_in_flight {
+            self.receive()?;
+        }
+
+        let data = std::mem::take(&mut self.block);
+        let start = data.len().saturating_sub(WINDOW_SIZE);
+        let dictionary = std::mem::replace(&mut self.dictionary, data[start..].to_vec());
+        let job = Job {
+            index: self.next_index,
+            dictionary,
+            data,
+            last,
+        };
+        self.next_index += 1;
+
+        let (config, format, level) = (self.config, self.format, self.level);
+        let pool = self.pool.get_or_insert_with(|| {
+            Pool::new(config.threads, level, move |compress, job| {
+                compress_block(compress, format, job)
+            })
+        });
+        pool.send(job)
+    }
+
+    /// Waits for the next compressed block, and writes out the blocks which
+    /// are next in order.
+    fn receive(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        let block = match self.pool.as_ref().unwrap().recv()? {
+            Ok(block) => block,
+            Err(err) => {
+                self.failed = Some(err.clone());
+                return Err(err.into());
+            }
+        };
+        self.done.insert(block.index, block);
+
+        self.buf.append(&mut self.header);
+        while let Some(block) = self.done.remove(&self.next_write) {
+            self.buf.extend_from_slice(&block.data);
+            self.check.combine(&block.check, block.len);
+            self.next_write += 1;
+        }
+        self.dump()
+    }
+
+    /// Waits until all submitted blocks were written out.
+    fn drain(&mut self) -> io::Result<()> {
+        while self.next_write < self.next_index {
+            self.receive()?;
+        }
+        self.dump()
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        // The last block is only submitted once, even if writing it out
+        // fails and this is called again.
+        if !self.final_submitted {
+            self.submit(true)?;
+            self.final_submitted = true;
+        }
+        if !self.finished {
+            self.drain()?;
+            let trailer = self.check.trailer();
+            self.buf.extend_from_slice(&trailer);
+            self.finished = true;
+            self.pool = None;
+        }
+        self.dump()?;
+        self.get_mut().flush()
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.obj.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for ParWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.final_submitted {
+            return Err(crate::mem::write_after_finish());
+        }
+        self.check_failed()?;
+        // A full block is only submitted with the next data, so that none of
+        // `buf` is taken if that fails.
+        if self.block.len() == self.config.block_size {
+            self.submit(false)?;
+        }
+        let n = cmp::min(buf.len(), self.config.block_size - self.block.len());
+        if self.block.capacity() == 0 {
+            self.block.reserve_exact(self.config.block_size);
+        }
+        self.block.extend_from_slice(&buf[..n]);
+        self.total_in += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check_failed()?;
+        if !self.final_submitted && !self.block.is_empty() {
+            self.submit(false)?;
+        }
+        self.drain()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for ParWriter<W> {
+    fn drop(&mut self) {
+        if self.obj.is_some() && self.failed.is_none() && !thread::panicking() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+impl<W: Write + fmt::Debug> fmt::Debug for ParWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParWriter")
+            .field("obj", &self.obj)
+            .field("format", &self.format)
+            .field("config", &self.config)
+            .field("total_in", &self.total_in)
+            .field("total_out", &self.total_out)
+            .finish()
+    }
+}
+
+/// Defines an encoder of one format on top of [`ParWriter`], with the given
+/// docs for the type and for its `new` constructor, which creates it with the
+/// given method of [`ParBuilder`].
+macro_rules! par_encoder {
+    ($(#[$attr:meta])* $name:ident, $(#[$new_attr:meta])* $builder:ident) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name<W: Write> {
+            inner: ParWriter<W>,
+        }
+
+        impl<W: Write> $name<W> {
+            $(#[$new_attr])*
+            pub fn new(w: W, level: Compression) -> $name<W> {
+                ParBuilder::new().$builder(w, level)
+            }
+
+            /// Acquires a reference to the underlying writer.
+            pub fn get_ref(&self) -> &W {
+                self.inner.get_ref()
+            }
+
+            /// Acquires a mutable reference to the underlying writer.
+            ///
+            /// Note that mutating the output/input state of the stream may corrupt
+            /// this object, so care must be taken when using this method.
+            pub fn get_mut(&mut self) -> &mut W {
+                self.inner.get_mut()
+            }
+
+            /// Returns the number of bytes which were written to this encoder.
+            pub fn total_in(&self) -> u64 {
+                self.inner.total_in
+            }
+
+            /// Returns the number of compressed bytes which were written to the
+            /// underlying writer.
+            ///
+            /// Blocks are written once they were compressed, so this lags behind
+            /// until the stream is flushed or finished.
+            pub fn total_out(&self) -> u64 {
+                self.inner.total_out
+            }
+
+            /// Attempt to finish this output stream, writing out final chunks of data.
+            ///
+            /// This waits for all blocks to be compressed and stops the threads.
+            /// Once this function was called, further calls to `write` return an
+            /// error of kind [`ErrorKind::StreamFinished`](crate::ErrorKind::StreamFinished).
+            ///
+            /// # Errors
+            ///
+            /// This function will perform I/O to finish the stream, returning any
+            /// errors which happen. It can be called again to retry, unless a
+            /// block failed to compress: the stream can't be completed then, so
+            /// every later call returns that error, and dropping the encoder
+            /// doesn't finish it.
+            pub fn try_finish(&mut self) -> io::Result<()> {
+                self.inner.try_finish()
+            }
+
+            /// Consumes this encoder, flushing the output stream.
+            ///
+            /// # Errors
+            ///
+            /// This function will perform I/O to complete this stream, and any I/O
+            /// errors which occur will be returned from this function.
+            pub fn finish(self) -> io::Result<W> {
+                self.inner.finish()
+            }
+        }
+
+        impl<W: Write> Write for $name<W> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.inner.write(buf)
+            }
+
+            /// Compresses the data written so far as a block of its own, ending it
+            /// with a sync flush, and waits for all blocks to be written out.
+            fn flush(&mut self) -> io::Result<()> {
+                self.inner.flush()
+            }
+        }
+    };
+}
+
+par_encoder!(
+    /// A gzip encoder which compresses on multiple threads.
+    ///
+    /// This structure exposes a [`Write`] interface like
+    /// [`write::GzEncoder`](crate::write::GzEncoder), and its output can be read by
+    /// any gzip decoder. See [`ParBuilder`] to configure the threads and blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::prelude::*;
+    /// use flate2::Compression;
+    /// use flate2::write::ParGzEncoder;
+    ///
+    /// let mut e = ParGzEncoder::new(Vec::new(), Compression::default());
+    /// e.write_all(&b"Hello World".repeat(100_000)).unwrap();
+    /// let compressed = e.finish().unwrap();
+    /// ```
+    ParGzEncoder,
+    /// Creates a new encoder which will use the given compression level and
+    /// one thread per CPU.
+    ///
+    /// The header is the same as the one of
+    /// [`GzEncoder::new`](crate::write::GzEncoder::new).
+    write_gz
+);
+
+par_encoder!(
+    /// A zlib encoder which compresses on multiple threads.
+    ///
+    /// This structure exposes a [`Write`] interface like
+    /// [`write::ZlibEncoder`](crate::write::ZlibEncoder), and its output can be read by
+    /// any zlib decoder. See [`ParBuilder`] to configure the threads and blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::prelude::*;
+    /// use flate2::Compression;
+    /// use flate2::write::ParZlibEncoder;
+    ///
+    /// let mut e = ParZlibEncoder::new(Vec::new(), Compression::default());
+    /// e.write_all(&b"Hello World".repeat(100_000)).unwrap();
+    /// let compressed = e.finish().unwrap();
+    /// ```
+    ParZlibEncoder,
+    /// Creates a new encoder which will use the given compression level and
+    /// one thread per CPU.
+    write_zlib
+);
+
+par_encoder!(
+    /// A raw deflate encoder which compresses on multiple threads.
+    ///
+    /// This structure exposes a [`Write`] interface like
+    /// [`write::DeflateEncoder`](crate::write::DeflateEncoder), and its output can be read by
+    /// any deflate decoder. See [`ParBuilder`] to configure the threads and blocks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::prelude::*;
+    /// use flate2::Compression;
+    /// use flate2::write::ParDeflateEncoder;
+    ///
+    /// let mut e = ParDeflateEncoder::new(Vec::new(), Compression::default());
+    /// e.write_all(&b"Hello World".repeat(100_000)).unwrap();
+    /// let compressed = e.finish().unwrap();
+    /// ```
+    ParDeflateEncoder,
+    /// Creates a new encoder which will use the given compression level and
+    /// one thread per CPU.
+    write_deflate
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read;
+
+    fn data() -> Vec<u8> {
+        let mut data = b"hello parallel world! ".repeat(20_000);
+        data.extend(crate::random_bytes().take(100_000));
+        data.extend(b"and some more text at the end".repeat(1000));
+        data
+    }
+
+    fn decode(format: Format, compressed: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        match format {
+            Format::Gzip => read::GzDecoder::new(compressed).read_to_end(&mut out),
+            Format::Zlib => read::ZlibDecoder::new(compressed).read_to_end(&mut out),
+            _ => read::DeflateDecoder::new(compressed).read_to_end(&mut out),
+        }
+        .unwrap();
+        out
+    }
+
+    fn encode(builder: ParBuilder, format: Format, level: Compression, data: &[u8]) -> Vec<u8> {
+        fn run<W: Write>(mut w: W, data: &[u8]) -> W {
+            for chunk in data.chunks(10_000) {
+                w.write_all(chunk).unwrap();
+            }
+            w
+        }
+        match format {
+            Format::Gzip => run(builder.write_gz(Vec::new(), level), data).finish(),
+            Format::Zlib => run(builder.write_zlib(Vec::new(), level), data).finish(),
+            _ => run(builder.write_deflate(Vec::new(), level), data).finish(),
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = data();
+        for format in [Format::Deflate, Format::Zlib, Format::Gzip] {
+            for threads in [1, 3] {
+                for level in [
+                    Compression::none(),
+                    Compression::fast(),
+                    Compression::best(),
+                ] {
+                    let builder = ParBuilder::new().threads(threads).block_size(WINDOW_SIZE);
+                    let compressed = encode(builder, format, level, &data);
+                    assert_eq!(decode(format, &compressed), data);
+                }
+            }
+            assert_eq!(
+                decode(
+                    format,
+                    &encode(ParBuilder::new(), format, Compression::default(), &[])
+                ),
+                b""
+            );
+        }
+    }
+
+    #[test]
+    fn blocks_share_the_window() {
+        // Without the previous block as a dictionary, every block would store
+        // the random bytes again.
+        let random = crate::random_bytes().take(20_000).collect::<Vec<_>>();
+        let data = random.repeat(20);
+        let builder = ParBuilder::new().threads(2).block_size(WINDOW_SIZE);
+        let compressed = encode(builder, Format::Deflate, Compression::default(), &data);
+        assert!(compressed.len() < 2 * random.len(), "{}", compressed.len());
+        assert_eq!(decode(Format::Deflate, &compressed), data);
+    }
+
+    #[test]
+    fn flush_and_drop() {
+        let data = data();
+        let mut e = ParGzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data[..1000]).unwrap();
+        e.flush().unwrap();
+        assert_eq!(e.total_in(), 1000);
+        assert_eq!(e.total_out(), e.get_ref().len() as u64);
+        let mut d = read::GzDecoder::new(&e.get_ref()[..]);
+        let mut out = vec![0; 1000];
+        d.read_exact(&mut out).unwrap();
+        assert_eq!(out, &data[..1000]);
+
+        let mut out = Vec::new();
+        {
+            let mut e = ParZlibEncoder::new(&mut out, Compression::default());
+            e.write_all(&data).unwrap();
+        }
+        assert_eq!(decode(Format::Zlib, &out), data);
+    }
+
+    #[test]
+    fn retry_finish() {
+        let data = data();
+        let builder = ParBuilder::new().threads(2).block_size(WINDOW_SIZE);
+        let mut e = builder.write_gz(crate::FlakyWriter::default(), Compression::fast());
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            if let Ok(n) = e.write(rest) {
+                rest = &rest[n..];
+            }
+        }
+        while e.try_finish().is_err() {}
+        crate::assert_write_after_finish(e.write(b"more").unwrap_err());
+        assert_eq!(e.total_in(), data.len() as u64);
+        assert_eq!(e.total_out(), e.get_ref().data.len() as u64);
+        assert_eq!(decode(Format::Gzip, &e.finish().unwrap().data), data);
+    }
+
+    #[test]
+    fn failed_block_poisons_the_encoder() {
+        let data = data();
+        let builder = ParBuilder::new().threads(2).block_size(WINDOW_SIZE);
+        let mut e = builder.write_gz(Vec::new(), Compression::fast());
+        e.inner.pool = Some(Pool::new(2, Compression::fast(), |compress, job: Job| {
+            if job.index == 1 {
+                let kind = crate::ErrorKind::Other;
+                return Err(CompressError::new(kind, Some("failed block")));
+            }
+            compress_block(compress, Format::Gzip, job)
+        }));
+        let err = data
+            .chunks(10_000)
+            .find_map(|chunk| e.write_all(chunk).err())
+            .unwrap_or_else(|| e.flush().unwrap_err());
+        assert!(err.to_string().contains("failed block"), "{}", err);
+        for err in [
+            e.write(b"more").unwrap_err(),
+            e.flush().unwrap_err(),
+            e.try_finish().unwrap_err(),
+        ] {
+            assert!(err.to_string().contains("failed block"), "{}", err);
+        }
+        // Dropping the encoder doesn't wait for the block which never comes.
+        drop(e);
+    }
+
+    #[test]
+    fn zlib_header_and_adler() {
+        assert_eq!(zlib_header(Compression::fast()), [0x78, 0x01]);
+        assert_eq!(zlib_header(Compression::default()), [0x78, 0x9c]);
+        assert_eq!(zlib_header(Compression::best()), [0x78, 0xda]);
+
+        let data = data();
+        let (a, b) = data.split_at(123_456);
+        let (adler_a, adler_b) = (crc::adler32(1, a), crc::adler32(1, b));
+        let combined = adler32_combine(adler_a, adler_b, b.len() as u64);
+        assert_eq!(combined, crc::adler32(1, &data));
+        assert_eq!(adler32_combine(adler_a, 1, 0), adler_a);
+        // The checksum of "Wikipedia", from its article on Adler-32.
+        assert_eq!(crc::adler32(1, b"Wikipedia"), 0x11e60398);
+    }
+}
@@ -3,7 +3,8 @@ use std::io::prelude::*;
 use std::mem;
 
 use crate::{
-    Compress, CompressError, Decompress, DecompressError, FlushCompress, FlushDecompress, Status,
+    Compress, CompressError, Compression, Decompress, DecompressError, FlushCompress,
+    FlushDecompress, Status,
 };
 
 #[derive(Debug)]
@@ -152,21 +153,48 @@ where
             // If we haven't read any data and we have hit EOF, then the
             // deflate stream is incomplete.
             Ok(Status::Ok | Status::BufError) if read == 0 && eof && !dst.is_empty() => {
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "incomplete deflate stream",
-                ));
+                return Err(DecompressError::truncated(data.total_in(), data.total_out()).into());
             }
             Ok(Status::Ok | Status::BufError | Status::StreamEnd) => return Ok(read),
 
-            Err(..) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "corrupt deflate stream",
-                ))
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// Reads from an encoder implementing `Read` which was asked to change its
+// compression level to `pending_level`. The level is changed once the data
+// compressed with the previous level has been flushed and read.
+pub fn read_encoder<R: BufRead>(
+    obj: &mut R,
+    data: &mut Compress,
+    pending_level: &mut Option<Compression>,
+    dst: &mut [u8],
+) -> io::Result<usize> {
+    if let Some(level) = pending_level.take() {
+        if data.total_in() == 0 {
+            // Nothing was compressed with the previous level yet.
+            data.set_level(level)?;
+        } else if dst.is_empty() {
+            *pending_level = Some(level);
+        } else if !obj.fill_buf()?.is_empty() {
+            // The level only needs to be changed if there's data left to
+            // compress with it.
+            let before = data.total_out();
+            data.compress(&[], dst, FlushCompress::Sync)?;
+            let read = (data.total_out() - before) as usize;
+            if read == dst.len() {
+                // The flush may not be complete yet.
+                *pending_level = Some(level);
+                return Ok(read);
+            }
+            data.set_level(level)?;
+            if read > 0 {
+                return Ok(read);
             }
         }
     }
+    read(obj, data, dst)
 }
 
 impl<W: Write, D: Ops> Writer<W, D> {
@@ -240,10 +268,7 @@ impl<W: Write, D: Ops> Writer<W, D> {
                 Ok(st) => match st {
                     Status::Ok | Status::BufError | Status::StreamEnd => Ok((written, st)),
                 },
-                Err(..) => Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "corrupt deflate stream",
-                )),
+                Err(e) => Err(e.into()),
             };
         }
     }
@@ -260,14 +285,10 @@ impl<W: Write, D: Ops> Writer<W, D> {
         }
         Ok(())
     }
-}
-
-impl<W: Write, D: Ops> Write for Writer<W, D> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.write_with_status(buf).map(|res| res.0)
-    }
 
-    fn flush(&mut self) -> io::Result<()> {
+    // Compresses or decompresses all buffered input and writes all resulting
+    // output to the inner writer, without flushing the inner writer itself.
+    fn flush_data(&mut self) -> io::Result<()> {
         self.data
             .run_vec(&[], &mut self.buf, Flush::sync())
             .map_err(Into::into)?;
@@ -287,7 +308,26 @@ impl<W: Write, D: Ops> Write for Writer<W, D> {
                 break;
             }
         }
+        Ok(())
+    }
+}
+
+impl<W: Write> Writer<W, Compress> {
+    // Changes the compression level of all data written from now on, after
+    // compressing the data written so far with the previous level.
+    pub fn set_level(&mut self, level: Compression) -> io::Result<()> {
+        self.flush_data()?;
+        self.data.set_level(level).map_err(Into::into)
+    }
+}
 
+impl<W: Write, D: Ops> Write for Writer<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_with_status(buf).map(|res| res.0)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_data()?;
         self.obj.as_mut().unwrap().flush()
     }
 }
@@ -3,7 +3,7 @@ use std::io::prelude::*;
 use std::mem;
 
 use crate::zio;
-use crate::{Compress, Decompress};
+use crate::{Compress, Decompress, DecompressLimits};
 
 /// A ZLIB encoder, or compressor.
 ///
@@ -37,6 +37,7 @@ use crate::{Compress, Decompress};
 pub struct ZlibEncoder<R> {
     obj: R,
     data: Compress,
+    pending_level: Option<crate::Compression>,
 }
 
 impl<R: BufRead> ZlibEncoder<R> {
@@ -46,6 +47,7 @@ impl<R: BufRead> ZlibEncoder<R> {
         ZlibEncoder {
             obj: r,
             data: Compress::new(level, true),
+            pending_level: None,
         }
     }
 
@@ -55,6 +57,7 @@ impl<R: BufRead> ZlibEncoder<R> {
         ZlibEncoder {
             obj: r,
             data: compression,
+            pending_level: None,
         }
     }
 }
@@ -76,6 +79,15 @@ impl<R> ZlibEncoder<R> {
         mem::replace(&mut self.obj, r)
     }
 
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: crate::Compression) {
+        self.pending_level = Some(level);
+    }
+
     /// Acquires a reference to the underlying reader
     pub fn get_ref(&self) -> &R {
         &self.obj
@@ -113,7 +125,7 @@ impl<R> ZlibEncoder<R> {
 
 impl<R: BufRead> Read for ZlibEncoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        zio::read(&mut self.obj, &mut self.data, buf)
+        zio::read_encoder(&mut self.obj, &mut self.data, &mut self.pending_level, buf)
     }
 }
 
@@ -239,6 +251,14 @@ impl<R> ZlibDecoder<R> {
     pub fn total_out(&self) -> u64 {
         self.data.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.data.set_limits(limits);
+    }
 }
 
 impl<R: BufRead> Read for ZlibDecoder<R> {
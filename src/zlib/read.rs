@@ -3,7 +3,7 @@ use std::io::prelude::*;
 
 use super::bufread;
 use crate::bufreader::BufReader;
-use crate::Decompress;
+use crate::{Decompress, DecompressLimits};
 
 /// A ZLIB encoder, or compressor.
 ///
@@ -69,6 +69,15 @@ impl<R> ZlibEncoder<R> {
         self.inner.get_mut().reset(r)
     }
 
+    /// Changes the compression level used for the data read from now on.
+    ///
+    /// The data compressed so far is flushed with the previous level and
+    /// returned by the following reads before the new level takes effect. Any
+    /// error while changing the level is returned from those reads.
+    pub fn set_level(&mut self, level: crate::Compression) {
+        self.inner.set_level(level)
+    }
+
     /// Acquires a reference to the underlying stream
     pub fn get_ref(&self) -> &R {
         self.inner.get_ref().get_ref()
@@ -262,6 +271,14 @@ impl<R> ZlibDecoder<R> {
     pub fn total_out(&self) -> u64 {
         self.inner.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.set_limits(limits);
+    }
 }
 
 impl<R: Read> Read for ZlibDecoder<R> {
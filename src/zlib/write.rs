@@ -2,7 +2,7 @@ use std::io;
 use std::io::prelude::*;
 
 use crate::zio;
-use crate::{Compress, Decompress};
+use crate::{Compress, Decompress, DecompressLimits};
 
 /// A ZLIB encoder, or compressor.
 ///
@@ -86,6 +86,22 @@ impl<W: Write> ZlibEncoder<W> {
         Ok(self.inner.replace(w))
     }
 
+    /// Changes the compression level used for the data written from now on.
+    ///
+    /// All data written so far is compressed with the previous level and
+    /// written out to the underlying writer first, much like a call to
+    /// `flush` which doesn't flush the underlying writer itself.
+    ///
+    /// # Errors
+    ///
+    /// This function will perform I/O to write out the data compressed so
+    /// far, and any I/O errors which occur will be returned from this
+    /// function. An error is also returned if the backend doesn't support
+    /// the change.
+    pub fn set_level(&mut self, level: crate::Compression) -> io::Result<()> {
+        self.inner.set_level(level)
+    }
+
     /// Attempt to finish this output stream, writing out final chunks of data.
     ///
     /// Note that this function can only be used once data has finished being
@@ -268,7 +284,7 @@ impl<W: Write> ZlibDecoder<W> {
     /// errors which occur will be returned from this function.
     pub fn reset(&mut self, w: W) -> io::Result<W> {
         self.inner.finish()?;
-        self.inner.data = Decompress::new(true);
+        self.inner.data.reset(true);
         Ok(self.inner.replace(w))
     }
 
@@ -325,6 +341,14 @@ impl<W: Write> ZlibDecoder<W> {
     pub fn total_out(&self) -> u64 {
         self.inner.data.total_out()
     }
+
+    /// Sets the limits on the decompressed data, see [`DecompressLimits`].
+    ///
+    /// Once a limit is exceeded, decompression fails with an error of kind
+    /// [`ErrorKind::LimitExceeded`](crate::ErrorKind::LimitExceeded).
+    pub fn set_limits(&mut self, limits: DecompressLimits) {
+        self.inner.data.set_limits(limits);
+    }
 }
 
 impl<W: Write> Write for ZlibDecoder<W> {
@@ -0,0 +1,296 @@
+//! Validate that the I/O types work with a custom backend.
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use flate2::backend::{Backend, DeflateBackend, InflateBackend};
+use flate2::{
+    bufread, read, write, Compress, CompressError, Compression, Decompress, DecompressError,
+    ErrorKind, FlushCompress, FlushDecompress, Format, Status,
+};
+
+/// Forwards to the default backend and counts the calls.
+#[derive(Debug)]
+struct Counting<T> {
+    inner: T,
+    calls: Arc<AtomicUsize>,
+}
+
+impl<T> Counting<T> {
+    fn new(inner: T) -> (Counting<T>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = Counting {
+            inner,
+            calls: calls.clone(),
+        };
+        (backend, calls)
+    }
+}
+
+impl Backend for Counting<Compress> {
+    fn total_in(&self) -> u64 {
+        self.inner.total_in()
+    }
+
+    fn total_out(&self) -> u64 {
+        self.inner.total_out()
+    }
+}
+
+impl DeflateBackend for Counting<Compress> {
+    fn compress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushCompress,
+    ) -> Result<Status, CompressError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.inner.compress(input, output, flush)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+impl Backend for Counting<Decompress> {
+    fn total_in(&self) -> u64 {
+        self.inner.total_in()
+    }
+
+    fn total_out(&self) -> u64 {
+        self.inner.total_out()
+    }
+}
+
+impl InflateBackend for Counting<Decompress> {
+    fn decompress(
+        &mut self,
+        input: &[u8],
+        output: &mut [u8],
+        flush: FlushDecompress,
+    ) -> Result<Status, DecompressError> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.inner.decompress(input, output, flush)
+    }
+
+    fn reset(&mut self, zlib_header: bool) {
+        self.inner.reset(zlib_header);
+    }
+}
+
+fn counting_compress(zlib_header: bool) -> (Compress, Arc<AtomicUsize>) {
+    let (backend, calls) = Counting::new(Compress::new(Compression::default(), zlib_header));
+    (Compress::with_backend(backend), calls)
+}
+
+fn counting_decompress(zlib_header: bool) -> (Decompress, Arc<AtomicUsize>) {
+    let (backend, calls) = Counting::new(Decompress::new(zlib_header));
+    let format = if zlib_header {
+        Format::Zlib
+    } else {
+        Format::Deflate
+    };
+    (Decompress::with_backend(backend, format), calls)
+}
+
+fn data() -> Vec<u8> {
+    b"hello world, hello backend! ".repeat(1000)
+}
+
+#[test]
+fn deflate_and_zlib_with_custom_backend() {
+    let data = data();
+
+    let (compress, deflate_calls) = counting_compress(false);
+    let mut e = write::DeflateEncoder::new_with_compress(Vec::new(), compress);
+    e.write_all(&data).unwrap();
+    let deflate = e.finish().unwrap();
+    assert!(deflate_calls.load(Ordering::Relaxed) > 0);
+
+    let (decompress, calls) = counting_decompress(false);
+    let mut d = read::DeflateDecoder::new_with_decompress(&deflate[..], decompress);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    let (decompress, calls) = counting_decompress(false);
+    let mut d = write::DeflateDecoder::new_with_decompress(Vec::new(), decompress);
+    d.write_all(&deflate).unwrap();
+    assert_eq!(d.finish().unwrap(), data);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    let (compress, _) = counting_compress(true);
+    let mut e = read::ZlibEncoder::new_with_compress(&data[..], compress);
+    let mut zlib = Vec::new();
+    e.read_to_end(&mut zlib).unwrap();
+
+    let (decompress, calls) = counting_decompress(true);
+    let mut d = bufread::ZlibDecoder::new_with_decompress(&zlib[..], decompress);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    // The default backend is used alongside the custom ones.
+    let mut d = read::ZlibDecoder::new(&zlib[..]);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+}
+
+#[test]
+fn gzip_with_custom_backend() {
+    let data = data();
+
+    let (compress, calls) = counting_compress(false);
+    let mut e = write::GzEncoder::new_with_compress(Vec::new(), compress);
+    e.write_all(&data).unwrap();
+    let member = e.finish().unwrap();
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    let (compress, _) = counting_compress(false);
+    let mut e = read::GzEncoder::new_with_compress(&data[..], compress);
+    let mut other = Vec::new();
+    e.read_to_end(&mut other).unwrap();
+    assert_eq!(other, member);
+
+    let (decompress, calls) = counting_decompress(false);
+    let mut d = read::GzDecoder::new_with_decompress(&member[..], decompress);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    let mut multi = member.clone();
+    multi.extend_from_slice(&member);
+    let mut expected = data.clone();
+    expected.extend_from_slice(&data);
+
+    let (decompress, calls) = counting_decompress(false);
+    let mut d = bufread::MultiGzDecoder::new_with_decompress(&multi[..], decompress);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, expected);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+
+    let (decompress, calls) = counting_decompress(false);
+    let mut d = write::MultiGzDecoder::new_with_decompress(Vec::new(), decompress);
+    d.write_all(&multi).unwrap();
+    assert_eq!(d.finish().unwrap(), expected);
+    assert!(calls.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn reset_keeps_custom_gzip_backend() {
+    let data = data();
+    let mut e = write::GzEncoder::new(Vec::new(), Compression::default());
+    e.write_all(&data).unwrap();
+    let member = e.finish().unwrap();
+
+    let (backend, calls) = Counting::new(Decompress::new_gzip(15));
+    let mut d = Decompress::with_backend(backend, Format::Gzip);
+    for _ in 0..2 {
+        let before = calls.load(Ordering::Relaxed);
+        let mut out = Vec::with_capacity(data.len());
+        let status = d
+            .decompress_vec(&member, &mut out, FlushDecompress::Finish)
+            .unwrap();
+        assert_eq!(status, Status::StreamEnd);
+        assert_eq!(out, data);
+        assert!(calls.load(Ordering::Relaxed) > before);
+        d.reset(true);
+        assert_eq!(d.format(), Some(Format::Gzip));
+    }
+}
+
+#[test]
+fn optional_methods_are_unsupported() {
+    let (mut compress, _) = counting_compress(false);
+    let err = compress.set_dictionary(b"dictionary").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(
+        compress.try_clone().unwrap_err().kind(),
+        ErrorKind::Unsupported
+    );
+
+    let (mut decompress, _) = counting_decompress(false);
+    let err = decompress.set_dictionary(b"dictionary").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(decompress.format(), Some(Format::Deflate));
+}
+
+/// The built-in backends which are enabled, as functions creating a
+/// compressor and a decompressor for `format`.
+#[allow(clippy::type_complexity)]
+fn builtin_backends() -> Vec<(
+    &'static str,
+    fn(Format) -> Compress,
+    fn(Format) -> Decompress,
+)> {
+    #[allow(unused_mut)]
+    let mut backends: Vec<(_, fn(Format) -> Compress, fn(Format) -> Decompress)> = Vec::new();
+    #[cfg(feature = "miniz_oxide")]
+    backends.push((
+        "miniz_oxide",
+        |format| {
+            let config = Compress::builder().format(format);
+            Compress::with_backend(flate2::backend::MinizOxideDeflate::new(&config).unwrap())
+        },
+        |format| {
+            let config = Decompress::builder().format(format);
+            let backend = flate2::backend::MinizOxideInflate::new(&config).unwrap();
+            Decompress::with_backend(backend, format)
+        },
+    ));
+    #[cfg(feature = "zlib-rs")]
+    backends.push((
+        "zlib-rs",
+        |format| {
+            let config = Compress::builder().format(format);
+            Compress::with_backend(flate2::backend::ZlibRsDeflate::new(&config).unwrap())
+        },
+        |format| {
+            let config = Decompress::builder().format(format);
+            let backend = flate2::backend::ZlibRsInflate::new(&config).unwrap();
+            Decompress::with_backend(backend, format)
+        },
+    ));
+    #[cfg(feature = "any_c_zlib")]
+    backends.push((
+        "zlib",
+        |format| {
+            let config = Compress::builder().format(format);
+            Compress::with_backend(flate2::backend::ZlibDeflate::new(&config).unwrap())
+        },
+        |format| {
+            let config = Decompress::builder().format(format);
+            let backend = flate2::backend::ZlibInflate::new(&config).unwrap();
+            Decompress::with_backend(backend, format)
+        },
+    ));
+    backends
+}
+
+#[test]
+fn builtin_backends_are_interchangeable() {
+    let data = data();
+    for (name, compress, _) in builtin_backends() {
+        let mut e = write::ZlibEncoder::new_with_compress(Vec::new(), compress(Format::Zlib));
+        e.write_all(&data).unwrap();
+        let zlib = e.finish().unwrap();
+
+        for (other, _, decompress) in builtin_backends() {
+            let mut d = read::ZlibDecoder::new_with_decompress(&zlib[..], decompress(Format::Zlib));
+            let mut out = Vec::new();
+            d.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data, "{} to {}", name, other);
+        }
+
+        let mut d = read::ZlibDecoder::new(&zlib[..]);
+        let mut out = Vec::new();
+        d.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data, "{} to the default backend", name);
+    }
+}
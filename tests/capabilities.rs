@@ -1,9 +1,6 @@
 //! Validate that certain feature-gated functionality is still available.
-#[cfg(feature = "any_zlib")]
 use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn compress_new_with_window_bits_is_present_and_works() {
     let string = "hello world".as_bytes();
@@ -39,8 +36,6 @@ fn compress_new_with_window_bits_is_present_and_works() {
     assert_eq!(&decoded[..string.len()], string);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn decompress_new_gzip_window_bits_is_present_and_works() {
     let string = "hello world".as_bytes();
@@ -67,40 +62,30 @@ fn decompress_new_gzip_window_bits_is_present_and_works() {
     }
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 #[should_panic(expected = "window_bits must be within 9 ..= 15")]
 fn compress_new_with_window_bits_invalid_low() {
     let _ = Compress::new_with_window_bits(Compression::default(), true, 8);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 #[should_panic(expected = "window_bits must be within 9 ..= 15")]
 fn compress_new_with_window_bits_invalid_high() {
     let _ = Compress::new_with_window_bits(Compression::default(), true, 16);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 #[should_panic(expected = "window_bits must be within 9 ..= 15")]
 fn compress_new_gzip_invalid_low() {
     let _ = Compress::new_gzip(Compression::default(), 8);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 #[should_panic(expected = "window_bits must be within 9 ..= 15")]
 fn compress_new_gzip_invalid_high() {
     let _ = Compress::new_gzip(Compression::default(), 16);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn set_dictionary_with_zlib_header() {
     let string = "hello, hello!".as_bytes();
@@ -149,8 +134,6 @@ fn set_dictionary_with_zlib_header() {
     assert_eq!(&decoded[..decoder.total_out() as usize], string);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn set_dictionary_raw() {
     let string = "hello, hello!".as_bytes();
@@ -181,8 +164,6 @@ fn set_dictionary_raw() {
     assert_eq!(&decoded[..decoder.total_out() as usize], string);
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn compression_levels_are_effective() {
     let input = b"hello hello hello hello hello hello hello hello";
@@ -207,8 +188,6 @@ fn compression_levels_are_effective() {
     );
 }
 
-// Unsupported for `miniz_oxide`.
-#[cfg(feature = "any_zlib")]
 #[test]
 fn set_level_is_effective() {
     let input = b"hello hello hello hello hello hello hello hello";
@@ -238,3 +217,57 @@ fn set_level_is_effective() {
         encoded_none.len(),
     );
 }
+
+#[test]
+fn backend_reports_its_capabilities() {
+    let backend = flate2::backend_info();
+    assert!(!backend.name().is_empty());
+    assert_eq!(
+        backend.version().is_some(),
+        matches!(backend.name(), "zlib" | "zlib-ng")
+    );
+
+    let capabilities = backend.capabilities();
+    assert!(capabilities.window_bits());
+    assert!(capabilities.dictionary());
+    assert!(capabilities.gzip());
+    assert_eq!(
+        capabilities.mem_level(),
+        Compress::builder().mem_level(9).build().is_ok()
+    );
+    assert_eq!(
+        capabilities.try_clone(),
+        Compress::new(Compression::default(), true)
+            .try_clone()
+            .is_ok()
+    );
+    assert_eq!(
+        capabilities.prime(),
+        Compress::new(Compression::default(), false)
+            .prime(3, 0)
+            .is_ok()
+    );
+}
+
+#[test]
+fn block_boundaries_are_reported_if_supported() {
+    let mut encoded = Vec::with_capacity(1024);
+    let mut encoder = Compress::new(Compression::default(), false);
+    encoder
+        .compress_vec(b"first block", &mut encoded, FlushCompress::Block)
+        .unwrap();
+    encoder
+        .compress_vec(b"second block", &mut encoded, FlushCompress::Finish)
+        .unwrap();
+
+    let mut decoder = Decompress::new(false);
+    let mut decoded = Vec::with_capacity(1024);
+    decoder
+        .decompress_vec(&encoded, &mut decoded, FlushDecompress::Block)
+        .unwrap();
+    assert_eq!(decoded, b"first block");
+    assert_eq!(
+        decoder.position().is_block_boundary(),
+        flate2::backend_info().capabilities().block_boundaries()
+    );
+}
@@ -0,0 +1,159 @@
+//! Validate that the decoders enforce the decompression limits.
+use std::io;
+use std::io::prelude::*;
+
+use flate2::{
+    bufread, read, write, Compression, Decompress, DecompressError, DecompressLimits, ErrorKind,
+    FlushDecompress,
+};
+
+const SIZE: usize = 256 * 1024;
+
+fn zeros() -> Vec<u8> {
+    vec![0; SIZE]
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut e = write::GzEncoder::new(Vec::new(), Compression::best());
+    e.write_all(data).unwrap();
+    e.finish().unwrap()
+}
+
+fn limit_kind(err: io::Error) -> ErrorKind {
+    let err = err.get_ref().expect("a decompression error");
+    err.downcast_ref::<DecompressError>().unwrap().kind()
+}
+
+fn read_err(mut r: impl Read) -> ErrorKind {
+    limit_kind(r.read_to_end(&mut Vec::new()).unwrap_err())
+}
+
+fn write_err<W: Write>(mut w: W, data: &[u8]) -> ErrorKind {
+    limit_kind(w.write_all(data).and_then(|_| w.flush()).unwrap_err())
+}
+
+#[test]
+fn max_output() {
+    let data = zeros();
+    let limits = DecompressLimits::new().max_output(SIZE as u64 - 1);
+
+    let mut e = write::DeflateEncoder::new(Vec::new(), Compression::best());
+    e.write_all(&data).unwrap();
+    let deflate = e.finish().unwrap();
+    let mut e = write::ZlibEncoder::new(Vec::new(), Compression::best());
+    e.write_all(&data).unwrap();
+    let zlib = e.finish().unwrap();
+    let gz = gzip(&data);
+
+    let mut d = read::DeflateDecoder::new(&deflate[..]);
+    d.set_limits(limits);
+    assert_eq!(read_err(d), ErrorKind::LimitExceeded);
+    let mut d = bufread::ZlibDecoder::new(&zlib[..]);
+    d.set_limits(limits);
+    assert_eq!(read_err(d), ErrorKind::LimitExceeded);
+    let mut d = read::GzDecoder::new(&gz[..]);
+    d.set_limits(limits);
+    assert_eq!(read_err(d), ErrorKind::LimitExceeded);
+
+    let mut d = write::DeflateDecoder::new(Vec::new());
+    d.set_limits(limits);
+    assert_eq!(write_err(d, &deflate), ErrorKind::LimitExceeded);
+    let mut d = write::ZlibDecoder::new(Vec::new());
+    d.set_limits(limits);
+    assert_eq!(write_err(d, &zlib), ErrorKind::LimitExceeded);
+    let mut d = write::GzDecoder::new(Vec::new());
+    d.set_limits(limits);
+    assert_eq!(write_err(d, &gz), ErrorKind::LimitExceeded);
+
+    // Data right at the limit is fine.
+    let mut d = bufread::GzDecoder::new(&gz[..]);
+    d.set_limits(DecompressLimits::new().max_output(SIZE as u64));
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, data);
+}
+
+#[test]
+fn max_output_spans_members() {
+    let member = gzip(&zeros());
+    let multi = [&member[..], &member[..]].concat();
+    let limits = DecompressLimits::new().max_output(SIZE as u64 + 1);
+
+    let mut d = read::MultiGzDecoder::new(&multi[..]);
+    d.set_limits(limits);
+    assert_eq!(read_err(d), ErrorKind::LimitExceeded);
+
+    let mut d = write::MultiGzDecoder::new(Vec::new());
+    d.set_limits(limits);
+    assert_eq!(write_err(d, &multi), ErrorKind::LimitExceeded);
+}
+
+#[test]
+fn max_ratio() {
+    let gz = gzip(&zeros());
+
+    let mut d = bufread::MultiGzDecoder::new(&gz[..]);
+    d.set_limits(DecompressLimits::new().max_ratio(100));
+    assert_eq!(read_err(d), ErrorKind::LimitExceeded);
+
+    let mut d = write::GzDecoder::new(Vec::new());
+    d.set_limits(DecompressLimits::new().max_ratio(100));
+    assert_eq!(write_err(d, &gz), ErrorKind::LimitExceeded);
+
+    // Text of ordinary redundancy stays well below a generous ratio.
+    let text = b"hello world, hello limits! ".repeat(10_000);
+    let gz = gzip(&text);
+    let mut d = read::GzDecoder::new(&gz[..]);
+    d.set_limits(DecompressLimits::new().max_ratio(1_000_000));
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, text);
+}
+
+#[test]
+fn max_members() {
+    let member = gzip(b"member");
+    let multi = member.repeat(3);
+    let limits = DecompressLimits::new().max_members(2);
+
+    let mut d = read::MultiGzDecoder::new(&multi[..]);
+    d.set_limits(limits);
+    let mut out = Vec::new();
+    let err = d.read_to_end(&mut out).unwrap_err();
+    assert_eq!(limit_kind(err), ErrorKind::LimitExceeded);
+    assert_eq!(out, b"membermember");
+
+    let mut d = write::MultiGzDecoder::new(Vec::new());
+    d.set_limits(limits);
+    assert_eq!(write_err(d, &multi), ErrorKind::LimitExceeded);
+
+    let mut d = bufread::MultiGzDecoder::new(&multi[..2 * member.len()]);
+    d.set_limits(limits);
+    let mut out = Vec::new();
+    d.read_to_end(&mut out).unwrap();
+    assert_eq!(out, b"membermember");
+}
+
+#[test]
+fn decompress_limits() {
+    let mut e = write::ZlibEncoder::new(Vec::new(), Compression::best());
+    e.write_all(&zeros()).unwrap();
+    let zlib = e.finish().unwrap();
+
+    let mut d = Decompress::new(true);
+    d.set_limits(DecompressLimits::new().max_output(1000));
+    assert_eq!(d.limits().get_max_output(), Some(1000));
+    let mut out = vec![0; SIZE];
+    let err = d
+        .decompress(&zlib, &mut out, FlushDecompress::Finish)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+    assert_eq!(err.output_offset(), Some(1001));
+
+    // Resetting starts counting anew.
+    d.reset(true);
+    let mut out = vec![0; 1000];
+    d.decompress(&zlib, &mut out, FlushDecompress::None)
+        .unwrap();
+    assert_eq!(d.total_out(), 1000);
+}